@@ -0,0 +1,79 @@
+//! # Actor-to-Actor Calls
+//!
+//! This module contains the client through which an actor invokes operations on other
+//! actors running on the same host or lattice. The target actor is identified by its public
+//! key (the 56-character subject of its signed module, beginning with `M`), and the host
+//! delivers the call to the handler the target registered for the operation
+//!
+//! ```
+//! extern crate wascc_actor as actor;
+//! use actor::prelude::*;
+//! use serde_derive::{Deserialize, Serialize};
+//!
+//! const PRICING_ACTOR: &str = "MBCFOPM6JW2APJLXJD3Z5O4CN7CPYJ2B4FTKLJUR5YR5MITIU7HD3WD5";
+//!
+//! #[derive(Serialize)]
+//! struct QuoteRequest {
+//!     sku: String,
+//!     quantity: u32,
+//! }
+//!
+//! #[derive(Deserialize)]
+//! struct Quote {
+//!     total_cents: u64,
+//! }
+//!
+//! fn quote(sku: &str, quantity: u32) -> HandlerResult<Quote> {
+//!     let request = QuoteRequest { sku: sku.to_string(), quantity };
+//!     actors::actor(PRICING_ACTOR).call_typed("Quote", &request)
+//! }
+//! ```
+
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use wapc_guest::host_call;
+use wascc_codec::{deserialize, serialize};
+
+use crate::HandlerResult;
+
+/// The length of an actor's public key
+const ACTOR_KEY_LENGTH: usize = 56;
+
+/// A reference to another actor, through which its operations are invoked
+#[derive(Debug, Clone, PartialEq)]
+pub struct ActorRef {
+    public_key: String,
+}
+
+/// Creates a reference to the actor with the given public key
+pub fn actor(public_key: &str) -> ActorRef {
+    ActorRef {
+        public_key: public_key.to_string(),
+    }
+}
+
+impl ActorRef {
+    /// The public key of the target actor
+    pub fn public_key(&self) -> &str {
+        &self.public_key
+    }
+
+    /// Invokes an operation on the target actor with a raw payload, returning its raw reply
+    pub fn call(&self, operation: &str, payload: &[u8]) -> HandlerResult<Vec<u8>> {
+        if self.public_key.len() != ACTOR_KEY_LENGTH || !self.public_key.starts_with('M') {
+            return Err(format!("Not an actor public key: {}", self.public_key).into());
+        }
+        host_call("default", &self.public_key, operation, payload).map_err(|e| e.into())
+    }
+
+    /// Invokes an operation on the target actor, serializing the request and deserializing
+    /// the reply the same way `actor_handlers!` does for the target's handler
+    pub fn call_typed<Req, Resp>(&self, operation: &str, request: &Req) -> HandlerResult<Resp>
+    where
+        Req: Serialize,
+        Resp: DeserializeOwned,
+    {
+        self.call(operation, &serialize(request)?)
+            .and_then(|v| deserialize::<Resp>(v.as_ref()))
+    }
+}
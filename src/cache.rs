@@ -0,0 +1,286 @@
+//! # Cache
+//!
+//! This module contains the client through which actors use the currently bound
+//! `wascc:cache` capability provider. Unlike the durable `wascc:keyvalue` contract, cache
+//! entries may be evicted at any time and always carry a time to live, which lets hosts
+//! bind memcached-style backends. Entries can be tagged so related entries are evicted
+//! together, and the actor keeps hit and miss counts for each binding it uses
+//!
+//! ```
+//! extern crate wascc_actor as actor;
+//! use actor::prelude::*;
+//! use serde_derive::{Deserialize, Serialize};
+//!
+//! #[derive(Serialize, Deserialize)]
+//! struct Profile {
+//!     name: String,
+//! }
+//!
+//! fn profile(user_id: &str) -> HandlerResult<Profile> {
+//!     cache::default().get_or_compute(&format!("profile:{}", user_id), 60_000, || {
+//!         let name = keyvalue::default().get(&format!("user:{}:name", user_id))?;
+//!         Ok(Profile { name: name.unwrap_or_default() })
+//!     })
+//! }
+//!
+//! fn cache_orders(user_id: &str, orders: &Vec<String>) -> HandlerResult<()> {
+//!     let tag = format!("user:{}", user_id);
+//!     cache::default().set_tagged(&format!("orders:{}", user_id), orders, 30_000, &[&tag])
+//! }
+//!
+//! fn user_updated(user_id: &str) -> HandlerResult<u64> {
+//!     cache::default().evict_tag(&format!("user:{}", user_id))
+//! }
+//! ```
+
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use serde_derive::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::sync::RwLock;
+use wapc_guest::host_call;
+use wascc_codec::{deserialize, serialize};
+
+use crate::HandlerResult;
+
+/// The reserved capability ID for cache functionality
+pub const CAPID_CACHE: &str = "wascc:cache";
+
+/// Guest sends a `CacheKeyRequest` to the capability provider, receives a `CacheGetResponse` back
+pub const OP_CACHE_GET: &str = "CacheGet";
+/// Guest sends a `CacheSetRequest` to the capability provider, receives nothing back
+pub const OP_CACHE_SET: &str = "CacheSet";
+/// Guest sends a `CacheKeyRequest` to the capability provider, receives nothing back
+pub const OP_CACHE_DELETE: &str = "CacheDelete";
+/// Guest sends an `EvictTagRequest` to the capability provider, receives an
+/// `EvictTagResponse` back
+pub const OP_EVICT_TAG: &str = "EvictTag";
+
+lazy_static! {
+    static ref STATS: RwLock<HashMap<String, CacheStats>> = RwLock::new(HashMap::new());
+}
+
+/// A request naming a single entry
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CacheKeyRequest {
+    /// The key of the entry
+    pub key: String,
+}
+
+/// The provider's answer to a get
+#[derive(Debug, Clone, PartialEq, Default, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CacheGetResponse {
+    /// Indicates whether the entry was found
+    pub found: bool,
+    /// The value of the entry, if it was found
+    #[serde(with = "serde_bytes")]
+    #[serde(default)]
+    pub value: Vec<u8>,
+}
+
+/// A request to store an entry
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CacheSetRequest {
+    /// The key of the entry
+    pub key: String,
+    /// The value of the entry
+    #[serde(with = "serde_bytes")]
+    pub value: Vec<u8>,
+    /// The number of milliseconds after which the entry expires
+    pub ttl_ms: u64,
+    /// Tags by which the entry can be evicted along with related entries
+    #[serde(default)]
+    pub tags: Vec<String>,
+}
+
+/// A request to evict every entry with a tag
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct EvictTagRequest {
+    /// The tag
+    pub tag: String,
+}
+
+/// The provider's answer to an `EvictTagRequest`
+#[derive(Debug, Clone, PartialEq, Default, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct EvictTagResponse {
+    /// The number of entries evicted
+    pub evicted: u64,
+}
+
+/// The hits and misses of the gets made through a binding by this module instance
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct CacheStats {
+    /// The number of gets that found an entry
+    pub hits: u64,
+    /// The number of gets that found no entry
+    pub misses: u64,
+}
+
+impl CacheStats {
+    /// The fraction of gets that found an entry, or zero if there have been none
+    pub fn hit_ratio(&self) -> f64 {
+        let total = self.hits + self.misses;
+        if total == 0 {
+            0.0
+        } else {
+            self.hits as f64 / total as f64
+        }
+    }
+}
+
+/// An abstraction around a host runtime capability for a cache
+pub struct CacheHostBinding {
+    binding: String,
+}
+
+impl Default for CacheHostBinding {
+    fn default() -> Self {
+        CacheHostBinding {
+            binding: "default".to_string(),
+        }
+    }
+}
+
+/// Creates a named host binding for the cache capability
+pub fn host(binding: &str) -> CacheHostBinding {
+    CacheHostBinding {
+        binding: binding.to_string(),
+    }
+}
+
+/// Creates the default host binding for the cache capability
+pub fn default() -> CacheHostBinding {
+    CacheHostBinding::default()
+}
+
+impl CacheHostBinding {
+    /// Obtains the raw value of an entry
+    pub fn get_bytes(&self, key: &str) -> HandlerResult<Option<Vec<u8>>> {
+        let cmd = CacheKeyRequest {
+            key: key.to_string(),
+        };
+        let response = host_call(&self.binding, CAPID_CACHE, OP_CACHE_GET, &serialize(cmd)?)
+            .map_err(|e| e.into())
+            .and_then(|v| deserialize::<CacheGetResponse>(v.as_ref()))?;
+        let mut stats = STATS.write().unwrap();
+        let stats = stats.entry(self.binding.clone()).or_default();
+        if response.found {
+            stats.hits += 1;
+            Ok(Some(response.value))
+        } else {
+            stats.misses += 1;
+            Ok(None)
+        }
+    }
+
+    /// Obtains the value of an entry stored with `set`
+    pub fn get<T: DeserializeOwned>(&self, key: &str) -> HandlerResult<Option<T>> {
+        match self.get_bytes(key)? {
+            Some(bytes) => deserialize(&bytes).map(Some),
+            None => Ok(None),
+        }
+    }
+
+    /// Stores a raw value that expires after the given number of milliseconds
+    pub fn set_bytes(&self, key: &str, value: Vec<u8>, ttl_ms: u64) -> HandlerResult<()> {
+        self.set_bytes_tagged(key, value, ttl_ms, &[])
+    }
+
+    /// Stores a raw value with tags
+    pub fn set_bytes_tagged(
+        &self,
+        key: &str,
+        value: Vec<u8>,
+        ttl_ms: u64,
+        tags: &[&str],
+    ) -> HandlerResult<()> {
+        let cmd = CacheSetRequest {
+            key: key.to_string(),
+            value,
+            ttl_ms,
+            tags: tags.iter().map(|t| t.to_string()).collect(),
+        };
+        host_call(&self.binding, CAPID_CACHE, OP_CACHE_SET, &serialize(cmd)?)
+            .map(|_v| ())
+            .map_err(|e| e.into())
+    }
+
+    /// Stores a value that expires after the given number of milliseconds
+    pub fn set<T: Serialize>(&self, key: &str, value: &T, ttl_ms: u64) -> HandlerResult<()> {
+        self.set_bytes(key, serialize(value)?, ttl_ms)
+    }
+
+    /// Stores a value with tags, so it can be evicted along with related entries
+    pub fn set_tagged<T: Serialize>(
+        &self,
+        key: &str,
+        value: &T,
+        ttl_ms: u64,
+        tags: &[&str],
+    ) -> HandlerResult<()> {
+        self.set_bytes_tagged(key, serialize(value)?, ttl_ms, tags)
+    }
+
+    /// Obtains the value of an entry, or computes it and stores it for the given number of
+    /// milliseconds if it is not cached. Errors from the computation are returned and
+    /// nothing is stored
+    pub fn get_or_compute<T, F>(&self, key: &str, ttl_ms: u64, compute: F) -> HandlerResult<T>
+    where
+        T: Serialize + DeserializeOwned,
+        F: FnOnce() -> HandlerResult<T>,
+    {
+        if let Some(value) = self.get(key)? {
+            return Ok(value);
+        }
+        let value = compute()?;
+        self.set(key, &value, ttl_ms)?;
+        Ok(value)
+    }
+
+    /// Removes an entry
+    pub fn delete(&self, key: &str) -> HandlerResult<()> {
+        let cmd = CacheKeyRequest {
+            key: key.to_string(),
+        };
+        host_call(
+            &self.binding,
+            CAPID_CACHE,
+            OP_CACHE_DELETE,
+            &serialize(cmd)?,
+        )
+        .map(|_v| ())
+        .map_err(|e| e.into())
+    }
+
+    /// Evicts every entry with the given tag, returning the number evicted
+    pub fn evict_tag(&self, tag: &str) -> HandlerResult<u64> {
+        let cmd = EvictTagRequest {
+            tag: tag.to_string(),
+        };
+        host_call(&self.binding, CAPID_CACHE, OP_EVICT_TAG, &serialize(cmd)?)
+            .map_err(|e| e.into())
+            .and_then(|v| deserialize::<EvictTagResponse>(v.as_ref()))
+            .map(|r| r.evicted)
+    }
+
+    /// The hits and misses of the gets made through this binding since the module
+    /// instance started (or the statistics were last reset)
+    pub fn stats(&self) -> CacheStats {
+        STATS
+            .read()
+            .unwrap()
+            .get(&self.binding)
+            .copied()
+            .unwrap_or_default()
+    }
+
+    /// Resets the hit and miss counts of this binding
+    pub fn reset_stats(&self) {
+        STATS.write().unwrap().remove(&self.binding);
+    }
+}
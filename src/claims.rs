@@ -0,0 +1,76 @@
+//! # Claims
+//!
+//! This module contains the claims of a validated token, shared by the `identity` client
+//! and the `http::jwt` validator and re-exported from both
+
+use serde::de::DeserializeOwned;
+use serde_json::{Map, Value};
+
+use crate::HandlerResult;
+
+/// The claims of a validated token, as returned by both the identity provider and the JWT
+/// validator
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct Claims(Map<String, Value>);
+
+impl Claims {
+    /// The value of a claim
+    pub fn get(&self, name: &str) -> Option<&Value> {
+        self.0.get(name)
+    }
+
+    /// The subject (`sub`) of the token
+    pub fn subject(&self) -> Option<&str> {
+        self.0.get("sub").and_then(|v| v.as_str())
+    }
+
+    /// The issuer (`iss`) of the token
+    pub fn issuer(&self) -> Option<&str> {
+        self.0.get("iss").and_then(|v| v.as_str())
+    }
+
+    /// The audiences (`aud`) of the token, which may be a single string or a list
+    pub fn audience(&self) -> Vec<&str> {
+        match self.0.get("aud") {
+            Some(Value::String(aud)) => vec![aud.as_str()],
+            Some(Value::Array(auds)) => auds.iter().filter_map(|a| a.as_str()).collect(),
+            _ => Vec::new(),
+        }
+    }
+
+    /// The expiry time (`exp`) of the token, in seconds since the Unix epoch
+    pub fn expires_at(&self) -> Option<u64> {
+        self.0.get("exp").and_then(|v| v.as_u64())
+    }
+
+    /// The scopes granted to the token, from its space-separated `scope` claim or its
+    /// `scp` list
+    pub fn scopes(&self) -> Vec<&str> {
+        match (self.0.get("scope"), self.0.get("scp")) {
+            (Some(Value::String(scope)), _) => scope.split_whitespace().collect(),
+            (_, Some(Value::Array(scopes))) => scopes.iter().filter_map(|s| s.as_str()).collect(),
+            _ => Vec::new(),
+        }
+    }
+
+    /// Indicates whether the token was granted the given scope
+    pub fn has_scope(&self, scope: &str) -> bool {
+        self.scopes().contains(&scope)
+    }
+
+    /// Deserializes the claims into a type, for IdP-specific claims such as roles
+    pub fn deserialize<T: DeserializeOwned>(&self) -> HandlerResult<T> {
+        Ok(serde_json::from_value(Value::Object(self.0.clone()))?)
+    }
+
+    /// The underlying map of claim names to values
+    pub fn as_map(&self) -> &Map<String, Value> {
+        &self.0
+    }
+}
+
+impl From<Map<String, Value>> for Claims {
+    fn from(claims: Map<String, Value>) -> Self {
+        Claims(claims)
+    }
+}
@@ -0,0 +1,145 @@
+//! # Configuration
+//!
+//! This module contains access to the configuration values an actor was bound with. The
+//! host supplies the values from the binding's configuration through the `wascc:config`
+//! capability, so deployment-specific settings (service URLs, limits, tenant names) can be
+//! set when the actor is bound rather than compiled in. Values are fetched from the host
+//! once per binding and cached for the lifetime of the module instance
+//!
+//! ```
+//! extern crate wascc_actor as actor;
+//! use actor::prelude::*;
+//!
+//! fn upstream() -> HandlerResult<(String, u64)> {
+//!     let settings = config::default();
+//!     let base_url = settings.require("base_url")?;
+//!     let timeout_ms = settings.get_as::<u64>("timeout_ms")?.unwrap_or(2_000);
+//!     Ok((base_url, timeout_ms))
+//! }
+//! ```
+
+use serde::de::DeserializeOwned;
+use serde_derive::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::HashMap;
+use std::sync::RwLock;
+use wapc_guest::host_call;
+use wascc_codec::deserialize;
+
+use crate::HandlerResult;
+
+/// The reserved capability ID for binding configuration
+pub const CAPID_CONFIG: &str = "wascc:config";
+
+/// Guest sends an empty payload to the host, receives a `BindingConfiguration` back for
+/// the binding named in the call
+pub const OP_GET_CONFIGURATION: &str = "GetConfiguration";
+
+lazy_static! {
+    static ref CACHE: RwLock<HashMap<String, HashMap<String, String>>> =
+        RwLock::new(HashMap::new());
+}
+
+/// The configuration values of a binding
+#[derive(Debug, Clone, PartialEq, Default, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BindingConfiguration {
+    /// The values, by name
+    pub values: HashMap<String, String>,
+}
+
+/// An abstraction around the host's configuration for a binding
+pub struct ConfigHostBinding {
+    binding: String,
+}
+
+impl Default for ConfigHostBinding {
+    fn default() -> Self {
+        ConfigHostBinding {
+            binding: "default".to_string(),
+        }
+    }
+}
+
+/// Creates a host binding for the configuration of the named binding
+pub fn host(binding: &str) -> ConfigHostBinding {
+    ConfigHostBinding {
+        binding: binding.to_string(),
+    }
+}
+
+/// Creates a host binding for the configuration of the default binding
+pub fn default() -> ConfigHostBinding {
+    ConfigHostBinding::default()
+}
+
+impl ConfigHostBinding {
+    /// The value with the given name, if it was configured
+    pub fn get(&self, name: &str) -> HandlerResult<Option<String>> {
+        self.with_values(|values| values.get(name).cloned())
+    }
+
+    /// The value with the given name, or an error if it was not configured
+    pub fn require(&self, name: &str) -> HandlerResult<String> {
+        self.get(name)?.ok_or_else(|| {
+            format!(
+                "Configuration value '{}' is not set on binding '{}'",
+                name, self.binding
+            )
+            .into()
+        })
+    }
+
+    /// The value with the given name converted to a type, if it was configured. Values
+    /// are interpreted as JSON, so `8080`, `true` and `["a", "b"]` convert to numbers,
+    /// booleans and lists. Values that are not valid JSON, or whose JSON doesn't convert to
+    /// the type, are treated as strings, so `get_as::<String>` reads `8080` as `"8080"`
+    pub fn get_as<T: DeserializeOwned>(&self, name: &str) -> HandlerResult<Option<T>> {
+        match self.get(name)? {
+            Some(raw) => {
+                let value =
+                    serde_json::from_str(&raw).unwrap_or_else(|_| Value::String(raw.clone()));
+                let is_string = value.is_string();
+                serde_json::from_value(value)
+                    .or_else(|e| {
+                        if is_string {
+                            Err(e)
+                        } else {
+                            serde_json::from_value(Value::String(raw)).map_err(|_| e)
+                        }
+                    })
+                    .map(Some)
+                    .map_err(|e| {
+                        format!("Configuration value '{}' has the wrong type: {}", name, e).into()
+                    })
+            }
+            None => Ok(None),
+        }
+    }
+
+    /// All of the binding's configuration values
+    pub fn all(&self) -> HandlerResult<HashMap<String, String>> {
+        self.with_values(|values| values.clone())
+    }
+
+    /// Discards the cached values, so they are fetched from the host again on next use
+    pub fn refresh(&self) {
+        CACHE.write().unwrap().remove(&self.binding);
+    }
+
+    fn with_values<T, F>(&self, f: F) -> HandlerResult<T>
+    where
+        F: FnOnce(&HashMap<String, String>) -> T,
+    {
+        if let Some(values) = CACHE.read().unwrap().get(&self.binding) {
+            return Ok(f(values));
+        }
+        let values = host_call(&self.binding, CAPID_CONFIG, OP_GET_CONFIGURATION, &[])
+            .map_err(|e| e.into())
+            .and_then(|v| deserialize::<BindingConfiguration>(v.as_ref()))?
+            .values;
+        let result = f(&values);
+        CACHE.write().unwrap().insert(self.binding.clone(), values);
+        Ok(result)
+    }
+}
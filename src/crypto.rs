@@ -0,0 +1,277 @@
+//! # Cryptography
+//!
+//! This module contains the client through which actors perform cryptographic operations
+//! with the currently bound `wascc:crypto` capability provider. Keys are held by the
+//! provider (for example in a KMS or HSM) and referred to by name, so private key material
+//! never enters the actor's memory and the actor needs no cryptography crates of its own
+//!
+//! ```
+//! extern crate wascc_actor as actor;
+//! use actor::prelude::*;
+//!
+//! fn sign_receipt(receipt: &[u8]) -> HandlerResult<Vec<u8>> {
+//!     crypto::default().sign("receipts", receipt)
+//! }
+//!
+//! fn store_card(number: &str) -> HandlerResult<()> {
+//!     let sealed = crypto::default().encrypt("cards", number.as_bytes())?;
+//!     keyvalue::default().set("card", &base64::encode(&sealed), None)
+//! }
+//! ```
+
+use serde_derive::{Deserialize, Serialize};
+use wapc_guest::host_call;
+use wascc_codec::{deserialize, serialize};
+
+use crate::HandlerResult;
+
+/// The reserved capability ID for cryptography functionality
+pub const CAPID_CRYPTO: &str = "wascc:crypto";
+
+/// Guest sends a `SignRequest` to the capability provider, receives a `SignResponse` back
+pub const OP_SIGN: &str = "Sign";
+/// Guest sends a `VerifyRequest` to the capability provider, receives a `VerifyResponse` back
+pub const OP_VERIFY: &str = "Verify";
+/// Guest sends an `EncryptRequest` to the capability provider, receives an `EncryptResponse` back
+pub const OP_ENCRYPT: &str = "Encrypt";
+/// Guest sends a `DecryptRequest` to the capability provider, receives a `DecryptResponse` back
+pub const OP_DECRYPT: &str = "Decrypt";
+/// Guest sends a `HashRequest` to the capability provider, receives a `HashResponse` back
+pub const OP_HASH: &str = "Hash";
+
+/// A request to sign data with a named key. The signature algorithm is that of the key
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SignRequest {
+    /// The name of the signing key
+    pub key_name: String,
+    /// The data to sign
+    #[serde(with = "serde_bytes")]
+    pub data: Vec<u8>,
+}
+
+/// The signature produced for a `SignRequest`
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SignResponse {
+    /// The signature
+    #[serde(with = "serde_bytes")]
+    pub signature: Vec<u8>,
+}
+
+/// A request to verify a signature with a named key
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct VerifyRequest {
+    /// The name of the key whose signature is expected
+    pub key_name: String,
+    /// The data that was signed
+    #[serde(with = "serde_bytes")]
+    pub data: Vec<u8>,
+    /// The signature to verify
+    #[serde(with = "serde_bytes")]
+    pub signature: Vec<u8>,
+}
+
+/// The outcome of a `VerifyRequest`
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct VerifyResponse {
+    /// Indicates whether the signature is valid
+    pub valid: bool,
+}
+
+/// A request to encrypt data with a named key
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct EncryptRequest {
+    /// The name of the encryption key
+    pub key_name: String,
+    /// The data to encrypt
+    #[serde(with = "serde_bytes")]
+    pub plaintext: Vec<u8>,
+    /// Additional data that is authenticated but not encrypted, and that must be supplied
+    /// again to decrypt
+    #[serde(with = "serde_bytes")]
+    #[serde(default)]
+    pub associated_data: Vec<u8>,
+}
+
+/// The ciphertext produced for an `EncryptRequest`
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct EncryptResponse {
+    /// The ciphertext, which includes whatever the provider needs to decrypt it (nonce,
+    /// key version, etc.)
+    #[serde(with = "serde_bytes")]
+    pub ciphertext: Vec<u8>,
+}
+
+/// A request to decrypt data with a named key
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DecryptRequest {
+    /// The name of the encryption key
+    pub key_name: String,
+    /// The ciphertext produced by an earlier encryption
+    #[serde(with = "serde_bytes")]
+    pub ciphertext: Vec<u8>,
+    /// The associated data supplied to the encryption
+    #[serde(with = "serde_bytes")]
+    #[serde(default)]
+    pub associated_data: Vec<u8>,
+}
+
+/// The plaintext produced for a `DecryptRequest`
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DecryptResponse {
+    /// The decrypted data
+    #[serde(with = "serde_bytes")]
+    pub plaintext: Vec<u8>,
+}
+
+/// A digest algorithm
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+pub enum HashAlgorithm {
+    /// SHA-256
+    #[serde(rename = "SHA-256")]
+    Sha256,
+    /// SHA-384
+    #[serde(rename = "SHA-384")]
+    Sha384,
+    /// SHA-512
+    #[serde(rename = "SHA-512")]
+    Sha512,
+}
+
+/// A request to compute a digest
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct HashRequest {
+    /// The digest algorithm
+    pub algorithm: HashAlgorithm,
+    /// The data to digest
+    #[serde(with = "serde_bytes")]
+    pub data: Vec<u8>,
+}
+
+/// The digest produced for a `HashRequest`
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct HashResponse {
+    /// The digest
+    #[serde(with = "serde_bytes")]
+    pub digest: Vec<u8>,
+}
+
+/// An abstraction around a host runtime capability for cryptography
+pub struct CryptoHostBinding {
+    binding: String,
+}
+
+impl Default for CryptoHostBinding {
+    fn default() -> Self {
+        CryptoHostBinding {
+            binding: "default".to_string(),
+        }
+    }
+}
+
+/// Creates a named host binding for the cryptography capability
+pub fn host(binding: &str) -> CryptoHostBinding {
+    CryptoHostBinding {
+        binding: binding.to_string(),
+    }
+}
+
+/// Creates the default host binding for the cryptography capability
+pub fn default() -> CryptoHostBinding {
+    CryptoHostBinding::default()
+}
+
+impl CryptoHostBinding {
+    /// Signs data with the named key, returning the signature
+    pub fn sign(&self, key_name: &str, data: &[u8]) -> HandlerResult<Vec<u8>> {
+        let cmd = SignRequest {
+            key_name: key_name.to_string(),
+            data: data.to_vec(),
+        };
+        host_call(&self.binding, CAPID_CRYPTO, OP_SIGN, &serialize(cmd)?)
+            .map_err(|e| e.into())
+            .and_then(|v| deserialize::<SignResponse>(v.as_ref()))
+            .map(|r| r.signature)
+    }
+
+    /// Verifies that a signature over the data was produced by the named key
+    pub fn verify(&self, key_name: &str, data: &[u8], signature: &[u8]) -> HandlerResult<bool> {
+        let cmd = VerifyRequest {
+            key_name: key_name.to_string(),
+            data: data.to_vec(),
+            signature: signature.to_vec(),
+        };
+        host_call(&self.binding, CAPID_CRYPTO, OP_VERIFY, &serialize(cmd)?)
+            .map_err(|e| e.into())
+            .and_then(|v| deserialize::<VerifyResponse>(v.as_ref()))
+            .map(|r| r.valid)
+    }
+
+    /// Encrypts data with the named key, returning the ciphertext
+    pub fn encrypt(&self, key_name: &str, plaintext: &[u8]) -> HandlerResult<Vec<u8>> {
+        self.encrypt_with_aad(key_name, plaintext, &[])
+    }
+
+    /// Encrypts data with the named key, binding the ciphertext to associated data (such as
+    /// a record ID) that must be supplied again to decrypt it
+    pub fn encrypt_with_aad(
+        &self,
+        key_name: &str,
+        plaintext: &[u8],
+        associated_data: &[u8],
+    ) -> HandlerResult<Vec<u8>> {
+        let cmd = EncryptRequest {
+            key_name: key_name.to_string(),
+            plaintext: plaintext.to_vec(),
+            associated_data: associated_data.to_vec(),
+        };
+        host_call(&self.binding, CAPID_CRYPTO, OP_ENCRYPT, &serialize(cmd)?)
+            .map_err(|e| e.into())
+            .and_then(|v| deserialize::<EncryptResponse>(v.as_ref()))
+            .map(|r| r.ciphertext)
+    }
+
+    /// Decrypts ciphertext produced by `encrypt` with the named key
+    pub fn decrypt(&self, key_name: &str, ciphertext: &[u8]) -> HandlerResult<Vec<u8>> {
+        self.decrypt_with_aad(key_name, ciphertext, &[])
+    }
+
+    /// Decrypts ciphertext produced by `encrypt_with_aad` with the named key
+    pub fn decrypt_with_aad(
+        &self,
+        key_name: &str,
+        ciphertext: &[u8],
+        associated_data: &[u8],
+    ) -> HandlerResult<Vec<u8>> {
+        let cmd = DecryptRequest {
+            key_name: key_name.to_string(),
+            ciphertext: ciphertext.to_vec(),
+            associated_data: associated_data.to_vec(),
+        };
+        host_call(&self.binding, CAPID_CRYPTO, OP_DECRYPT, &serialize(cmd)?)
+            .map_err(|e| e.into())
+            .and_then(|v| deserialize::<DecryptResponse>(v.as_ref()))
+            .map(|r| r.plaintext)
+    }
+
+    /// Computes the digest of data
+    pub fn hash(&self, algorithm: HashAlgorithm, data: &[u8]) -> HandlerResult<Vec<u8>> {
+        let cmd = HashRequest {
+            algorithm,
+            data: data.to_vec(),
+        };
+        host_call(&self.binding, CAPID_CRYPTO, OP_HASH, &serialize(cmd)?)
+            .map_err(|e| e.into())
+            .and_then(|v| deserialize::<HashResponse>(v.as_ref()))
+            .map(|r| r.digest)
+    }
+}
@@ -0,0 +1,369 @@
+//! # Devices
+//!
+//! This module contains the client through which actors running at the edge read and drive
+//! the hardware exposed by the currently bound `wascc:device` capability provider: digital
+//! (GPIO) pins, analog inputs and PWM outputs, and named sensors. Pins the actor watches
+//! raise interrupts, which the provider delivers to the actor with the `OP_INTERRUPT`
+//! operation; an `InterruptDispatcher` routes them to a handler per pin
+//!
+//! ```
+//! extern crate wascc_actor as actor;
+//! use actor::prelude::*;
+//! use actor::device::{Edge, Interrupt, InterruptDispatcher};
+//!
+//! const BUTTON_PIN: u32 = 17;
+//! const LED_PIN: u32 = 27;
+//!
+//! actor_handlers! {
+//!     codec::core::OP_HEALTH_REQUEST => health,
+//!     device::OP_INTERRUPT => interrupt
+//! }
+//!
+//! fn health(_req: codec::core::HealthRequest) -> HandlerResult<()> {
+//!     device::default().watch(BUTTON_PIN, Edge::Rising)
+//! }
+//!
+//! fn interrupt(event: Interrupt) -> HandlerResult<()> {
+//!     InterruptDispatcher::new()
+//!         .on(BUTTON_PIN, button_pressed)
+//!         .dispatch(&event)
+//! }
+//!
+//! fn button_pressed(_event: &Interrupt) -> HandlerResult<()> {
+//!     let gpio = device::default();
+//!     let lit = gpio.digital_read(LED_PIN)?;
+//!     gpio.digital_write(LED_PIN, !lit)?;
+//!     let reading = gpio.sample("temperature")?;
+//!     log::info!("Temperature is {} {}", reading.value, reading.unit);
+//!     Ok(())
+//! }
+//! # fn main() {}
+//! ```
+
+use serde_derive::{Deserialize, Serialize};
+use std::collections::HashMap;
+use wapc_guest::host_call;
+use wascc_codec::{deserialize, serialize};
+
+use crate::HandlerResult;
+
+/// The reserved capability ID for device functionality
+pub const CAPID_DEVICE: &str = "wascc:device";
+
+/// Guest sends a `PinRequest` to the capability provider, receives a `DigitalValue` back
+pub const OP_DIGITAL_READ: &str = "DigitalRead";
+/// Guest sends a `DigitalValue` to the capability provider, receives nothing back
+pub const OP_DIGITAL_WRITE: &str = "DigitalWrite";
+/// Guest sends a `PinRequest` to the capability provider, receives an `AnalogValue` back
+pub const OP_ANALOG_READ: &str = "AnalogRead";
+/// Guest sends an `AnalogValue` to the capability provider, receives nothing back
+pub const OP_ANALOG_WRITE: &str = "AnalogWrite";
+/// Guest sends a `SampleRequest` to the capability provider, receives a `SampleResponse` back
+pub const OP_SAMPLE_SENSOR: &str = "SampleSensor";
+/// Guest sends a `WatchRequest` to the capability provider, receives nothing back
+pub const OP_WATCH_PIN: &str = "WatchPin";
+/// Guest sends a `PinRequest` to the capability provider, receives nothing back
+pub const OP_UNWATCH_PIN: &str = "UnwatchPin";
+
+/// Capability provider sends an `Interrupt` to the actor when a watched pin changes,
+/// receives nothing back
+pub const OP_INTERRUPT: &str = "DeviceInterrupt";
+
+/// A request naming a single pin
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PinRequest {
+    /// The pin number, as numbered by the provider
+    pub pin: u32,
+}
+
+/// The level of a digital pin
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DigitalValue {
+    /// The pin number
+    pub pin: u32,
+    /// Indicates whether the pin is high
+    pub high: bool,
+}
+
+/// The value of an analog input or PWM output
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AnalogValue {
+    /// The pin number
+    pub pin: u32,
+    /// The value as a fraction of full scale, from 0.0 to 1.0. For outputs this is the PWM
+    /// duty cycle
+    pub value: f64,
+}
+
+/// A request to sample a sensor
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SampleRequest {
+    /// The name of the sensor, as configured on the provider
+    pub sensor: String,
+    /// The number of samples to take
+    pub count: u32,
+    /// The number of milliseconds between samples
+    pub interval_ms: u64,
+}
+
+/// A single sensor reading
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SensorReading {
+    /// The value read
+    pub value: f64,
+    /// The unit of the value, such as `celsius` or `lux`
+    #[serde(default)]
+    pub unit: String,
+    /// The time the reading was taken, in milliseconds since the Unix epoch
+    pub timestamp_ms: u64,
+}
+
+/// The readings taken for a `SampleRequest`
+#[derive(Debug, Clone, PartialEq, Default, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SampleResponse {
+    /// The readings, oldest first
+    pub readings: Vec<SensorReading>,
+}
+
+/// The transitions of a digital pin
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum Edge {
+    /// The pin went from low to high
+    Rising,
+    /// The pin went from high to low
+    Falling,
+    /// Either transition
+    Both,
+}
+
+/// A request to deliver interrupts for a pin
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct WatchRequest {
+    /// The pin number
+    pub pin: u32,
+    /// The transitions that raise an interrupt
+    pub edge: Edge,
+    /// The number of milliseconds after an interrupt during which further transitions are
+    /// ignored, to debounce mechanical switches
+    #[serde(default)]
+    pub debounce_ms: u64,
+}
+
+/// A change of a watched pin, delivered to the actor with the `OP_INTERRUPT` operation
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Interrupt {
+    /// The pin number
+    pub pin: u32,
+    /// The transition that occurred, either `Rising` or `Falling`
+    pub edge: Edge,
+    /// The time of the transition, in nanoseconds of the provider's monotonic clock
+    pub timestamp_ns: u64,
+}
+
+type BoxedInterruptHandler = Box<dyn Fn(&Interrupt) -> HandlerResult<()> + Send + Sync>;
+
+/// Routes interrupts to handlers by pin. Handlers registered for a specific edge take
+/// precedence over handlers registered for every edge of the same pin. Interrupts for pins
+/// with no handler produce an error
+///
+/// Because handlers must be `Send + Sync`, a dispatcher can be built once and kept in a
+/// static.
+#[derive(Default)]
+pub struct InterruptDispatcher {
+    handlers: HashMap<(u32, Edge), BoxedInterruptHandler>,
+}
+
+impl InterruptDispatcher {
+    /// Creates a dispatcher with no handlers
+    pub fn new() -> InterruptDispatcher {
+        InterruptDispatcher::default()
+    }
+
+    /// Adds a handler for every interrupt raised by a pin
+    pub fn on<F>(self, pin: u32, handler: F) -> InterruptDispatcher
+    where
+        F: Fn(&Interrupt) -> HandlerResult<()> + Send + Sync + 'static,
+    {
+        self.on_edge(pin, Edge::Both, handler)
+    }
+
+    /// Adds a handler for the interrupts raised by a pin for one transition
+    pub fn on_edge<F>(mut self, pin: u32, edge: Edge, handler: F) -> InterruptDispatcher
+    where
+        F: Fn(&Interrupt) -> HandlerResult<()> + Send + Sync + 'static,
+    {
+        self.handlers.insert((pin, edge), Box::new(handler));
+        self
+    }
+
+    /// Invokes the handler for an interrupt
+    pub fn dispatch(&self, interrupt: &Interrupt) -> HandlerResult<()> {
+        match self
+            .handlers
+            .get(&(interrupt.pin, interrupt.edge))
+            .or_else(|| self.handlers.get(&(interrupt.pin, Edge::Both)))
+        {
+            Some(handler) => handler(interrupt),
+            None => Err(format!(
+                "No handler for {:?} interrupt on pin {}",
+                interrupt.edge, interrupt.pin
+            )
+            .into()),
+        }
+    }
+}
+
+/// An abstraction around a host runtime capability for device I/O
+pub struct DeviceHostBinding {
+    binding: String,
+}
+
+impl Default for DeviceHostBinding {
+    fn default() -> Self {
+        DeviceHostBinding {
+            binding: "default".to_string(),
+        }
+    }
+}
+
+/// Creates a named host binding for the device capability
+pub fn host(binding: &str) -> DeviceHostBinding {
+    DeviceHostBinding {
+        binding: binding.to_string(),
+    }
+}
+
+/// Creates the default host binding for the device capability
+pub fn default() -> DeviceHostBinding {
+    DeviceHostBinding::default()
+}
+
+impl DeviceHostBinding {
+    /// Indicates whether a digital pin is high
+    pub fn digital_read(&self, pin: u32) -> HandlerResult<bool> {
+        let cmd = PinRequest { pin };
+        host_call(
+            &self.binding,
+            CAPID_DEVICE,
+            OP_DIGITAL_READ,
+            &serialize(cmd)?,
+        )
+        .map_err(|e| e.into())
+        .and_then(|v| deserialize::<DigitalValue>(v.as_ref()))
+        .map(|v| v.high)
+    }
+
+    /// Drives a digital pin high or low
+    pub fn digital_write(&self, pin: u32, high: bool) -> HandlerResult<()> {
+        let cmd = DigitalValue { pin, high };
+        host_call(
+            &self.binding,
+            CAPID_DEVICE,
+            OP_DIGITAL_WRITE,
+            &serialize(cmd)?,
+        )
+        .map(|_v| ())
+        .map_err(|e| e.into())
+    }
+
+    /// Reads an analog input, as a fraction of full scale
+    pub fn analog_read(&self, pin: u32) -> HandlerResult<f64> {
+        let cmd = PinRequest { pin };
+        host_call(
+            &self.binding,
+            CAPID_DEVICE,
+            OP_ANALOG_READ,
+            &serialize(cmd)?,
+        )
+        .map_err(|e| e.into())
+        .and_then(|v| deserialize::<AnalogValue>(v.as_ref()))
+        .map(|v| v.value)
+    }
+
+    /// Sets the duty cycle of a PWM output, from 0.0 to 1.0
+    pub fn analog_write(&self, pin: u32, value: f64) -> HandlerResult<()> {
+        if !(0.0..=1.0).contains(&value) {
+            return Err(format!("Analog value {} is outside 0.0 to 1.0", value).into());
+        }
+        let cmd = AnalogValue { pin, value };
+        host_call(
+            &self.binding,
+            CAPID_DEVICE,
+            OP_ANALOG_WRITE,
+            &serialize(cmd)?,
+        )
+        .map(|_v| ())
+        .map_err(|e| e.into())
+    }
+
+    /// Takes a single reading from a sensor
+    pub fn sample(&self, sensor: &str) -> HandlerResult<SensorReading> {
+        self.sample_many(sensor, 1, 0)?
+            .pop()
+            .ok_or_else(|| format!("Sensor {} returned no readings", sensor).into())
+    }
+
+    /// Takes several readings from a sensor, the given number of milliseconds apart. The
+    /// provider takes the readings, so the actor is not held in a loop between them
+    pub fn sample_many(
+        &self,
+        sensor: &str,
+        count: u32,
+        interval_ms: u64,
+    ) -> HandlerResult<Vec<SensorReading>> {
+        let cmd = SampleRequest {
+            sensor: sensor.to_string(),
+            count,
+            interval_ms,
+        };
+        host_call(
+            &self.binding,
+            CAPID_DEVICE,
+            OP_SAMPLE_SENSOR,
+            &serialize(cmd)?,
+        )
+        .map_err(|e| e.into())
+        .and_then(|v| deserialize::<SampleResponse>(v.as_ref()))
+        .map(|r| r.readings)
+    }
+
+    /// Starts delivering interrupts for a pin's transitions to the actor
+    pub fn watch(&self, pin: u32, edge: Edge) -> HandlerResult<()> {
+        self.watch_debounced(pin, edge, 0)
+    }
+
+    /// Starts delivering interrupts for a pin's transitions, ignoring transitions within the
+    /// given number of milliseconds of the previous interrupt
+    pub fn watch_debounced(&self, pin: u32, edge: Edge, debounce_ms: u64) -> HandlerResult<()> {
+        let cmd = WatchRequest {
+            pin,
+            edge,
+            debounce_ms,
+        };
+        host_call(&self.binding, CAPID_DEVICE, OP_WATCH_PIN, &serialize(cmd)?)
+            .map(|_v| ())
+            .map_err(|e| e.into())
+    }
+
+    /// Stops delivering interrupts for a pin
+    pub fn unwatch(&self, pin: u32) -> HandlerResult<()> {
+        let cmd = PinRequest { pin };
+        host_call(
+            &self.binding,
+            CAPID_DEVICE,
+            OP_UNWATCH_PIN,
+            &serialize(cmd)?,
+        )
+        .map(|_v| ())
+        .map_err(|e| e.into())
+    }
+}
@@ -140,3 +140,19 @@ impl From<Box<dyn ::std::error::Error>> for Error {
         Error(Box::new(ErrorKind::MiscError(source)))
     }
 }
+
+/// Indicates whether an error returned from a host call means the capability provider does
+/// not implement the requested operation. Providers answer operations they don't recognize
+/// with a "bad dispatch" error (or, for some providers, an "unsupported operation" error),
+/// so this lets callers fall back to older operations without masking other failures
+pub fn is_unsupported_operation(e: &(dyn StdError + Send + Sync)) -> bool {
+    let message = e.to_string().to_ascii_lowercase();
+    [
+        "bad dispatch",
+        "unsupported operation",
+        "unknown operation",
+        "not supported",
+    ]
+    .iter()
+    .any(|m| message.contains(m))
+}
@@ -1,24 +1,276 @@
+//! # Event Streams
+//!
+//! This module contains the event stream client through which actors consume the currently
+//! bound `wascc:eventstreams` capability provider. Each `EventStreamsHostBinding` targets a
+//! single named binding, so an actor can use several providers at once (for example, writing
+//! audit events to one provider and domain events to another)
+
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use serde_derive::{Deserialize, Serialize};
 use std::collections::HashMap;
 use wapc_guest::host_call;
 use wascc_codec::eventstreams::*;
 use wascc_codec::{deserialize, serialize};
 
+use crate::keyvalue::KeyValueStoreHostBinding;
 use crate::HandlerResult;
 
 const CAPID_EVENTS: &str = "wascc:eventstreams";
 
-/// Creates a new host binding for an event stream capability provider
-pub fn host(binding: &str) -> EventStreamsHostBinding {
-    EventStreamsHostBinding {
-        binding: binding.to_string(),
-    }
+/// Provider delivers each event appended to a subscribed stream to the actor with this
+/// operation. Register a handler for it that accepts an `Event`
+pub use wascc_codec::eventstreams::OP_DELIVER_EVENT;
+
+/// Guest sends a StreamSubscription to the capability provider, lack of error indicates
+/// events will be delivered via `OP_DELIVER_EVENT`
+pub const OP_SUBSCRIBE_STREAM: &str = "SubscribeStream";
+/// Guest sends a StreamSubscription to the capability provider to stop event delivery
+pub const OP_UNSUBSCRIBE_STREAM: &str = "UnsubscribeStream";
+
+/// Guest sends a StreamRef to the capability provider, lack of error indicates the stream
+/// and all of its events were deleted
+pub const OP_DELETE_STREAM: &str = "DeleteStream";
+/// Guest sends a StreamTruncation to the capability provider, lack of error indicates the
+/// events were removed
+pub const OP_TRUNCATE_STREAM: &str = "TruncateStream";
+
+/// Guest sends an EventBatch to the capability provider, receives a BatchWriteResponse back
+pub const OP_WRITE_EVENT_BATCH: &str = "WriteEventBatch";
+
+/// Guest sends an IdempotentWrite to the capability provider, receives a WriteResponse back
+pub const OP_WRITE_EVENT_IDEMPOTENT: &str = "WriteEventIdempotent";
+
+/// An event to be written to a stream at most once per deduplication ID
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct IdempotentWrite {
+    /// ID of the stream
+    pub stream_id: String,
+    /// The values of the event
+    pub values: HashMap<String, String>,
+    /// The client-supplied deduplication ID
+    pub dedup_id: String,
 }
 
-/// Creates a default host binding for an event stream capability provider
-pub fn default() -> EventStreamsHostBinding {
-    EventStreamsHostBinding {
-        binding: "default".to_string(),
-    }
+/// Guest sends a ConditionalWrite to the capability provider, receives a WriteResponse back.
+/// The provider returns an error if the stream's last event does not have the expected ID
+pub const OP_WRITE_EVENT_EXPECTING: &str = "WriteEventExpecting";
+
+/// An event to be written to a stream only if the stream has not advanced
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ConditionalWrite {
+    /// ID of the stream
+    pub stream_id: String,
+    /// The values of the event
+    pub values: HashMap<String, String>,
+    /// The ID the stream's most recent event must have. Empty if the stream must be empty
+    pub expected_last_id: String,
+}
+
+/// A set of events to be appended to a stream atomically
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct EventBatch {
+    /// ID of the stream
+    pub stream_id: String,
+    /// The events to append, in order
+    pub events: Vec<Event>,
+}
+
+/// The response from the provider after writing a batch of events
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BatchWriteResponse {
+    /// Unique IDs of the events written, in the order they were supplied
+    #[serde(default)]
+    pub event_ids: Vec<String>,
+}
+
+/// Guest sends a StreamRef to the capability provider, receives a StreamInfo back
+pub const OP_QUERY_STREAM_INFO: &str = "QueryStreamInfo";
+
+/// Guest sends a ReadAfterRequest to the capability provider, receives a StreamResults back
+pub const OP_READ_STREAM_AFTER: &str = "ReadStreamAfter";
+
+/// A request for the events of a stream that follow a given event
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ReadAfterRequest {
+    /// ID of the stream
+    pub stream_id: String,
+    /// The ID of the event after which to start reading. Empty to read from the start of
+    /// the stream
+    pub after_id: String,
+    /// The maximum number of events to return
+    pub count: u64,
+}
+
+/// Guest sends a PageRequest to the capability provider, receives a StreamPage back
+pub const OP_QUERY_STREAM_PAGE: &str = "QueryStreamPage";
+
+/// A request for one page of the events in a stream
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PageRequest {
+    /// ID of the stream
+    pub stream_id: String,
+    /// The maximum number of events to return
+    pub count: u64,
+    /// The cursor returned with the previous page. Empty to read the first page
+    #[serde(default)]
+    pub cursor: String,
+    /// Values that every returned event must contain. Empty to return every event
+    #[serde(default)]
+    pub filter: HashMap<String, String>,
+}
+
+/// One page of the events in a stream
+#[derive(Debug, Clone, PartialEq, Default, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct StreamPage {
+    /// The events in the page
+    #[serde(default)]
+    pub events: Vec<Event>,
+    /// The cursor with which to request the next page. Empty if this is the last page
+    #[serde(default)]
+    pub next_cursor: String,
+}
+
+/// Summary information about a stream
+#[derive(Debug, Clone, PartialEq, Default, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct StreamInfo {
+    /// ID of the stream
+    pub stream_id: String,
+    /// The number of events in the stream
+    pub length: u64,
+    /// The ID of the oldest event in the stream. Empty if the stream has no events
+    #[serde(default)]
+    pub first_event_id: String,
+    /// The ID of the newest event in the stream. Empty if the stream has no events
+    #[serde(default)]
+    pub last_event_id: String,
+    /// The approximate number of bytes used to store the stream. Zero if the provider does
+    /// not report sizes
+    #[serde(default)]
+    pub approximate_bytes: u64,
+}
+
+/// Identifies a stream
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct StreamRef {
+    /// ID of the stream
+    pub stream_id: String,
+}
+
+/// A request to remove the oldest events of a stream
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct StreamTruncation {
+    /// ID of the stream
+    pub stream_id: String,
+    /// The ID of the last event to remove. This event and every event before it are removed
+    pub up_to_id: String,
+}
+
+/// The number of events read per page by `replay`
+pub const REPLAY_PAGE_SIZE: u64 = 100;
+
+/// The state of an aggregate rebuilt by `replay_with_snapshot`, along with the ID of the
+/// last event folded into it
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Snapshot<S> {
+    /// The folded state
+    pub state: S,
+    /// The ID of the last event folded into the state. Empty if no events have been folded
+    pub last_event_id: String,
+}
+
+/// The event value under which `write_event_typed` stores the JSON-encoded body of a typed
+/// event
+pub const BODY_KEY: &str = "_body";
+
+/// Guest sends a ConsumerGroup to the capability provider to create (or look up an existing)
+/// consumer group on a stream
+pub const OP_CREATE_CONSUMER_GROUP: &str = "CreateConsumerGroup";
+/// Guest sends a GroupFetch to the capability provider, receives a StreamResults back
+pub const OP_FETCH_GROUP_EVENTS: &str = "FetchGroupEvents";
+/// Guest sends a GroupAck to the capability provider, lack of error indicates success
+pub const OP_ACK_GROUP_EVENT: &str = "AckGroupEvent";
+
+/// A named group of consumers sharing the events of a stream. Each event is handed to only
+/// one member of the group, and is redelivered if it is not acknowledged
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ConsumerGroup {
+    /// ID of the stream the group consumes
+    pub stream_id: String,
+    /// The name of the group
+    pub group: String,
+}
+
+/// A request to fetch the next batch of unclaimed events for a consumer group
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct GroupFetch {
+    /// ID of the stream the group consumes
+    pub stream_id: String,
+    /// The name of the group
+    pub group: String,
+    /// The maximum number of events to return
+    pub max_events: u64,
+}
+
+/// Acknowledges that a consumer group has finished processing an event
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct GroupAck {
+    /// ID of the stream the group consumes
+    pub stream_id: String,
+    /// The name of the group
+    pub group: String,
+    /// The ID of the processed event
+    pub event_id: String,
+}
+
+/// The position in a stream from which a subscription begins delivering events
+#[derive(Debug, Clone, PartialEq)]
+pub enum SubscribeFrom {
+    /// Deliver every event in the stream, followed by new events as they are appended
+    Beginning,
+    /// Deliver only events appended after the subscription is made
+    Now,
+    /// Deliver the events that follow the event with the given ID, typically the last
+    /// event the actor processed
+    After(String),
+}
+
+/// A request for the provider to push the events of a stream to the actor
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct StreamSubscription {
+    /// ID of the stream
+    pub stream_id: String,
+    /// Whether to deliver only events appended after the subscription is made
+    #[serde(default)]
+    pub new_only: bool,
+    /// Deliver the events that follow the event with this ID. Empty to start from the
+    /// beginning of the stream (unless `new_only` is set)
+    #[serde(default)]
+    pub after_id: String,
+}
+
+/// A single page of the events in a stream
+#[derive(Debug, Clone, PartialEq)]
+pub struct EventPage {
+    /// The events in this page
+    pub events: Vec<Event>,
+    /// The cursor with which to request the next page. `None` if this is the last page
+    pub cursor: Option<String>,
 }
 
 /// A host binding for an event stream capability provider
@@ -26,6 +278,26 @@ pub struct EventStreamsHostBinding {
     binding: String,
 }
 
+impl Default for EventStreamsHostBinding {
+    fn default() -> Self {
+        EventStreamsHostBinding {
+            binding: "default".to_string(),
+        }
+    }
+}
+
+/// Creates a named host binding for an event stream capability provider
+pub fn host(binding: &str) -> EventStreamsHostBinding {
+    EventStreamsHostBinding {
+        binding: binding.to_string(),
+    }
+}
+
+/// Creates the default host binding for an event stream capability provider
+pub fn default() -> EventStreamsHostBinding {
+    EventStreamsHostBinding::default()
+}
+
 impl EventStreamsHostBinding {
     /// Writes the given event (a collection of key-value pairs) to a named stream
     pub fn write_event(
@@ -49,6 +321,108 @@ impl EventStreamsHostBinding {
             .map_err(|e| e.into())
     }
 
+    /// Writes an event to a named stream at most once per deduplication ID. If an event with
+    /// the same ID was already written to the stream (for example by an earlier attempt of a
+    /// retried handler), nothing is appended and the ID of the existing event is returned.
+    /// This uses the `OP_WRITE_EVENT_IDEMPOTENT` operation, so providers that can't
+    /// deduplicate writes return an error rather than appending the event unconditionally
+    pub fn write_event_idempotent(
+        &self,
+        stream: &str,
+        values: HashMap<String, String>,
+        dedup_id: &str,
+    ) -> HandlerResult<String> {
+        let cmd = IdempotentWrite {
+            stream_id: stream.to_string(),
+            values,
+            dedup_id: dedup_id.to_string(),
+        };
+        self.append(OP_WRITE_EVENT_IDEMPOTENT, cmd)
+    }
+
+    /// Writes an event to a named stream only if the stream has not advanced: its most
+    /// recent event must have the given ID, or the stream must be empty if `None` is given.
+    /// Otherwise the provider rejects the write with an error, and the actor should reload
+    /// the aggregate's state and retry. This provides optimistic concurrency over
+    /// event-sourced aggregates. This uses the `OP_WRITE_EVENT_EXPECTING` operation, so
+    /// providers that can't check the stream's position return an error rather than
+    /// appending the event unconditionally
+    pub fn write_event_expecting(
+        &self,
+        stream: &str,
+        values: HashMap<String, String>,
+        expected_last_id: Option<&str>,
+    ) -> HandlerResult<String> {
+        let cmd = ConditionalWrite {
+            stream_id: stream.to_string(),
+            values,
+            expected_last_id: expected_last_id.unwrap_or_default().to_string(),
+        };
+        self.append(OP_WRITE_EVENT_EXPECTING, cmd)
+    }
+
+    fn append<T: Serialize>(&self, operation: &str, cmd: T) -> HandlerResult<String> {
+        host_call(&self.binding, CAPID_EVENTS, operation, &serialize(cmd)?)
+            .map_err(|e| e.into())
+            .and_then(|v| deserialize::<WriteResponse>(v.as_ref()))
+            .map(|r| r.event_id)
+    }
+
+    /// Appends several events to a stream atomically: either every event is written, or
+    /// none are. Returns the IDs of the written events in the order they were supplied
+    pub fn write_batch(
+        &self,
+        stream: &str,
+        events: Vec<HashMap<String, String>>,
+    ) -> HandlerResult<Vec<String>> {
+        let expected = events.len();
+        let cmd = EventBatch {
+            stream_id: stream.to_string(),
+            events: events
+                .into_iter()
+                .map(|values| Event {
+                    event_id: "".to_string(),
+                    stream: stream.to_string(),
+                    values,
+                })
+                .collect(),
+        };
+        let ids = host_call(
+            &self.binding,
+            CAPID_EVENTS,
+            OP_WRITE_EVENT_BATCH,
+            &serialize(cmd)?,
+        )
+        .map_err(|e| e.into())
+        .and_then(|v| deserialize::<BatchWriteResponse>(v.as_ref()))?
+        .event_ids;
+        if ids.len() != expected {
+            return Err(format!(
+                "Provider returned {} event IDs for a batch of {} events",
+                ids.len(),
+                expected
+            )
+            .into());
+        }
+        Ok(ids)
+    }
+
+    /// Writes a typed event to a named stream. The value is stored as JSON under `BODY_KEY`,
+    /// and each of its top-level scalar fields is also stored as a separate value, so that
+    /// providers can continue to index and filter events by field
+    pub fn write_event_typed<T: Serialize>(
+        &self,
+        stream: &str,
+        value: &T,
+    ) -> HandlerResult<String> {
+        self.write_event(stream, typed_values(value)?)
+    }
+
+    /// Reads all available events from the given stream, decoding each as a typed event
+    pub fn read_typed<T: DeserializeOwned>(&self, stream: &str) -> HandlerResult<Vec<T>> {
+        self.read_all(stream)?.iter().map(event_body).collect()
+    }
+
     /// Reads all available events from the given stream
     pub fn read_all(&self, stream: &str) -> HandlerResult<Vec<Event>> {
         let query = self.generate_query(0, stream, None);
@@ -62,20 +436,304 @@ impl EventStreamsHostBinding {
         self.execute_query(query)
     }
 
-    fn execute_query(&self, query: StreamQuery) -> HandlerResult<Vec<Event>> {
+    /// Reads up to `max_count` events that were written after the event with the given ID,
+    /// in stream order. Pass an empty ID to read from the start of the stream. Actors can
+    /// consume a stream incrementally by checkpointing the ID of the last event they
+    /// processed and passing it to the next call. This uses the `OP_READ_STREAM_AFTER`
+    /// operation, so providers that don't support it return an error rather than replaying
+    /// the stream from the start
+    pub fn read_from(
+        &self,
+        stream: &str,
+        starting_id: &str,
+        max_count: u64,
+    ) -> HandlerResult<Vec<Event>> {
+        let cmd = ReadAfterRequest {
+            stream_id: stream.to_string(),
+            after_id: starting_id.to_string(),
+            count: max_count,
+        };
+        host_call(
+            &self.binding,
+            CAPID_EVENTS,
+            OP_READ_STREAM_AFTER,
+            &serialize(cmd)?,
+        )
+        .map_err(|e| e.into())
+        .and_then(|v| deserialize::<StreamResults>(v.as_ref()))
+        .map(|r| r.events)
+    }
+
+    /// Rebuilds a state value by folding every event of a stream into it, in order. The
+    /// stream is read a page at a time, so it never needs to fit in memory
+    pub fn replay<S, F>(&self, stream: &str, initial_state: S, fold: F) -> HandlerResult<S>
+    where
+        F: FnMut(S, &Event) -> HandlerResult<S>,
+    {
+        self.replay_after(
+            stream,
+            Snapshot {
+                state: initial_state,
+                last_event_id: "".to_string(),
+            },
+            fold,
+        )
+        .map(|snapshot| snapshot.state)
+    }
+
+    /// Rebuilds a state value like `replay`, starting from the snapshot saved in the
+    /// key-value store under the given key (if any) and folding only the events written
+    /// since. The updated snapshot is saved before the state is returned
+    pub fn replay_with_snapshot<S, F>(
+        &self,
+        stream: &str,
+        store: &KeyValueStoreHostBinding,
+        key: &str,
+        initial_state: S,
+        fold: F,
+    ) -> HandlerResult<S>
+    where
+        S: Serialize + DeserializeOwned,
+        F: FnMut(S, &Event) -> HandlerResult<S>,
+    {
+        let start = match store.get(key)? {
+            Some(json) => serde_json::from_str(&json)?,
+            None => Snapshot {
+                state: initial_state,
+                last_event_id: "".to_string(),
+            },
+        };
+        let snapshot = self.replay_after(stream, start, fold)?;
+        store.set(key, &serde_json::to_string(&snapshot)?, None)?;
+        Ok(snapshot.state)
+    }
+
+    fn replay_after<S, F>(
+        &self,
+        stream: &str,
+        mut snapshot: Snapshot<S>,
+        mut fold: F,
+    ) -> HandlerResult<Snapshot<S>>
+    where
+        F: FnMut(S, &Event) -> HandlerResult<S>,
+    {
+        loop {
+            let events = self.read_from(stream, &snapshot.last_event_id, REPLAY_PAGE_SIZE)?;
+            let last_event_id = match events.last() {
+                Some(ev) => ev.event_id.to_string(),
+                None => return Ok(snapshot),
+            };
+            if last_event_id == snapshot.last_event_id {
+                return Err(format!(
+                    "Stream '{}' did not advance past event '{}'",
+                    stream, last_event_id
+                )
+                .into());
+            }
+            for ev in &events {
+                snapshot.state = fold(snapshot.state, ev)?;
+            }
+            snapshot.last_event_id = last_event_id;
+            if (events.len() as u64) < REPLAY_PAGE_SIZE {
+                return Ok(snapshot);
+            }
+        }
+    }
+
+    /// Reads a single page of at most `limit` events. Pass `None` to read the first page,
+    /// then the cursor of each page to read the next, until a page is returned without a
+    /// cursor. This uses the `OP_QUERY_STREAM_PAGE` operation, so providers that can't page
+    /// through a stream return an error rather than the first page again
+    pub fn read_page(
+        &self,
+        stream: &str,
+        cursor: Option<String>,
+        limit: u64,
+    ) -> HandlerResult<EventPage> {
+        self.read_page_filtered(stream, cursor, limit, HashMap::new())
+    }
+
+    /// Reads a single page of events like `read_page`, returning only events whose values
+    /// contain every entry in the filter. Filtering is performed by the provider, so events
+    /// that don't match are never loaded into the actor
+    pub fn read_page_filtered(
+        &self,
+        stream: &str,
+        cursor: Option<String>,
+        limit: u64,
+        filter: HashMap<String, String>,
+    ) -> HandlerResult<EventPage> {
+        let cmd = PageRequest {
+            stream_id: stream.to_string(),
+            count: limit,
+            cursor: cursor.unwrap_or_default(),
+            filter,
+        };
+        host_call(
+            &self.binding,
+            CAPID_EVENTS,
+            OP_QUERY_STREAM_PAGE,
+            &serialize(cmd)?,
+        )
+        .map_err(|e| e.into())
+        .and_then(|v| deserialize::<StreamPage>(v.as_ref()))
+        .map(|r| EventPage {
+            events: r.events,
+            cursor: if r.next_cursor.is_empty() {
+                None
+            } else {
+                Some(r.next_cursor)
+            },
+        })
+    }
+
+    /// Obtains summary information about a stream, from which consumers can determine how
+    /// far behind they are. A stream that does not exist is reported as empty
+    pub fn stream_info(&self, stream: &str) -> HandlerResult<StreamInfo> {
+        let cmd = StreamRef {
+            stream_id: stream.to_string(),
+        };
+        host_call(
+            &self.binding,
+            CAPID_EVENTS,
+            OP_QUERY_STREAM_INFO,
+            &serialize(cmd)?,
+        )
+        .map_err(|e| e.into())
+        .and_then(|v| deserialize::<StreamInfo>(v.as_ref()))
+    }
+
+    /// Deletes a stream and all of its events
+    pub fn delete_stream(&self, stream: &str) -> HandlerResult<()> {
+        let cmd = StreamRef {
+            stream_id: stream.to_string(),
+        };
+        host_call(
+            &self.binding,
+            CAPID_EVENTS,
+            OP_DELETE_STREAM,
+            &serialize(cmd)?,
+        )
+        .map(|_v| ())
+        .map_err(|e| e.into())
+    }
+
+    /// Removes the event with the given ID and every event that precedes it from a stream,
+    /// leaving later events in place
+    pub fn truncate(&self, stream: &str, up_to_id: &str) -> HandlerResult<()> {
+        let cmd = StreamTruncation {
+            stream_id: stream.to_string(),
+            up_to_id: up_to_id.to_string(),
+        };
+        host_call(
+            &self.binding,
+            CAPID_EVENTS,
+            OP_TRUNCATE_STREAM,
+            &serialize(cmd)?,
+        )
+        .map(|_v| ())
+        .map_err(|e| e.into())
+    }
+
+    /// Subscribes to a stream. The provider delivers existing events from the given position
+    /// and then each new event as it is appended, via the `OP_DELIVER_EVENT` operation
+    pub fn subscribe(&self, stream: &str, from: SubscribeFrom) -> HandlerResult<()> {
+        let (new_only, after_id) = match from {
+            SubscribeFrom::Beginning => (false, "".to_string()),
+            SubscribeFrom::Now => (true, "".to_string()),
+            SubscribeFrom::After(id) => (false, id),
+        };
+        self.manage_subscription(
+            OP_SUBSCRIBE_STREAM,
+            StreamSubscription {
+                stream_id: stream.to_string(),
+                new_only,
+                after_id,
+            },
+        )
+    }
+
+    /// Stops the delivery of events from a stream
+    pub fn unsubscribe(&self, stream: &str) -> HandlerResult<()> {
+        self.manage_subscription(
+            OP_UNSUBSCRIBE_STREAM,
+            StreamSubscription {
+                stream_id: stream.to_string(),
+                new_only: false,
+                after_id: "".to_string(),
+            },
+        )
+    }
+
+    /// Creates a consumer group on a stream. Creating a group that already exists leaves its
+    /// position in the stream unchanged, so every actor instance can call this on startup
+    pub fn create_consumer(&self, stream: &str, group: &str) -> HandlerResult<()> {
+        let cmd = ConsumerGroup {
+            stream_id: stream.to_string(),
+            group: group.to_string(),
+        };
+        host_call(
+            &self.binding,
+            CAPID_EVENTS,
+            OP_CREATE_CONSUMER_GROUP,
+            &serialize(cmd)?,
+        )
+        .map(|_v| ())
+        .map_err(|e| e.into())
+    }
+
+    /// Claims up to `max` events of a stream for a consumer group. Claimed events are not
+    /// handed to other members of the group unless they go unacknowledged
+    pub fn fetch(&self, stream: &str, group: &str, max: u64) -> HandlerResult<Vec<Event>> {
+        let cmd = GroupFetch {
+            stream_id: stream.to_string(),
+            group: group.to_string(),
+            max_events: max,
+        };
+        host_call(
+            &self.binding,
+            CAPID_EVENTS,
+            OP_FETCH_GROUP_EVENTS,
+            &serialize(cmd)?,
+        )
+        .map_err(|e| e.into())
+        .and_then(|v| deserialize::<StreamResults>(v.as_ref()))
+        .map(|r| r.events)
+    }
+
+    /// Acknowledges that a consumer group has finished processing an event of a stream
+    pub fn ack(&self, stream: &str, group: &str, event_id: &str) -> HandlerResult<()> {
+        let cmd = GroupAck {
+            stream_id: stream.to_string(),
+            group: group.to_string(),
+            event_id: event_id.to_string(),
+        };
+        host_call(
+            &self.binding,
+            CAPID_EVENTS,
+            OP_ACK_GROUP_EVENT,
+            &serialize(cmd)?,
+        )
+        .map(|_v| ())
+        .map_err(|e| e.into())
+    }
+
+    fn manage_subscription(&self, op: &str, cmd: StreamSubscription) -> HandlerResult<()> {
+        host_call(&self.binding, CAPID_EVENTS, op, &serialize(cmd)?)
+            .map(|_v| ())
+            .map_err(|e| e.into())
+    }
+
+    fn execute_query<Q: Serialize>(&self, query: Q) -> HandlerResult<Vec<Event>> {
         host_call(
             &self.binding,
             CAPID_EVENTS,
             OP_QUERY_STREAM,
             &serialize(query)?,
         )
-        .map(|v| {
-            deserialize::<StreamResults>(v.as_ref())
-                .unwrap()
-                .events
-                .clone()
-        })
         .map_err(|e| e.into())
+        .and_then(|v| deserialize::<StreamResults>(v.as_ref()))
+        .map(|r| r.events)
     }
 
     fn generate_query(&self, count: u64, stream: &str, range: Option<TimeRange>) -> StreamQuery {
@@ -86,3 +744,33 @@ impl EventStreamsHostBinding {
         }
     }
 }
+
+/// Decodes the body of an event written with `write_event_typed`. Events written as plain
+/// value maps are decoded from their values instead
+pub fn event_body<T: DeserializeOwned>(event: &Event) -> HandlerResult<T> {
+    match event.values.get(BODY_KEY) {
+        Some(body) => serde_json::from_str(body),
+        None => serde_json::from_value(serde_json::to_value(&event.values)?),
+    }
+    .map_err(|e| format!("Cannot decode body of event '{}': {}", event.event_id, e).into())
+}
+
+fn typed_values<T: Serialize>(value: &T) -> HandlerResult<HashMap<String, String>> {
+    let json = serde_json::to_value(value)?;
+    let mut values = HashMap::new();
+    if let serde_json::Value::Object(ref fields) = json {
+        for (name, field) in fields {
+            match field {
+                serde_json::Value::String(s) => {
+                    values.insert(name.to_string(), s.to_string());
+                }
+                serde_json::Value::Number(_) | serde_json::Value::Bool(_) => {
+                    values.insert(name.to_string(), field.to_string());
+                }
+                _ => {}
+            }
+        }
+    }
+    values.insert(BODY_KEY.to_string(), json.to_string());
+    Ok(values)
+}
@@ -1,9 +1,119 @@
+use serde_derive::{Deserialize, Serialize};
+use std::collections::HashMap;
 use wapc_guest::host_call;
 use wascc_codec::extras::*;
 use wascc_codec::{deserialize, serialize};
 
 const CAPID_EXTRAS: &str = "wascc:extras";
 
+/// The operation to request a number of random bytes from the host's secure RNG
+pub const OP_REQUEST_RANDOM_BYTES: &str = "RequestRandomBytes";
+
+/// Upper and lower case letters and digits, a common alphabet for `get_token`
+pub const TOKEN_ALPHANUMERIC: &str =
+    "ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789";
+
+/// The Crockford base32 alphabet used to encode ULIDs
+const ULID_ALPHABET: &[u8] = b"0123456789ABCDEFGHJKMNPQRSTVWXYZ";
+
+/// The operation to request the host's current time
+pub const OP_REQUEST_TIME: &str = "RequestTime";
+
+/// The current time according to the host
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct HostTime {
+    /// Wall-clock time in milliseconds since the Unix epoch, suitable for timestamps
+    pub epoch_ms: u64,
+    /// A monotonic clock reading in nanoseconds, suitable for measuring durations. Only
+    /// differences between readings are meaningful
+    pub monotonic_ns: u64,
+}
+
+/// Wire-compatible with the codec's `GeneratorRequest`, with the addition of the name of
+/// the sequence to draw from
+#[derive(Debug, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct NamedGeneratorRequest {
+    guid: bool,
+    sequence: bool,
+    random: bool,
+    min: u32,
+    max: u32,
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    sequence_name: String,
+}
+
+/// The operation to request information about the host running the actor
+pub const OP_REQUEST_HOST_INFO: &str = "RequestHostInfo";
+
+/// Information about the host running the actor
+#[derive(Debug, Clone, PartialEq, Default, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct HostInfo {
+    /// The unique ID (public key) of the host
+    pub host_id: String,
+    /// The version of the host runtime
+    #[serde(default)]
+    pub host_version: String,
+    /// The labels configured on the host, such as its region or hardware class
+    #[serde(default)]
+    pub labels: HashMap<String, String>,
+    /// Free-form annotations configured on the host
+    #[serde(default)]
+    pub annotations: HashMap<String, String>,
+}
+
+/// The operation to request the identity and claims of the calling actor
+pub const OP_REQUEST_IDENTITY: &str = "RequestIdentity";
+
+/// The identity of an actor and the claims embedded in its signed module
+#[derive(Debug, Clone, PartialEq, Default, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ActorIdentity {
+    /// The actor's public key (the subject of its module's claims)
+    pub public_key: String,
+    /// The public key of the account that issued (signed) the actor
+    pub issuer: String,
+    /// The friendly name of the actor, if any
+    #[serde(default)]
+    pub name: String,
+    /// The capability IDs the actor is authorized to use, e.g. `wascc:keyvalue`
+    #[serde(default)]
+    pub capabilities: Vec<String>,
+    /// The tags embedded in the actor's claims
+    #[serde(default)]
+    pub tags: Vec<String>,
+    /// Any additional, non-standard claims embedded in the actor's token
+    #[serde(default)]
+    pub custom_claims: HashMap<String, String>,
+}
+
+impl ActorIdentity {
+    /// Indicates whether the actor's claims authorize it to use the given capability
+    pub fn has_capability(&self, capid: &str) -> bool {
+        self.capabilities.iter().any(|c| c == capid)
+    }
+}
+
+/// A request for a number of random bytes
+#[derive(Debug, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RandomBytesRequest {
+    /// The number of bytes to generate
+    pub len: u32,
+}
+
+/// The random bytes generated by the host
+#[derive(Debug, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RandomBytesResult {
+    /// The generated bytes
+    #[serde(with = "serde_bytes")]
+    #[serde(default)]
+    pub bytes: Vec<u8>,
+}
+
 /// A hsot binding for the wascc:extras capability
 pub struct ExtrasHostBinding {
     binding: String,
@@ -47,6 +157,52 @@ impl ExtrasHostBinding {
         .map_err(|e| e.into())
     }
 
+    /// Queries the host for the given number of bytes from a cryptographically secure RNG,
+    /// suitable for salts, nonces and keys
+    pub fn get_random_bytes(&self, len: u32) -> crate::HandlerResult<Vec<u8>> {
+        let cmd = RandomBytesRequest { len };
+        let bytes = host_call(
+            &self.binding,
+            CAPID_EXTRAS,
+            OP_REQUEST_RANDOM_BYTES,
+            &serialize(cmd)?,
+        )
+        .map_err(|e| e.into())
+        .and_then(|v| deserialize::<RandomBytesResult>(v.as_ref()))?
+        .bytes;
+        if bytes.len() != len as usize {
+            return Err(format!(
+                "Host returned {} random bytes, {} were requested",
+                bytes.len(),
+                len
+            )
+            .into());
+        }
+        Ok(bytes)
+    }
+
+    /// Queries the host for the current wall-clock and monotonic time
+    pub fn get_time(&self) -> crate::HandlerResult<HostTime> {
+        host_call(&self.binding, CAPID_EXTRAS, OP_REQUEST_TIME, &[])
+            .map_err(|e| e.into())
+            .and_then(|v| deserialize::<HostTime>(v.as_ref()))
+    }
+
+    /// Queries the host for its identity, version, and configured labels and annotations
+    pub fn host_info(&self) -> crate::HandlerResult<HostInfo> {
+        host_call(&self.binding, CAPID_EXTRAS, OP_REQUEST_HOST_INFO, &[])
+            .map_err(|e| e.into())
+            .and_then(|v| deserialize::<HostInfo>(v.as_ref()))
+    }
+
+    /// Queries the host for the calling actor's own identity and claims, so that an actor
+    /// can report or enforce its authorization posture
+    pub fn identity(&self) -> crate::HandlerResult<ActorIdentity> {
+        host_call(&self.binding, CAPID_EXTRAS, OP_REQUEST_IDENTITY, &[])
+            .map_err(|e| e.into())
+            .and_then(|v| deserialize::<ActorIdentity>(v.as_ref()))
+    }
+
     /// Requests a newly generated GUID string from the host
     pub fn get_guid(&self) -> crate::HandlerResult<String> {
         let cmd = GeneratorRequest {
@@ -67,6 +223,24 @@ impl ExtrasHostBinding {
         .map_err(|e| e.into())
     }
 
+    /// Generates a ULID: a 26-character identifier made of a millisecond timestamp from the
+    /// host clock followed by 80 random bits from the host RNG. ULIDs sort lexicographically
+    /// in creation order, which makes them well suited to key-value range queries and event
+    /// IDs
+    pub fn get_ulid(&self) -> crate::HandlerResult<String> {
+        let time = self.get_time()?;
+        let random = self.get_random_bytes(10)?;
+        let value = random
+            .iter()
+            .fold(u128::from(time.epoch_ms & 0xFFFF_FFFF_FFFF), |acc, b| {
+                (acc << 8) | u128::from(*b)
+            });
+        Ok((0..26)
+            .rev()
+            .map(|i| ULID_ALPHABET[((value >> (i * 5)) & 0x1F) as usize] as char)
+            .collect())
+    }
+
     /// Requests a sequence number from the host. Note that the sequence number will only be
     /// unique within the host, and is not globally unique
     pub fn get_sequence_number(&self) -> crate::HandlerResult<u64> {
@@ -87,4 +261,174 @@ impl ExtrasHostBinding {
         .map(|r| r.sequence_number)
         .map_err(|e| e.into())
     }
+
+    /// Requests the next number of a named sequence from the host. Each name draws from an
+    /// independent sequence, so different subsystems of an actor don't share a counter. As
+    /// with `get_sequence_number`, the number is only unique within the host
+    pub fn get_sequence_number_named(&self, name: &str) -> crate::HandlerResult<u64> {
+        if name.is_empty() {
+            return Err("Sequence name cannot be empty".into());
+        }
+        let cmd = NamedGeneratorRequest {
+            sequence: true,
+            guid: false,
+            random: false,
+            min: 0,
+            max: 0,
+            sequence_name: name.to_string(),
+        };
+        host_call(
+            &self.binding,
+            CAPID_EXTRAS,
+            OP_REQUEST_SEQUENCE,
+            &serialize(cmd)?,
+        )
+        .map_err(|e| e.into())
+        .and_then(|v| deserialize::<GeneratorResult>(v.as_ref()))
+        .map(|r| r.sequence_number)
+    }
+
+    /// Queries the host RNG for a uniformly distributed number in the range `[min, max)`
+    pub fn get_random_u64(&self, min: u64, max: u64) -> crate::HandlerResult<u64> {
+        if min >= max {
+            return Err(format!("Invalid random range {}..{}", min, max).into());
+        }
+        EntropyPool::new(self, 1)
+            .next_below(max - min)
+            .map(|offset| min + offset)
+    }
+
+    /// Queries the host RNG for a uniformly distributed signed number in the range
+    /// `[min, max)`
+    pub fn get_random_i64(&self, min: i64, max: i64) -> crate::HandlerResult<i64> {
+        if min >= max {
+            return Err(format!("Invalid random range {}..{}", min, max).into());
+        }
+        let span = (i128::from(max) - i128::from(min)) as u64;
+        EntropyPool::new(self, 1)
+            .next_below(span)
+            .map(|offset| (i128::from(min) + i128::from(offset)) as i64)
+    }
+
+    /// Queries the host RNG for a uniformly distributed float in the range `[0, 1)`
+    pub fn get_random_float(&self) -> crate::HandlerResult<f64> {
+        let bits = EntropyPool::new(self, 1).next_u64()? >> 11;
+        Ok(bits as f64 / (1u64 << 53) as f64)
+    }
+
+    /// Generates a random string of `len` characters drawn uniformly from the given alphabet
+    /// using the host's secure RNG, suitable for API keys and invite codes
+    pub fn get_token(&self, len: usize, alphabet: &str) -> crate::HandlerResult<String> {
+        let symbols: Vec<char> = alphabet.chars().collect();
+        if symbols.is_empty() {
+            return Err("Token alphabet cannot be empty".into());
+        }
+        let mut pool = EntropyPool::new(self, len);
+        (0..len)
+            .map(|_| {
+                pool.next_below(symbols.len() as u64)
+                    .map(|i| symbols[i as usize])
+            })
+            .collect()
+    }
+
+    /// Chooses an item from a slice uniformly at random using the host RNG. Returns `None`
+    /// if the slice is empty
+    pub fn choose<'a, T>(&self, items: &'a [T]) -> crate::HandlerResult<Option<&'a T>> {
+        if items.is_empty() {
+            return Ok(None);
+        }
+        let index = EntropyPool::new(self, 1).next_below(items.len() as u64)?;
+        Ok(items.get(index as usize))
+    }
+
+    /// Shuffles a slice in place using the host RNG, with every permutation equally likely
+    pub fn shuffle<T>(&self, items: &mut [T]) -> crate::HandlerResult<()> {
+        let mut pool = EntropyPool::new(self, items.len());
+        for i in (1..items.len()).rev() {
+            let j = pool.next_below(i as u64 + 1)?;
+            items.swap(i, j as usize);
+        }
+        Ok(())
+    }
+}
+
+/// Buffers random bytes fetched from the host, so that helpers needing many random numbers
+/// don't make a host call for each one
+struct EntropyPool<'a> {
+    extras: &'a ExtrasHostBinding,
+    bytes: Vec<u8>,
+    batch: usize,
+}
+
+impl<'a> EntropyPool<'a> {
+    fn new(extras: &'a ExtrasHostBinding, expected_draws: usize) -> EntropyPool<'a> {
+        EntropyPool {
+            extras,
+            bytes: Vec::new(),
+            batch: expected_draws.clamp(1, 512),
+        }
+    }
+
+    fn next_u64(&mut self) -> crate::HandlerResult<u64> {
+        if self.bytes.len() < 8 {
+            self.bytes = self.extras.get_random_bytes(self.batch as u32 * 8)?;
+        }
+        let mut word = [0u8; 8];
+        word.copy_from_slice(&self.bytes[self.bytes.len() - 8..]);
+        self.bytes.truncate(self.bytes.len() - 8);
+        Ok(u64::from_le_bytes(word))
+    }
+
+    /// Draws a uniformly distributed number in the range `[0, bound)`, rejecting draws that
+    /// would bias the result towards smaller numbers
+    fn next_below(&mut self, bound: u64) -> crate::HandlerResult<u64> {
+        let zone = u64::MAX - (u64::MAX % bound + 1) % bound;
+        loop {
+            let x = self.next_u64()?;
+            if x <= zone {
+                return Ok(x % bound);
+            }
+        }
+    }
+}
+
+/// Measures elapsed time using the host's monotonic clock, for timing sub-operations of a
+/// handler. Each reading is a host call, so a stopwatch is cheap to hold but not free to read
+pub struct Stopwatch {
+    extras: ExtrasHostBinding,
+    started_ns: u64,
+}
+
+impl Stopwatch {
+    /// Starts a stopwatch using the default extras binding
+    pub fn start() -> crate::HandlerResult<Stopwatch> {
+        Stopwatch::start_with(default())
+    }
+
+    /// Starts a stopwatch using the given extras binding
+    pub fn start_with(extras: ExtrasHostBinding) -> crate::HandlerResult<Stopwatch> {
+        let started_ns = extras.get_time()?.monotonic_ns;
+        Ok(Stopwatch { extras, started_ns })
+    }
+
+    /// The number of nanoseconds elapsed since the stopwatch was started
+    pub fn elapsed_ns(&self) -> crate::HandlerResult<u64> {
+        let now = self.extras.get_time()?.monotonic_ns;
+        Ok(now.saturating_sub(self.started_ns))
+    }
+
+    /// The number of whole milliseconds elapsed since the stopwatch was started
+    pub fn elapsed_ms(&self) -> crate::HandlerResult<u64> {
+        self.elapsed_ns().map(|ns| ns / 1_000_000)
+    }
+
+    /// Restarts the stopwatch, returning the number of milliseconds elapsed before it was
+    /// restarted
+    pub fn lap_ms(&mut self) -> crate::HandlerResult<u64> {
+        let now = self.extras.get_time()?.monotonic_ns;
+        let elapsed = now.saturating_sub(self.started_ns);
+        self.started_ns = now;
+        Ok(elapsed / 1_000_000)
+    }
 }
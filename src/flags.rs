@@ -0,0 +1,227 @@
+//! # Feature Flags
+//!
+//! This module contains the client through which actors evaluate feature flags with the
+//! currently bound `wascc:flags` capability provider. Flags are evaluated against an
+//! evaluation context (user ID, region, plan, etc.) so the provider can apply its targeting
+//! and percentage rollout rules.
+//!
+//! Evaluations are cached in the actor for a short time (one second by default, see
+//! `cache_ttl_ms`), so a handler that checks the same flag repeatedly, or a burst of
+//! requests for the same context, does not ask the flags provider for every check. The cache
+//! depends on the host clock (`wascc:extras`): each check still reads the clock to tell
+//! whether a cached evaluation has expired, and on hosts without a clock nothing is cached
+//!
+//! ```
+//! extern crate wascc_actor as actor;
+//! use actor::prelude::*;
+//! use actor::flags::EvaluationContext;
+//!
+//! fn checkout_page(user_id: &str, region: &str) -> HandlerResult<&'static str> {
+//!     let context = EvaluationContext::new().set("userId", user_id).set("region", region);
+//!     if flags::default().is_enabled("new-checkout", &context)? {
+//!         Ok("checkout-v2.html")
+//!     } else {
+//!         Ok("checkout.html")
+//!     }
+//! }
+//! ```
+
+use serde_derive::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::RwLock;
+use wapc_guest::host_call;
+use wascc_codec::{deserialize, serialize};
+
+use crate::HandlerResult;
+
+/// The reserved capability ID for feature flag functionality
+pub const CAPID_FLAGS: &str = "wascc:flags";
+
+/// Guest sends an `EvaluationRequest` to the capability provider, receives an `Evaluation` back
+pub const OP_EVALUATE_FLAG: &str = "EvaluateFlag";
+
+/// The number of milliseconds evaluations are cached for unless configured otherwise
+pub const DEFAULT_CACHE_TTL_MS: u64 = 1_000;
+
+static NO_CLOCK: AtomicBool = AtomicBool::new(false);
+
+lazy_static! {
+    static ref CACHE: RwLock<HashMap<String, CachedEvaluation>> = RwLock::new(HashMap::new());
+}
+
+struct CachedEvaluation {
+    evaluation: Evaluation,
+    expires_ns: u64,
+}
+
+/// The attributes a flag is evaluated against
+#[derive(Debug, Clone, PartialEq, Default, Deserialize, Serialize)]
+pub struct EvaluationContext(BTreeMap<String, String>);
+
+impl EvaluationContext {
+    /// Creates an empty context
+    pub fn new() -> EvaluationContext {
+        EvaluationContext::default()
+    }
+
+    /// Sets an attribute
+    pub fn set(mut self, name: &str, value: &str) -> EvaluationContext {
+        self.0.insert(name.to_string(), value.to_string());
+        self
+    }
+
+    fn cache_key(&self, binding: &str, flag: &str) -> String {
+        let mut key = format!("{}\u{0}{}", binding, flag);
+        for (k, v) in &self.0 {
+            key.push('\u{0}');
+            key.push_str(k);
+            key.push('=');
+            key.push_str(v);
+        }
+        key
+    }
+}
+
+impl From<HashMap<String, String>> for EvaluationContext {
+    fn from(map: HashMap<String, String>) -> EvaluationContext {
+        EvaluationContext(map.into_iter().collect())
+    }
+}
+
+/// A request to evaluate a flag
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct EvaluationRequest {
+    /// The key of the flag
+    pub flag: String,
+    /// The attributes the flag is evaluated against
+    #[serde(default)]
+    pub context: EvaluationContext,
+}
+
+/// The result of evaluating a flag
+#[derive(Debug, Clone, PartialEq, Default, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Evaluation {
+    /// Indicates whether the flag is enabled for the context
+    pub enabled: bool,
+    /// The variant selected for the context, for multivariate flags
+    #[serde(default)]
+    pub variant: Option<String>,
+}
+
+/// Discards all cached evaluations, so the next check of every flag is answered by the
+/// provider
+pub fn clear_cache() {
+    CACHE.write().unwrap().clear();
+}
+
+/// An abstraction around a host runtime capability for feature flags
+pub struct FlagsHostBinding {
+    binding: String,
+    cache_ttl_ms: u64,
+}
+
+impl Default for FlagsHostBinding {
+    fn default() -> Self {
+        FlagsHostBinding {
+            binding: "default".to_string(),
+            cache_ttl_ms: DEFAULT_CACHE_TTL_MS,
+        }
+    }
+}
+
+/// Creates a named host binding for the feature flag capability
+pub fn host(binding: &str) -> FlagsHostBinding {
+    FlagsHostBinding {
+        binding: binding.to_string(),
+        ..Default::default()
+    }
+}
+
+/// Creates the default host binding for the feature flag capability
+pub fn default() -> FlagsHostBinding {
+    FlagsHostBinding::default()
+}
+
+impl FlagsHostBinding {
+    /// Sets the number of milliseconds evaluations made through this binding are cached
+    /// for. Zero disables caching, which also saves the clock read made for every check
+    /// while caching is enabled
+    pub fn cache_ttl_ms(mut self, ttl_ms: u64) -> FlagsHostBinding {
+        self.cache_ttl_ms = ttl_ms;
+        self
+    }
+
+    /// Indicates whether a flag is enabled for the given context
+    pub fn is_enabled(&self, flag: &str, context: &EvaluationContext) -> HandlerResult<bool> {
+        Ok(self.evaluate(flag, context)?.enabled)
+    }
+
+    /// The variant of a multivariate flag selected for an empty context
+    pub fn variant(&self, flag: &str) -> HandlerResult<Option<String>> {
+        self.variant_for(flag, &EvaluationContext::new())
+    }
+
+    /// The variant of a multivariate flag selected for the given context
+    pub fn variant_for(
+        &self,
+        flag: &str,
+        context: &EvaluationContext,
+    ) -> HandlerResult<Option<String>> {
+        Ok(self.evaluate(flag, context)?.variant)
+    }
+
+    /// Evaluates a flag for the given context, answering from the cache when a recent
+    /// evaluation of the same flag and context is available
+    pub fn evaluate(&self, flag: &str, context: &EvaluationContext) -> HandlerResult<Evaluation> {
+        // If the host clock is unavailable, evaluations go uncached, and the clock isn't
+        // asked again
+        let now_ns = if self.cache_ttl_ms > 0 && !NO_CLOCK.load(Ordering::Relaxed) {
+            match crate::extras::default().get_time() {
+                Ok(t) => Some(t.monotonic_ns),
+                Err(_) => {
+                    NO_CLOCK.store(true, Ordering::Relaxed);
+                    None
+                }
+            }
+        } else {
+            None
+        };
+        let key = context.cache_key(&self.binding, flag);
+        if let Some(now_ns) = now_ns {
+            if let Some(cached) = CACHE.read().unwrap().get(&key) {
+                if cached.expires_ns > now_ns {
+                    return Ok(cached.evaluation.clone());
+                }
+            }
+        }
+
+        let cmd = EvaluationRequest {
+            flag: flag.to_string(),
+            context: context.clone(),
+        };
+        let evaluation = host_call(
+            &self.binding,
+            CAPID_FLAGS,
+            OP_EVALUATE_FLAG,
+            &serialize(cmd)?,
+        )
+        .map_err(|e| e.into())
+        .and_then(|v| deserialize::<Evaluation>(v.as_ref()))?;
+
+        if let Some(now_ns) = now_ns {
+            let mut cache = CACHE.write().unwrap();
+            cache.retain(|_, c| c.expires_ns > now_ns);
+            cache.insert(
+                key,
+                CachedEvaluation {
+                    evaluation: evaluation.clone(),
+                    expires_ns: now_ns.saturating_add(self.cache_ttl_ms.saturating_mul(1_000_000)),
+                },
+            );
+        }
+        Ok(evaluation)
+    }
+}
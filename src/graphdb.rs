@@ -0,0 +1,244 @@
+//! # Graph Database
+//!
+//! This module contains the graph database client through which actors consume the
+//! currently bound `wascc:graphdb` capability provider. Queries are written in the
+//! provider's query language (Cypher, Gremlin, etc.) and their parameters are sent
+//! separately from the query text
+//!
+//! ```
+//! extern crate wascc_actor as actor;
+//! use actor::prelude::*;
+//! use actor::graphdb::Node;
+//! use serde_derive::Deserialize;
+//!
+//! #[derive(Deserialize)]
+//! struct Friend {
+//!     name: String,
+//!     since: u32,
+//! }
+//!
+//! fn friends_of(name: &str) -> HandlerResult<Vec<Friend>> {
+//!     graphdb::default()
+//!         .query(
+//!             "social",
+//!             "MATCH (:Person {name: $name})-[f:FRIEND]->(p:Person) \
+//!              RETURN p.name AS name, f.since AS since",
+//!         )
+//!         .param("name", name)
+//!         .fetch()
+//! }
+//!
+//! fn people() -> HandlerResult<Vec<Node>> {
+//!     graphdb::default()
+//!         .query("social", "MATCH (p:Person) RETURN p")
+//!         .run()?
+//!         .column("p")
+//! }
+//! ```
+
+use serde::de::DeserializeOwned;
+use serde_derive::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+use std::collections::HashMap;
+use wapc_guest::host_call;
+use wascc_codec::{deserialize, serialize};
+
+use crate::HandlerResult;
+
+/// The reserved capability ID for graph database functionality
+pub const CAPID_GRAPHDB: &str = "wascc:graphdb";
+
+/// Guest sends a `QueryRequest` to the capability provider, receives a `ResultSet` back
+pub const OP_QUERY: &str = "QueryGraph";
+/// Guest sends a `DeleteGraphRequest` to the capability provider, receives nothing back
+pub const OP_DELETE_GRAPH: &str = "DeleteGraph";
+
+/// A query against a named graph, with its parameters
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct QueryRequest {
+    /// The name of the graph to query
+    pub graph_name: String,
+    /// The text of the query
+    pub query: String,
+    /// The values of the query's named parameters
+    #[serde(default)]
+    pub params: HashMap<String, Value>,
+}
+
+/// A request to delete a graph and everything in it
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DeleteGraphRequest {
+    /// The name of the graph to delete
+    pub graph_name: String,
+}
+
+/// The results of a query
+#[derive(Debug, Clone, PartialEq, Default, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ResultSet {
+    /// The names of the returned columns
+    pub columns: Vec<String>,
+    /// The rows, each holding one value per column. Nodes and relationships are represented
+    /// as maps that decode into `Node` and `Relationship`
+    pub rows: Vec<Vec<Value>>,
+    /// Counters reported by the provider, such as the number of nodes created
+    #[serde(default)]
+    pub statistics: HashMap<String, i64>,
+}
+
+impl ResultSet {
+    /// Decodes each row into a type whose fields are named after the returned columns
+    pub fn decode<T: DeserializeOwned>(&self) -> HandlerResult<Vec<T>> {
+        self.rows
+            .iter()
+            .map(|row| {
+                let record: Map<String, Value> = self
+                    .columns
+                    .iter()
+                    .cloned()
+                    .zip(row.iter().cloned())
+                    .collect();
+                serde_json::from_value(Value::Object(record))
+                    .map_err(|e| format!("Failed to decode row: {}", e).into())
+            })
+            .collect()
+    }
+
+    /// Decodes the values of a single column
+    pub fn column<T: DeserializeOwned>(&self, name: &str) -> HandlerResult<Vec<T>> {
+        let index = self
+            .columns
+            .iter()
+            .position(|c| c == name)
+            .ok_or_else(|| format!("No such column in results: {}", name))?;
+        self.rows
+            .iter()
+            .map(|row| {
+                let value = row.get(index).cloned().unwrap_or(Value::Null);
+                serde_json::from_value(value)
+                    .map_err(|e| format!("Failed to decode column {}: {}", name, e).into())
+            })
+            .collect()
+    }
+}
+
+/// A node returned from a query
+#[derive(Debug, Clone, PartialEq, Default, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Node {
+    /// The provider's identifier for the node
+    pub id: i64,
+    /// The node's labels
+    #[serde(default)]
+    pub labels: Vec<String>,
+    /// The node's properties
+    #[serde(default)]
+    pub properties: Map<String, Value>,
+}
+
+/// A relationship returned from a query
+#[derive(Debug, Clone, PartialEq, Default, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Relationship {
+    /// The provider's identifier for the relationship
+    pub id: i64,
+    /// The type of the relationship
+    #[serde(rename = "type")]
+    pub rel_type: String,
+    /// The identifier of the node the relationship starts from
+    pub src_node: i64,
+    /// The identifier of the node the relationship ends at
+    pub dest_node: i64,
+    /// The relationship's properties
+    #[serde(default)]
+    pub properties: Map<String, Value>,
+}
+
+/// An abstraction around a host runtime capability for a graph database
+pub struct GraphDbHostBinding {
+    binding: String,
+}
+
+impl Default for GraphDbHostBinding {
+    fn default() -> Self {
+        GraphDbHostBinding {
+            binding: "default".to_string(),
+        }
+    }
+}
+
+/// Creates a named host binding for the graph database capability
+pub fn host(binding: &str) -> GraphDbHostBinding {
+    GraphDbHostBinding {
+        binding: binding.to_string(),
+    }
+}
+
+/// Creates the default host binding for the graph database capability
+pub fn default() -> GraphDbHostBinding {
+    GraphDbHostBinding::default()
+}
+
+impl GraphDbHostBinding {
+    /// Starts a query against the named graph. Add parameters with `param`, then `fetch`
+    /// typed rows or `run` it for the raw results
+    pub fn query(&self, graph_name: &str, query: &str) -> GraphQuery<'_> {
+        GraphQuery {
+            db: self,
+            request: QueryRequest {
+                graph_name: graph_name.to_string(),
+                query: query.to_string(),
+                params: HashMap::new(),
+            },
+        }
+    }
+
+    /// Performs a query
+    pub fn execute(&self, request: &QueryRequest) -> HandlerResult<ResultSet> {
+        host_call(&self.binding, CAPID_GRAPHDB, OP_QUERY, &serialize(request)?)
+            .map_err(|e| e.into())
+            .and_then(|v| deserialize::<ResultSet>(v.as_ref()))
+    }
+
+    /// Deletes a graph and everything in it
+    pub fn delete_graph(&self, graph_name: &str) -> HandlerResult<()> {
+        let cmd = DeleteGraphRequest {
+            graph_name: graph_name.to_string(),
+        };
+        host_call(
+            &self.binding,
+            CAPID_GRAPHDB,
+            OP_DELETE_GRAPH,
+            &serialize(cmd)?,
+        )
+        .map(|_v| ())
+        .map_err(|e| e.into())
+    }
+}
+
+/// A query being built. Obtained from `GraphDbHostBinding::query`
+pub struct GraphQuery<'a> {
+    db: &'a GraphDbHostBinding,
+    request: QueryRequest,
+}
+
+impl<'a> GraphQuery<'a> {
+    /// Sets the value of a named parameter
+    pub fn param<T: Into<Value>>(mut self, name: &str, value: T) -> Self {
+        self.request.params.insert(name.to_string(), value.into());
+        self
+    }
+
+    /// Performs the query, returning the raw results
+    pub fn run(&self) -> HandlerResult<ResultSet> {
+        self.db.execute(&self.request)
+    }
+
+    /// Performs the query, decoding each row into a type whose fields are named after the
+    /// returned columns
+    pub fn fetch<T: DeserializeOwned>(&self) -> HandlerResult<Vec<T>> {
+        self.run()?.decode()
+    }
+}
@@ -0,0 +1,238 @@
+//! # gRPC Client
+//!
+//! This module contains the client through which actors invoke unary methods on external
+//! gRPC services with the currently bound `wascc:grpc` capability provider. The service's
+//! address and transport security are part of the binding's configuration; the actor
+//! supplies the method path, the encoded request message, call metadata and an optional
+//! deadline.
+//!
+//! Requests and responses are protobuf-encoded bytes. Message types that implement
+//! `ProtoMessage` (a one-line implementation for `prost`-generated types, as shown below)
+//! can be sent and received directly with `send_message`
+//!
+//! ```
+//! extern crate wascc_actor as actor;
+//! use actor::prelude::*;
+//! use actor::grpc::ProtoMessage;
+//!
+//! # struct GetUserRequest { id: String }
+//! # struct User { name: String }
+//! # impl ProtoMessage for GetUserRequest {
+//! #     fn encode_message(&self) -> Vec<u8> { self.id.as_bytes().to_vec() }
+//! #     fn decode_message(b: &[u8]) -> HandlerResult<Self> { Ok(GetUserRequest { id: String::from_utf8(b.to_vec())? }) }
+//! # }
+//! # impl ProtoMessage for User {
+//! #     fn encode_message(&self) -> Vec<u8> { self.name.as_bytes().to_vec() }
+//! #     fn decode_message(b: &[u8]) -> HandlerResult<Self> { Ok(User { name: String::from_utf8(b.to_vec())? }) }
+//! # }
+//! // With prost, for each generated message type:
+//! //
+//! // impl ProtoMessage for GetUserRequest {
+//! //     fn encode_message(&self) -> Vec<u8> { prost::Message::encode_to_vec(self) }
+//! //     fn decode_message(bytes: &[u8]) -> HandlerResult<Self> {
+//! //         Ok(prost::Message::decode(bytes)?)
+//! //     }
+//! // }
+//!
+//! fn user_name(id: &str) -> HandlerResult<String> {
+//!     let user: User = grpc::default()
+//!         .call("/users.v1.UserService/GetUser")
+//!         .metadata("authorization", "Bearer abc123")
+//!         .deadline_ms(500)
+//!         .send_message(&GetUserRequest { id: id.to_string() })?;
+//!     Ok(user.name)
+//! }
+//! ```
+
+use serde_derive::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::error::Error;
+use std::fmt;
+use wapc_guest::host_call;
+use wascc_codec::{deserialize, serialize};
+
+use crate::HandlerResult;
+
+/// The reserved capability ID for gRPC client functionality
+pub const CAPID_GRPC: &str = "wascc:grpc";
+
+/// Guest sends a `GrpcRequest` to the capability provider, receives a `GrpcResponse` back
+pub const OP_UNARY_CALL: &str = "UnaryCall";
+
+/// The gRPC status code for a successful call
+pub const STATUS_OK: u32 = 0;
+/// The gRPC status code for a call whose deadline expired
+pub const STATUS_DEADLINE_EXCEEDED: u32 = 4;
+/// The gRPC status code for a call to a resource that does not exist
+pub const STATUS_NOT_FOUND: u32 = 5;
+/// The gRPC status code for a call to a service that is currently unavailable
+pub const STATUS_UNAVAILABLE: u32 = 14;
+/// The gRPC status code for a call whose caller could not be authenticated
+pub const STATUS_UNAUTHENTICATED: u32 = 16;
+
+/// A protobuf message that can be sent or received with `send_message`
+pub trait ProtoMessage: Sized {
+    /// Encodes the message in the protobuf wire format
+    fn encode_message(&self) -> Vec<u8>;
+
+    /// Decodes a message from the protobuf wire format
+    fn decode_message(bytes: &[u8]) -> HandlerResult<Self>;
+}
+
+/// A unary call to a gRPC method
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct GrpcRequest {
+    /// The method path, in the form `/package.Service/Method`
+    pub method: String,
+    /// The encoded request message
+    #[serde(with = "serde_bytes")]
+    pub payload: Vec<u8>,
+    /// The call's metadata (request headers)
+    #[serde(default)]
+    pub metadata: HashMap<String, String>,
+    /// The number of milliseconds the call may take before it fails with
+    /// `DEADLINE_EXCEEDED`, or no deadline if absent
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub deadline_ms: Option<u64>,
+}
+
+/// The outcome of a unary call
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct GrpcResponse {
+    /// The gRPC status code
+    pub status_code: u32,
+    /// The status message, for calls that did not succeed
+    #[serde(default)]
+    pub status_message: String,
+    /// The encoded response message, for calls that succeeded
+    #[serde(with = "serde_bytes")]
+    #[serde(default)]
+    pub payload: Vec<u8>,
+    /// The response's metadata (headers and trailers)
+    #[serde(default)]
+    pub metadata: HashMap<String, String>,
+}
+
+/// The error produced by a call that completed with a status other than `OK`. Callers can
+/// recover it from a `HandlerResult` error with `downcast_ref::<GrpcStatus>()`
+#[derive(Debug, Clone, PartialEq)]
+pub struct GrpcStatus {
+    /// The gRPC status code
+    pub code: u32,
+    /// The status message
+    pub message: String,
+}
+
+impl fmt::Display for GrpcStatus {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "gRPC call failed with status {}: {}",
+            self.code, self.message
+        )
+    }
+}
+
+impl Error for GrpcStatus {}
+
+/// An abstraction around a host runtime capability for a gRPC client
+pub struct GrpcHostBinding {
+    binding: String,
+}
+
+impl Default for GrpcHostBinding {
+    fn default() -> Self {
+        GrpcHostBinding {
+            binding: "default".to_string(),
+        }
+    }
+}
+
+/// Creates a named host binding for the gRPC client capability
+pub fn host(binding: &str) -> GrpcHostBinding {
+    GrpcHostBinding {
+        binding: binding.to_string(),
+    }
+}
+
+/// Creates the default host binding for the gRPC client capability
+pub fn default() -> GrpcHostBinding {
+    GrpcHostBinding::default()
+}
+
+impl GrpcHostBinding {
+    /// Starts building a call to the method with the given path
+    /// (`/package.Service/Method`)
+    pub fn call(&self, method: &str) -> GrpcCall<'_> {
+        GrpcCall {
+            client: self,
+            request: GrpcRequest {
+                method: method.to_string(),
+                payload: Vec::new(),
+                metadata: HashMap::new(),
+                deadline_ms: None,
+            },
+        }
+    }
+
+    /// Performs a call, returning the response whatever its status
+    pub fn unary(&self, request: &GrpcRequest) -> HandlerResult<GrpcResponse> {
+        host_call(
+            &self.binding,
+            CAPID_GRPC,
+            OP_UNARY_CALL,
+            &serialize(request)?,
+        )
+        .map_err(|e| e.into())
+        .and_then(|v| deserialize::<GrpcResponse>(v.as_ref()))
+    }
+}
+
+/// A call being built. Obtained from `GrpcHostBinding::call`
+pub struct GrpcCall<'a> {
+    client: &'a GrpcHostBinding,
+    request: GrpcRequest,
+}
+
+impl<'a> GrpcCall<'a> {
+    /// Adds a metadata entry (request header). Keys are lower-cased, as gRPC requires
+    pub fn metadata(mut self, key: &str, value: &str) -> Self {
+        self.request
+            .metadata
+            .insert(key.to_lowercase(), value.to_string());
+        self
+    }
+
+    /// Sets the number of milliseconds the call may take
+    pub fn deadline_ms(mut self, deadline_ms: u64) -> Self {
+        self.request.deadline_ms = Some(deadline_ms);
+        self
+    }
+
+    /// Performs the call with an encoded request message, returning the encoded response
+    /// message. Calls that complete with a status other than `OK` produce a `GrpcStatus`
+    /// error
+    pub fn send(mut self, payload: Vec<u8>) -> HandlerResult<Vec<u8>> {
+        self.request.payload = payload;
+        let response = self.client.unary(&self.request)?;
+        if response.status_code == STATUS_OK {
+            Ok(response.payload)
+        } else {
+            Err(Box::new(GrpcStatus {
+                code: response.status_code,
+                message: response.status_message,
+            }))
+        }
+    }
+
+    /// Performs the call with a request message, decoding the response message
+    pub fn send_message<Req: ProtoMessage, Resp: ProtoMessage>(
+        self,
+        request: &Req,
+    ) -> HandlerResult<Resp> {
+        let bytes = self.send(request.encode_message())?;
+        Resp::decode_message(&bytes)
+    }
+}
@@ -0,0 +1,643 @@
+//! # HTTP Server Helpers
+//!
+//! This module contains helpers for actors that handle requests delivered by a bound
+//! `wascc:http_server` capability provider via `OP_HANDLE_REQUEST`, starting with a router
+//! that dispatches requests to handler functions by method and path pattern
+
+pub mod assets;
+pub mod cookie;
+#[cfg(feature = "jwt")]
+pub mod jwt;
+pub mod middleware;
+pub mod negotiate;
+pub mod query;
+pub mod stream;
+
+pub use assets::StaticFiles;
+pub use cookie::{http_date, parse_cookies, CookieBuilder, SameSite};
+pub use middleware::{Middleware, RequestLogger};
+pub use negotiate::{respond_negotiated, CONTENT_TYPE_MSGPACK};
+pub use query::{url_decode, QueryMap};
+pub use stream::ResponseStream;
+
+use crate::HandlerResult;
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use std::any::{Any, TypeId};
+use std::collections::HashMap;
+use std::ops::{Deref, DerefMut};
+
+pub use wascc_codec::http::{Request, Response};
+
+/// The content type of JSON request and response bodies
+pub const CONTENT_TYPE_JSON: &str = "application/json";
+
+/// The request being handled, along with the parameters extracted from its path by the
+/// route that matched it and any values stored by middleware
+pub struct Context {
+    request: Request,
+    params: HashMap<String, String>,
+    extensions: HashMap<TypeId, Box<dyn Any + Send + Sync>>,
+}
+
+impl Context {
+    /// Creates a context for a request that has not been routed
+    pub fn new(request: Request) -> Context {
+        Context {
+            request,
+            params: HashMap::new(),
+            extensions: HashMap::new(),
+        }
+    }
+
+    /// Stores a value in the context, replacing any value of the same type. Middleware uses
+    /// this to pass data (such as an authenticated identity) to handlers
+    pub fn insert<T: Any + Send + Sync>(&mut self, value: T) {
+        self.extensions.insert(TypeId::of::<T>(), Box::new(value));
+    }
+
+    /// Retrieves a value of the given type stored with `insert`
+    pub fn get<T: Any + Send + Sync>(&self) -> Option<&T> {
+        self.extensions
+            .get(&TypeId::of::<T>())
+            .and_then(|v| v.downcast_ref())
+    }
+
+    /// The request being handled
+    pub fn request(&self) -> &Request {
+        &self.request
+    }
+
+    /// Consumes the context, returning the request
+    pub fn into_request(self) -> Request {
+        self.request
+    }
+
+    /// The value of a named path parameter, e.g. `id` for the pattern `/orders/:id`
+    pub fn param(&self, name: &str) -> Option<&str> {
+        self.params.get(name).map(|v| v.as_str())
+    }
+
+    /// All of the path parameters extracted by the matching route
+    pub fn params(&self) -> &HashMap<String, String> {
+        &self.params
+    }
+
+    /// The request's decoded query string parameters
+    pub fn query(&self) -> QueryMap {
+        let query = if self.request.query_string.is_empty() {
+            self.request.path.split_once('?').map_or("", |(_, q)| q)
+        } else {
+            self.request.query_string.trim_start_matches('?')
+        };
+        QueryMap::parse(query)
+    }
+
+    /// Deserializes the request's query string parameters into a type. See
+    /// `QueryMap::deserialize`
+    ///
+    /// ```
+    /// # extern crate wascc_actor as actor;
+    /// # #[macro_use] extern crate serde_derive;
+    /// # use actor::http::Context;
+    /// # use actor::prelude::*;
+    /// #[derive(Deserialize)]
+    /// struct Search {
+    ///     q: String,
+    ///     page: Option<u32>,
+    ///     limit: u32,
+    ///     tag: Vec<String>,
+    /// }
+    ///
+    /// let ctx = Context::new(codec::http::Request {
+    ///     query_string: "q=hello+world&limit=10&tag=a&tag=b%26c".to_string(),
+    ///     ..Default::default()
+    /// });
+    /// let search: Search = ctx.query_as().unwrap();
+    /// assert_eq!(search.q, "hello world");
+    /// assert_eq!(search.page, None);
+    /// assert_eq!(search.limit, 10);
+    /// assert_eq!(search.tag, vec!["a", "b&c"]);
+    /// ```
+    pub fn query_as<T: DeserializeOwned>(&self) -> HandlerResult<T> {
+        self.query().deserialize()
+    }
+
+    /// The cookies sent with the request
+    pub fn cookies(&self) -> HashMap<String, String> {
+        self.header("cookie").map(parse_cookies).unwrap_or_default()
+    }
+
+    /// The value of the named cookie, if it was sent with the request
+    pub fn cookie(&self, name: &str) -> Option<String> {
+        self.cookies().remove(name)
+    }
+
+    /// The value of a request header, matched case-insensitively
+    pub fn header(&self, name: &str) -> Option<&str> {
+        self.request
+            .header
+            .iter()
+            .find(|(k, _)| k.eq_ignore_ascii_case(name))
+            .map(|(_, v)| v.as_str())
+    }
+}
+
+/// Adds a builder to the codec's `Response` type
+pub trait ResponseExt {
+    /// Starts building a response, which defaults to `200 OK` with no headers or body
+    ///
+    /// ```
+    /// # use wascc_actor::http::{Response, ResponseExt};
+    /// let response = Response::builder()
+    ///     .status(201)
+    ///     .header("Location", "/orders/42")
+    ///     .json(&vec![1, 2, 3])
+    ///     .unwrap();
+    /// assert_eq!(response.status, "Created");
+    /// assert_eq!(response.header["Content-Type"], "application/json");
+    /// assert_eq!(response.body, b"[1,2,3]");
+    /// ```
+    fn builder() -> ResponseBuilder;
+}
+
+impl ResponseExt for Response {
+    fn builder() -> ResponseBuilder {
+        ResponseBuilder::default()
+    }
+}
+
+/// Builds a `Response`. Obtained from `Response::builder()`
+#[derive(Debug)]
+pub struct ResponseBuilder {
+    response: Response,
+}
+
+impl Default for ResponseBuilder {
+    fn default() -> Self {
+        ResponseBuilder {
+            response: status_response(200),
+        }
+    }
+}
+
+impl ResponseBuilder {
+    /// Sets the status code, along with its standard reason phrase
+    pub fn status(mut self, status_code: u32) -> ResponseBuilder {
+        self.response.status_code = status_code;
+        self.response.status = reason_phrase(status_code).to_string();
+        self
+    }
+
+    /// Sets a header, replacing any previous value of the same header
+    pub fn header(mut self, name: &str, value: &str) -> ResponseBuilder {
+        self.response
+            .header
+            .insert(name.to_string(), value.to_string());
+        self
+    }
+
+    /// Sets the `Content-Type` header
+    pub fn content_type(self, content_type: &str) -> ResponseBuilder {
+        self.header("Content-Type", content_type)
+    }
+
+    /// Sets a cookie. See `CookieBuilder::apply`
+    pub fn cookie(mut self, cookie: &CookieBuilder) -> ResponseBuilder {
+        cookie.apply(&mut self.response);
+        self
+    }
+
+    /// Finishes the response with the given body
+    pub fn body(mut self, body: Vec<u8>) -> Response {
+        self.response.body = body;
+        self.response
+    }
+
+    /// Finishes the response with a plain text body
+    pub fn text(self, body: &str) -> Response {
+        self.content_type("text/plain; charset=utf-8")
+            .body(body.as_bytes().to_vec())
+    }
+
+    /// Finishes the response with the given value serialized as a JSON body
+    pub fn json<T: Serialize>(self, body: &T) -> HandlerResult<Response> {
+        let body = serde_json::to_vec(body)?;
+        Ok(self.content_type(CONTENT_TYPE_JSON).body(body))
+    }
+
+    /// Finishes the response without a body
+    pub fn empty(self) -> Response {
+        self.response
+    }
+}
+
+/// A value extracted from the request before it is passed to a handler. If extraction
+/// fails, the returned response (typically a 4xx) is sent instead of calling the handler
+pub trait FromRequest: Sized {
+    /// Extracts the value from the request
+    fn from_request(ctx: &Context) -> Result<Self, Response>;
+}
+
+/// A value that a handler can return in place of a `Response`
+pub trait IntoResponse {
+    /// Converts the value into a response
+    fn into_response(self) -> HandlerResult<Response>;
+}
+
+impl IntoResponse for Response {
+    fn into_response(self) -> HandlerResult<Response> {
+        Ok(self)
+    }
+}
+
+/// An empty `204 No Content` response
+impl IntoResponse for () {
+    fn into_response(self) -> HandlerResult<Response> {
+        Ok(status_response(204))
+    }
+}
+
+/// Overrides the status code of the wrapped response, e.g. `(201, Json(order))`
+impl<R: IntoResponse> IntoResponse for (u32, R) {
+    fn into_response(self) -> HandlerResult<Response> {
+        let mut response = self.1.into_response()?;
+        response.status_code = self.0;
+        response.status = reason_phrase(self.0).to_string();
+        Ok(response)
+    }
+}
+
+/// A JSON request or response body. As a handler parameter, the request body is
+/// deserialized into the wrapped type, with malformed bodies rejected with a 400 and
+/// bodies of another content type rejected with a 415. As a return value, the wrapped
+/// value is serialized into a `200 OK` response with a JSON content type
+///
+/// ```
+/// # extern crate wascc_actor as actor;
+/// # #[macro_use] extern crate serde_derive;
+/// # use actor::prelude::*;
+/// use actor::http::{Context, Json, Router};
+///
+/// #[derive(Deserialize)]
+/// struct CreateOrder { item: String }
+///
+/// #[derive(Serialize)]
+/// struct Order { id: u64, item: String }
+///
+/// fn create(_ctx: &Context, order: Json<CreateOrder>) -> HandlerResult<(u32, Json<Order>)> {
+///     Ok((201, Json(Order { id: 1, item: order.0.item })))
+/// }
+///
+/// let router = Router::new().post("/orders", create);
+/// # let _ = router;
+/// ```
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Json<T>(pub T);
+
+impl<T> Deref for Json<T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        &self.0
+    }
+}
+
+impl<T> DerefMut for Json<T> {
+    fn deref_mut(&mut self) -> &mut T {
+        &mut self.0
+    }
+}
+
+impl<T: DeserializeOwned> FromRequest for Json<T> {
+    fn from_request(ctx: &Context) -> Result<Self, Response> {
+        if let Some(content_type) = ctx.header("content-type") {
+            if !content_type.to_ascii_lowercase().contains("json") {
+                return Err(status_response(415));
+            }
+        }
+        serde_json::from_slice(&ctx.request.body)
+            .map(Json)
+            .map_err(|e| {
+                let mut response = status_response(400);
+                response.body = format!("Invalid JSON body: {}", e).into_bytes();
+                response
+            })
+    }
+}
+
+/// The request's query string parameters, deserialized into the wrapped type. As a handler
+/// parameter, query strings that cannot be deserialized are rejected with a 400
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Query<T>(pub T);
+
+impl<T> Deref for Query<T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        &self.0
+    }
+}
+
+impl<T: DeserializeOwned> FromRequest for Query<T> {
+    fn from_request(ctx: &Context) -> Result<Self, Response> {
+        ctx.query_as().map(Query).map_err(|e| {
+            let mut response = status_response(400);
+            response.body = e.to_string().into_bytes();
+            response
+        })
+    }
+}
+
+impl<T: Serialize> IntoResponse for Json<T> {
+    fn into_response(self) -> HandlerResult<Response> {
+        Response::builder().json(&self.0)
+    }
+}
+
+/// A function that handles a routed request. Implemented for functions taking the request
+/// context, optionally followed by a value extracted with `FromRequest`, and returning any
+/// `IntoResponse` type. The type parameter only distinguishes these signatures
+pub trait Handler<Args>: Send + Sync {
+    /// Handles the request
+    fn call(&self, ctx: &Context) -> HandlerResult<Response>;
+}
+
+impl<F, R> Handler<()> for F
+where
+    F: Fn(&Context) -> HandlerResult<R> + Send + Sync,
+    R: IntoResponse,
+{
+    fn call(&self, ctx: &Context) -> HandlerResult<Response> {
+        self(ctx)?.into_response()
+    }
+}
+
+impl<F, E, R> Handler<(E,)> for F
+where
+    F: Fn(&Context, E) -> HandlerResult<R> + Send + Sync,
+    E: FromRequest,
+    R: IntoResponse,
+{
+    fn call(&self, ctx: &Context) -> HandlerResult<Response> {
+        match E::from_request(ctx) {
+            Ok(extracted) => self(ctx, extracted)?.into_response(),
+            Err(rejection) => Ok(rejection),
+        }
+    }
+}
+
+type BoxedHandler = Box<dyn Fn(&Context) -> HandlerResult<Response> + Send + Sync>;
+
+enum Segment {
+    Literal(String),
+    Param(String),
+    Rest(String),
+}
+
+struct Route {
+    method: String,
+    segments: Vec<Segment>,
+    handler: BoxedHandler,
+}
+
+impl Route {
+    fn matches(&self, path: &[&str]) -> Option<HashMap<String, String>> {
+        let mut params = HashMap::new();
+        for (i, segment) in self.segments.iter().enumerate() {
+            match segment {
+                Segment::Rest(name) => {
+                    let rest = path[i.min(path.len())..].join("/");
+                    params.insert(name.to_string(), url_decode(&rest, false));
+                    return Some(params);
+                }
+                Segment::Literal(literal) => {
+                    if path.get(i) != Some(&literal.as_str()) {
+                        return None;
+                    }
+                }
+                Segment::Param(name) => {
+                    params.insert(name.to_string(), url_decode(path.get(i)?, false));
+                }
+            }
+        }
+        if path.len() == self.segments.len() {
+            Some(params)
+        } else {
+            None
+        }
+    }
+}
+
+/// Dispatches requests to handlers by method and path. Patterns are made of `/`-separated
+/// segments, where a segment beginning with `:` captures a single path segment as a named
+/// parameter and a final segment beginning with `*` captures the rest of the path. Routes
+/// are tried in the order they were added. Requests whose path matches no route receive a
+/// 404 response, and requests whose path matches only routes for other methods receive a 405.
+///
+/// Because handlers must be `Send + Sync`, a router can be built once and kept in a static.
+///
+/// ```
+/// extern crate wascc_actor as actor;
+/// use actor::prelude::*;
+/// use actor::http::{Context, Router};
+///
+/// actor_handlers! { codec::http::OP_HANDLE_REQUEST => handle_request }
+///
+/// fn handle_request(req: codec::http::Request) -> HandlerResult<codec::http::Response> {
+///     Router::new()
+///         .with(http::RequestLogger)
+///         .get("/orders/:id", get_order)
+///         .get("/files/*path", get_file)
+///         .handle(req)
+/// }
+///
+/// fn get_order(ctx: &Context) -> HandlerResult<codec::http::Response> {
+///     let id = ctx.param("id").unwrap_or_default();
+///     Ok(codec::http::Response::json(id, 200, "OK"))
+/// }
+///
+/// fn get_file(ctx: &Context) -> HandlerResult<codec::http::Response> {
+///     let _path = ctx.param("path");
+///     Ok(codec::http::Response::not_found())
+/// }
+/// ```
+#[derive(Default)]
+pub struct Router {
+    routes: Vec<Route>,
+    middleware: Vec<Box<dyn Middleware>>,
+}
+
+impl Router {
+    /// Creates a router with no routes
+    pub fn new() -> Router {
+        Router::default()
+    }
+
+    /// Adds middleware that wraps every request handled by the router, including those
+    /// that match no route
+    pub fn with<M: Middleware + 'static>(mut self, middleware: M) -> Router {
+        self.middleware.push(Box::new(middleware));
+        self
+    }
+
+    /// Adds a route for the given method (case-insensitive) and path pattern
+    pub fn route<H, A>(mut self, method: &str, pattern: &str, handler: H) -> Router
+    where
+        H: Handler<A> + 'static,
+    {
+        self.routes.push(Route {
+            method: method.to_uppercase(),
+            segments: split_path(pattern)
+                .into_iter()
+                .map(|s| {
+                    if let Some(name) = s.strip_prefix(':') {
+                        Segment::Param(name.to_string())
+                    } else if let Some(name) = s.strip_prefix('*') {
+                        Segment::Rest(name.to_string())
+                    } else {
+                        Segment::Literal(s.to_string())
+                    }
+                })
+                .collect(),
+            handler: Box::new(move |ctx| handler.call(ctx)),
+        });
+        self
+    }
+
+    /// Adds a route for `GET` requests
+    pub fn get<H: Handler<A> + 'static, A>(self, pattern: &str, handler: H) -> Router {
+        self.route("GET", pattern, handler)
+    }
+
+    /// Adds a route for `POST` requests
+    pub fn post<H: Handler<A> + 'static, A>(self, pattern: &str, handler: H) -> Router {
+        self.route("POST", pattern, handler)
+    }
+
+    /// Adds a route for `PUT` requests
+    pub fn put<H: Handler<A> + 'static, A>(self, pattern: &str, handler: H) -> Router {
+        self.route("PUT", pattern, handler)
+    }
+
+    /// Adds a route for `PATCH` requests
+    pub fn patch<H: Handler<A> + 'static, A>(self, pattern: &str, handler: H) -> Router {
+        self.route("PATCH", pattern, handler)
+    }
+
+    /// Adds a route for `DELETE` requests
+    pub fn delete<H: Handler<A> + 'static, A>(self, pattern: &str, handler: H) -> Router {
+        self.route("DELETE", pattern, handler)
+    }
+
+    /// Routes a request to the first matching handler, returning the handler's response. If
+    /// the handler or a `before` hook fails, the error is logged and answered with a 500
+    /// response, which still passes through the `after` hooks of the middleware that ran
+    pub fn handle(&self, request: Request) -> HandlerResult<Response> {
+        let path = request
+            .path
+            .split('?')
+            .next()
+            .unwrap_or_default()
+            .to_string();
+        let path = split_path(&path);
+        let mut ctx = Context::new(request);
+        let mut allowed = Vec::new();
+        let mut matched = None;
+        for route in &self.routes {
+            if let Some(params) = route.matches(&path) {
+                if route.method.eq_ignore_ascii_case(&ctx.request.method) {
+                    ctx.params = params;
+                    matched = Some(route);
+                    break;
+                }
+                allowed.push(route.method.to_string());
+            }
+        }
+
+        let mut ran = 0;
+        let mut response = None;
+        for middleware in &self.middleware {
+            ran += 1;
+            if let Some(r) = middleware.before(&mut ctx).transpose() {
+                response = Some(r);
+                break;
+            }
+        }
+        let response = response.unwrap_or_else(|| match matched {
+            Some(route) => (route.handler)(&ctx),
+            None => Ok(unrouted(allowed)),
+        });
+        let mut response = response.unwrap_or_else(|e| {
+            log::error!("{} {} failed: {}", ctx.request.method, ctx.request.path, e);
+            status_response(500)
+        });
+        for middleware in self.middleware[..ran].iter().rev() {
+            middleware.after(&ctx, &mut response)?;
+        }
+        Ok(response)
+    }
+}
+
+fn unrouted(mut allowed: Vec<String>) -> Response {
+    if allowed.is_empty() {
+        Response::not_found()
+    } else {
+        allowed.sort();
+        allowed.dedup();
+        Response::builder()
+            .status(405)
+            .header("Allow", &allowed.join(", "))
+            .empty()
+    }
+}
+
+fn status_response(status_code: u32) -> Response {
+    Response {
+        status_code,
+        status: reason_phrase(status_code).to_string(),
+        ..Default::default()
+    }
+}
+
+fn reason_phrase(status_code: u32) -> &'static str {
+    match status_code {
+        100 => "Continue",
+        101 => "Switching Protocols",
+        200 => "OK",
+        201 => "Created",
+        202 => "Accepted",
+        204 => "No Content",
+        206 => "Partial Content",
+        301 => "Moved Permanently",
+        302 => "Found",
+        303 => "See Other",
+        304 => "Not Modified",
+        307 => "Temporary Redirect",
+        308 => "Permanent Redirect",
+        400 => "Bad Request",
+        401 => "Unauthorized",
+        403 => "Forbidden",
+        404 => "Not Found",
+        405 => "Method Not Allowed",
+        406 => "Not Acceptable",
+        409 => "Conflict",
+        410 => "Gone",
+        412 => "Precondition Failed",
+        413 => "Payload Too Large",
+        415 => "Unsupported Media Type",
+        416 => "Range Not Satisfiable",
+        422 => "Unprocessable Entity",
+        429 => "Too Many Requests",
+        500 => "Internal Server Error",
+        501 => "Not Implemented",
+        502 => "Bad Gateway",
+        503 => "Service Unavailable",
+        504 => "Gateway Timeout",
+        _ => "",
+    }
+}
+
+fn split_path(path: &str) -> Vec<&str> {
+    path.split('/').filter(|s| !s.is_empty()).collect()
+}
@@ -0,0 +1,233 @@
+//! # Static Assets
+//!
+//! This module contains a helper that serves static files stored in a blobstore container,
+//! turning an actor into a small static file server backed by `wascc:blobstore`
+
+use super::{url_decode, Request, Response, ResponseExt};
+use crate::objectstore::{BlobInfo, ObjectStoreHostBinding};
+use crate::HandlerResult;
+
+/// Serves `GET` and `HEAD` requests beneath a path prefix from the blobs of a container.
+/// The remainder of the path after the prefix is the blob ID, so with the prefix `/static`
+/// a request for `/static/css/site.css` is served from the blob `css/site.css`. Requests
+/// for a directory (a path ending in `/`) are served from its index file.
+///
+/// Responses carry the content type stored with the blob (or one guessed from its file
+/// extension) and, when the provider supplies an entity tag for the blob, an `ETag`.
+/// Conditional requests with a matching `If-None-Match` receive a `304` (blobs without an
+/// entity tag are always sent in full, since nothing else reliably tells an overwritten blob
+/// from the original), and single-range `Range` requests receive a `206` with just the
+/// requested bytes
+///
+/// ```
+/// extern crate wascc_actor as actor;
+/// use actor::prelude::*;
+/// use actor::http::{Context, Router, StaticFiles};
+///
+/// fn handle_request(req: codec::http::Request) -> HandlerResult<codec::http::Response> {
+///     let files = StaticFiles::new(objectstore::default(), "site", "/static");
+///     Router::new()
+///         .get("/static/*path", move |ctx: &Context| files.serve(ctx.request()))
+///         .handle(req)
+/// }
+/// ```
+pub struct StaticFiles {
+    store: ObjectStoreHostBinding,
+    container: String,
+    prefix: String,
+    index_file: String,
+}
+
+impl StaticFiles {
+    /// Creates a server for the files in the given container, beneath the given path prefix
+    pub fn new(store: ObjectStoreHostBinding, container: &str, prefix: &str) -> StaticFiles {
+        StaticFiles {
+            store,
+            container: container.to_string(),
+            prefix: prefix.trim_matches('/').to_string(),
+            index_file: "index.html".to_string(),
+        }
+    }
+
+    /// Sets the name of the file served for directory requests, `index.html` by default
+    pub fn index_file(mut self, name: &str) -> StaticFiles {
+        self.index_file = name.to_string();
+        self
+    }
+
+    /// Serves the file named by the request's path. Requests outside the prefix, or for
+    /// files that do not exist, receive a 404
+    pub fn serve(&self, request: &Request) -> HandlerResult<Response> {
+        let head = request.method.eq_ignore_ascii_case("HEAD");
+        if !head && !request.method.eq_ignore_ascii_case("GET") {
+            return Ok(Response::builder()
+                .status(405)
+                .header("Allow", "GET, HEAD")
+                .empty());
+        }
+        let id = match self.blob_id(&request.path) {
+            Some(id) => id,
+            None => return Ok(Response::not_found()),
+        };
+        let info = match self.store.get_blob_details(&self.container, &id)? {
+            Some(info) => info,
+            None => return Ok(Response::not_found()),
+        };
+
+        let etag = etag(&info);
+        let content_type = info
+            .content_type()
+            .unwrap_or_else(|| guess_content_type(&id))
+            .to_string();
+        let mut response = Response::builder()
+            .header("Accept-Ranges", "bytes")
+            .content_type(&content_type);
+        if let Some(ref etag) = etag {
+            response = response.header("ETag", etag);
+            if header(request, "if-none-match").is_some_and(|tags| {
+                tags.split(',').any(|t| {
+                    t.trim() == "*"
+                        || t.trim().trim_start_matches("W/") == etag.trim_start_matches("W/")
+                })
+            }) {
+                return Ok(response.status(304).empty());
+            }
+        }
+
+        let range = match header(request, "range") {
+            Some(range) => match parse_range(range, info.byte_size) {
+                Some(range) => range,
+                None => {
+                    return Ok(response
+                        .status(416)
+                        .header("Content-Range", &format!("bytes */{}", info.byte_size))
+                        .empty())
+                }
+            },
+            None => None,
+        };
+        match range {
+            Some((start, end)) => {
+                let response = response.status(206).header(
+                    "Content-Range",
+                    &format!("bytes {}-{}/{}", start, end, info.byte_size),
+                );
+                if head {
+                    return Ok(response.empty());
+                }
+                let bytes = self
+                    .store
+                    .get_blob_range(&self.container, &id, start, end - start + 1)?
+                    .unwrap_or_default();
+                Ok(response.body(bytes))
+            }
+            None if head => Ok(response.empty()),
+            None => {
+                let bytes = self
+                    .store
+                    .get_blob(&self.container, &id)?
+                    .unwrap_or_default();
+                Ok(response.body(bytes))
+            }
+        }
+    }
+
+    fn blob_id(&self, path: &str) -> Option<String> {
+        let path = path.split('?').next().unwrap_or_default();
+        let path = path.trim_start_matches('/');
+        let rest = if self.prefix.is_empty() {
+            path
+        } else {
+            path.strip_prefix(self.prefix.as_str())?
+        };
+        if !rest.is_empty() && !rest.starts_with('/') {
+            return None;
+        }
+        let rest = url_decode(rest.trim_start_matches('/'), false);
+        if rest.split('/').any(|s| s == "..") {
+            return None;
+        }
+        if rest.is_empty() || rest.ends_with('/') {
+            Some(format!("{}{}", rest, self.index_file))
+        } else {
+            Some(rest)
+        }
+    }
+}
+
+fn header<'a>(request: &'a Request, name: &str) -> Option<&'a str> {
+    request
+        .header
+        .iter()
+        .find(|(k, _)| k.eq_ignore_ascii_case(name))
+        .map(|(_, v)| v.as_str())
+}
+
+fn etag(info: &BlobInfo) -> Option<String> {
+    info.etag().map(|etag| {
+        if etag.starts_with('"') || etag.starts_with("W/") {
+            etag.to_string()
+        } else {
+            format!("\"{}\"", etag)
+        }
+    })
+}
+
+/// Parses a single-range `Range` header into inclusive start and end offsets. Returns
+/// `Some(None)` for ranges that should be ignored (multiple ranges or other units), and
+/// `None` for ranges that cannot be satisfied
+fn parse_range(range: &str, size: u64) -> Option<Option<(u64, u64)>> {
+    let spec = match range.trim().strip_prefix("bytes=") {
+        Some(spec) if !spec.contains(',') => spec.trim(),
+        _ => return Some(None),
+    };
+    let (start, end) = spec.split_once('-')?;
+    let (start, end) = if start.is_empty() {
+        let suffix: u64 = end.parse().ok()?;
+        if suffix == 0 || size == 0 {
+            return None;
+        }
+        (size.saturating_sub(suffix), size - 1)
+    } else {
+        let start: u64 = start.parse().ok()?;
+        let end = if end.is_empty() {
+            size.checked_sub(1)?
+        } else {
+            end.parse::<u64>().ok()?.min(size.checked_sub(1)?)
+        };
+        (start, end)
+    };
+    if start > end || start >= size {
+        None
+    } else {
+        Some(Some((start, end)))
+    }
+}
+
+fn guess_content_type(id: &str) -> &'static str {
+    let extension = id
+        .rsplit('/')
+        .next()
+        .and_then(|name| name.rsplit_once('.'))
+        .map(|(_, ext)| ext.to_ascii_lowercase())
+        .unwrap_or_default();
+    match extension.as_str() {
+        "html" | "htm" => "text/html; charset=utf-8",
+        "css" => "text/css; charset=utf-8",
+        "js" | "mjs" => "application/javascript",
+        "json" => "application/json",
+        "txt" => "text/plain; charset=utf-8",
+        "xml" => "application/xml",
+        "svg" => "image/svg+xml",
+        "png" => "image/png",
+        "jpg" | "jpeg" => "image/jpeg",
+        "gif" => "image/gif",
+        "webp" => "image/webp",
+        "ico" => "image/x-icon",
+        "woff" => "font/woff",
+        "woff2" => "font/woff2",
+        "wasm" => "application/wasm",
+        "pdf" => "application/pdf",
+        _ => "application/octet-stream",
+    }
+}
@@ -0,0 +1,258 @@
+//! # Cookies
+//!
+//! This module contains a parser for the `Cookie` request header and a builder for
+//! `Set-Cookie` response headers
+
+use std::collections::HashMap;
+use std::fmt;
+use wascc_codec::http::Response;
+
+use crate::HandlerResult;
+
+/// The name of the response header that sets a cookie
+pub const SET_COOKIE: &str = "Set-Cookie";
+
+/// Parses the value of a `Cookie` request header into a map of cookie names to values.
+/// Surrounding quotes are removed from values; if a name appears more than once, the first
+/// value is kept
+pub fn parse_cookies(header: &str) -> HashMap<String, String> {
+    let mut cookies = HashMap::new();
+    for pair in header.split(';') {
+        if let Some((name, value)) = pair.split_once('=') {
+            let name = name.trim();
+            if !name.is_empty() {
+                cookies
+                    .entry(name.to_string())
+                    .or_insert_with(|| value.trim().trim_matches('"').to_string());
+            }
+        }
+    }
+    cookies
+}
+
+/// The `SameSite` attribute of a cookie
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SameSite {
+    /// The cookie is only sent with requests originating from the same site
+    Strict,
+    /// The cookie is also sent when navigating to the site from elsewhere
+    Lax,
+    /// The cookie is sent with cross-site requests. Browsers require such cookies to be
+    /// `Secure`
+    None,
+}
+
+/// Builds the value of a `Set-Cookie` response header. So that a name, value or attribute
+/// can't end the cookie early or smuggle in attributes of its own, characters that RFC 6265
+/// doesn't allow in them (such as `;`, `,`, whitespace and control characters) are
+/// percent-encoded, as is `%` itself. `try_build` instead rejects names that aren't valid
+/// tokens
+///
+/// ```
+/// # use wascc_actor::http::{CookieBuilder, SameSite};
+/// let cookie = CookieBuilder::new("session", "abc123")
+///     .path("/")
+///     .max_age(3600)
+///     .expires(1_600_000_000)
+///     .secure(true)
+///     .http_only(true)
+///     .same_site(SameSite::Lax)
+///     .build();
+/// assert_eq!(
+///     cookie,
+///     "session=abc123; Path=/; Max-Age=3600; Expires=Sun, 13 Sep 2020 12:26:40 GMT; \
+///      Secure; HttpOnly; SameSite=Lax"
+/// );
+/// ```
+#[derive(Debug, Clone, PartialEq)]
+pub struct CookieBuilder {
+    name: String,
+    value: String,
+    domain: Option<String>,
+    path: Option<String>,
+    max_age: Option<i64>,
+    expires: Option<u64>,
+    secure: bool,
+    http_only: bool,
+    same_site: Option<SameSite>,
+}
+
+impl CookieBuilder {
+    /// Starts building a cookie with the given name and value
+    pub fn new(name: &str, value: &str) -> CookieBuilder {
+        CookieBuilder {
+            name: name.to_string(),
+            value: value.to_string(),
+            domain: None,
+            path: None,
+            max_age: None,
+            expires: None,
+            secure: false,
+            http_only: false,
+            same_site: None,
+        }
+    }
+
+    /// Builds a cookie that instructs the client to delete the named cookie
+    pub fn removal(name: &str) -> CookieBuilder {
+        CookieBuilder::new(name, "").max_age(0).expires(0)
+    }
+
+    /// Sets the domain to which the cookie is sent
+    pub fn domain(mut self, domain: &str) -> CookieBuilder {
+        self.domain = Some(domain.to_string());
+        self
+    }
+
+    /// Sets the path beneath which the cookie is sent
+    pub fn path(mut self, path: &str) -> CookieBuilder {
+        self.path = Some(path.to_string());
+        self
+    }
+
+    /// Sets the number of seconds until the cookie expires
+    pub fn max_age(mut self, seconds: i64) -> CookieBuilder {
+        self.max_age = Some(seconds);
+        self
+    }
+
+    /// Sets the time at which the cookie expires, in seconds since the Unix epoch
+    pub fn expires(mut self, epoch_s: u64) -> CookieBuilder {
+        self.expires = Some(epoch_s);
+        self
+    }
+
+    /// Sets whether the cookie is only sent over HTTPS
+    pub fn secure(mut self, secure: bool) -> CookieBuilder {
+        self.secure = secure;
+        self
+    }
+
+    /// Sets whether the cookie is hidden from client-side scripts
+    pub fn http_only(mut self, http_only: bool) -> CookieBuilder {
+        self.http_only = http_only;
+        self
+    }
+
+    /// Sets the cookie's `SameSite` attribute
+    pub fn same_site(mut self, same_site: SameSite) -> CookieBuilder {
+        self.same_site = Some(same_site);
+        self
+    }
+
+    /// Produces the value of the `Set-Cookie` header
+    pub fn build(&self) -> String {
+        self.to_string()
+    }
+
+    /// Produces the value of the `Set-Cookie` header, failing if the cookie's name is not an
+    /// RFC 6265 token (printable ASCII other than separators such as `=`, `;` and spaces)
+    pub fn try_build(&self) -> HandlerResult<String> {
+        if self.name.is_empty() || !self.name.bytes().all(is_token_char) {
+            return Err(format!("Invalid cookie name: {:?}", self.name).into());
+        }
+        Ok(self.build())
+    }
+
+    /// Sets the cookie on a response. Because the codec's response holds a single value per
+    /// header, a response can only set one cookie; applying another replaces it
+    pub fn apply(&self, response: &mut Response) {
+        response.header.insert(SET_COOKIE.to_string(), self.build());
+    }
+}
+
+impl fmt::Display for CookieBuilder {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}={}",
+            encode(&self.name, is_token_char),
+            encode(&self.value, is_cookie_octet)
+        )?;
+        if let Some(ref domain) = self.domain {
+            write!(f, "; Domain={}", encode(domain, is_attribute_char))?;
+        }
+        if let Some(ref path) = self.path {
+            write!(f, "; Path={}", encode(path, is_attribute_char))?;
+        }
+        if let Some(max_age) = self.max_age {
+            write!(f, "; Max-Age={}", max_age)?;
+        }
+        if let Some(expires) = self.expires {
+            write!(f, "; Expires={}", http_date(expires))?;
+        }
+        if self.secure {
+            write!(f, "; Secure")?;
+        }
+        if self.http_only {
+            write!(f, "; HttpOnly")?;
+        }
+        match self.same_site {
+            Some(SameSite::Strict) => write!(f, "; SameSite=Strict"),
+            Some(SameSite::Lax) => write!(f, "; SameSite=Lax"),
+            Some(SameSite::None) => write!(f, "; SameSite=None"),
+            None => Ok(()),
+        }
+    }
+}
+
+// Percent-encodes the bytes of `s` that `allowed` rejects, along with `%` itself
+fn encode(s: &str, allowed: fn(u8) -> bool) -> String {
+    let mut encoded = String::with_capacity(s.len());
+    for b in s.bytes() {
+        if b != b'%' && allowed(b) {
+            encoded.push(b as char);
+        } else {
+            encoded.push_str(&format!("%{:02X}", b));
+        }
+    }
+    encoded
+}
+
+// A `token` character (RFC 7230): printable ASCII other than separators
+fn is_token_char(b: u8) -> bool {
+    b.is_ascii_graphic() && !b"()<>@,;:\\\"/[]?={}".contains(&b)
+}
+
+// A `cookie-octet` (RFC 6265): printable ASCII other than `"`, `,`, `;` and `\`
+fn is_cookie_octet(b: u8) -> bool {
+    b.is_ascii_graphic() && !b"\",;\\".contains(&b)
+}
+
+// A character left as-is in an attribute value: printable ASCII other than `;`
+fn is_attribute_char(b: u8) -> bool {
+    b.is_ascii_graphic() && b != b';'
+}
+
+/// Formats a time, in seconds since the Unix epoch, as an HTTP date
+/// (e.g. `Sun, 06 Nov 1994 08:49:37 GMT`)
+pub fn http_date(epoch_s: u64) -> String {
+    const DAYS: [&str; 7] = ["Thu", "Fri", "Sat", "Sun", "Mon", "Tue", "Wed"];
+    const MONTHS: [&str; 12] = [
+        "Jan", "Feb", "Mar", "Apr", "May", "Jun", "Jul", "Aug", "Sep", "Oct", "Nov", "Dec",
+    ];
+    let days = epoch_s / 86_400;
+    let secs = epoch_s % 86_400;
+
+    // Converts days since the epoch to a civil date (Howard Hinnant's algorithm)
+    let z = days as i64 + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z - era * 146_097;
+    let yoe = (doe - doe / 1_460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + if month <= 2 { 1 } else { 0 };
+
+    format!(
+        "{}, {:02} {} {} {:02}:{:02}:{:02} GMT",
+        DAYS[(days % 7) as usize],
+        day,
+        MONTHS[(month - 1) as usize],
+        year,
+        secs / 3_600,
+        secs % 3_600 / 60,
+        secs % 60
+    )
+}
@@ -0,0 +1,319 @@
+//! # JWT Authentication
+//!
+//! This module contains a validator for JSON Web Tokens signed with HS256, RS256 or EdDSA,
+//! and middleware that authenticates requests carrying a Bearer token, exposing the
+//! token's claims to handlers through the request context. Requires the `jwt` feature
+
+use super::{Context, FromRequest, Middleware, Response, ResponseExt};
+pub use crate::claims::Claims;
+use crate::HandlerResult;
+use ed25519_dalek::Verifier;
+use hmac::{Hmac, Mac, NewMac};
+use num_bigint::BigUint;
+use serde_json::{Map, Value};
+use sha2::{Digest, Sha256};
+use std::convert::TryFrom;
+
+/// The DER-encoded `DigestInfo` prefix of a SHA-256 digest in a PKCS#1 v1.5 signature
+const SHA256_DIGEST_INFO: [u8; 19] = [
+    0x30, 0x31, 0x30, 0x0d, 0x06, 0x09, 0x60, 0x86, 0x48, 0x01, 0x65, 0x03, 0x04, 0x02, 0x01, 0x05,
+    0x00, 0x04, 0x20,
+];
+
+/// A key against which token signatures are verified
+pub enum JwtKey {
+    /// A shared secret for `HS256` tokens
+    Hmac(Vec<u8>),
+    /// An RSA public key for `RS256` tokens
+    Rsa {
+        /// The modulus
+        n: BigUint,
+        /// The public exponent
+        e: BigUint,
+    },
+    /// An Ed25519 public key for `EdDSA` tokens
+    Ed25519(ed25519_dalek::PublicKey),
+}
+
+impl JwtKey {
+    /// Creates a key for `HS256` tokens from a shared secret
+    pub fn hs256(secret: &[u8]) -> JwtKey {
+        JwtKey::Hmac(secret.to_vec())
+    }
+
+    /// Creates a key for `RS256` tokens from the base64url-encoded modulus and exponent of
+    /// an RSA public key, as found in the `n` and `e` members of a JSON Web Key
+    pub fn rs256(n: &str, e: &str) -> HandlerResult<JwtKey> {
+        Ok(JwtKey::Rsa {
+            n: BigUint::from_bytes_be(&decode_segment(n)?),
+            e: BigUint::from_bytes_be(&decode_segment(e)?),
+        })
+    }
+
+    /// Creates a key for `EdDSA` tokens from a 32-byte Ed25519 public key
+    pub fn eddsa(public_key: &[u8]) -> HandlerResult<JwtKey> {
+        ed25519_dalek::PublicKey::from_bytes(public_key)
+            .map(JwtKey::Ed25519)
+            .map_err(|e| format!("Invalid Ed25519 public key: {}", e).into())
+    }
+
+    fn algorithm(&self) -> &'static str {
+        match self {
+            JwtKey::Hmac(_) => "HS256",
+            JwtKey::Rsa { .. } => "RS256",
+            JwtKey::Ed25519(_) => "EdDSA",
+        }
+    }
+
+    fn verify(&self, message: &[u8], signature: &[u8]) -> bool {
+        match self {
+            JwtKey::Hmac(secret) => match Hmac::<Sha256>::new_varkey(secret) {
+                Ok(mut mac) => {
+                    mac.update(message);
+                    mac.verify(signature).is_ok()
+                }
+                Err(_) => false,
+            },
+            JwtKey::Rsa { n, e } => {
+                let k = n.bits().div_ceil(8) as usize;
+                let s = BigUint::from_bytes_be(signature);
+                if signature.len() != k || &s >= n {
+                    return false;
+                }
+                let em = s.modpow(e, n).to_bytes_be();
+                let mut expected = vec![0xff; k];
+                let t_len = SHA256_DIGEST_INFO.len() + 32;
+                if k < t_len + 11 {
+                    return false;
+                }
+                expected[0] = 0x00;
+                expected[1] = 0x01;
+                expected[k - t_len - 1] = 0x00;
+                expected[k - t_len..k - 32].copy_from_slice(&SHA256_DIGEST_INFO);
+                expected[k - 32..].copy_from_slice(&Sha256::digest(message));
+                // The leading zero byte is dropped by the integer conversion
+                em.len() == k - 1 && em[..] == expected[1..]
+            }
+            JwtKey::Ed25519(key) => ed25519_dalek::Signature::try_from(signature)
+                .map(|s| key.verify(message, &s).is_ok())
+                .unwrap_or(false),
+        }
+    }
+}
+
+/// As a handler parameter, the claims stored in the context by `JwtAuth`. Requests that
+/// were not authenticated are rejected with a 401
+impl FromRequest for Claims {
+    fn from_request(ctx: &Context) -> Result<Self, Response> {
+        ctx.get::<Claims>()
+            .cloned()
+            .ok_or_else(|| unauthorized("missing_token"))
+    }
+}
+
+/// Validates tokens against a set of keys, checking their signature, expiry (`exp`), start
+/// (`nbf`) and optionally their issuer (`iss`) and audience (`aud`). A token is accepted if
+/// its signature verifies with any configured key of the token's algorithm; if the token's
+/// header names a key ID (`kid`), keys registered under a different ID are skipped
+///
+/// ```
+/// # use wascc_actor::http::jwt::{JwtKey, JwtValidator};
+/// let validator = JwtValidator::new().key(JwtKey::hs256(b"your-256-bit-secret"));
+/// let token = "eyJhbGciOiJIUzI1NiIsInR5cCI6IkpXVCJ9.\
+///              eyJzdWIiOiIxMjM0NTY3ODkwIiwibmFtZSI6IkpvaG4gRG9lIiwiaWF0IjoxNTE2MjM5MDIyfQ.\
+///              SflKxwRJSMeKKF2QT4fwpMeJf36POk6yJV_adQssw5c";
+/// let claims = validator.validate_at(token, 1_516_239_022).unwrap();
+/// assert_eq!(claims.subject(), Some("1234567890"));
+/// assert!(validator.validate_at(&token.replace("Sfl", "Xfl"), 1_516_239_022).is_err());
+/// ```
+#[derive(Default)]
+pub struct JwtValidator {
+    keys: Vec<(Option<String>, JwtKey)>,
+    issuer: Option<String>,
+    audience: Option<String>,
+    leeway_s: u64,
+}
+
+impl JwtValidator {
+    /// Creates a validator with no keys
+    pub fn new() -> JwtValidator {
+        JwtValidator::default()
+    }
+
+    /// Adds a key
+    pub fn key(mut self, key: JwtKey) -> JwtValidator {
+        self.keys.push((None, key));
+        self
+    }
+
+    /// Adds a key that is used for tokens whose header names the given key ID
+    pub fn key_with_id(mut self, kid: &str, key: JwtKey) -> JwtValidator {
+        self.keys.push((Some(kid.to_string()), key));
+        self
+    }
+
+    /// Requires tokens to have been issued by the given issuer
+    pub fn issuer(mut self, issuer: &str) -> JwtValidator {
+        self.issuer = Some(issuer.to_string());
+        self
+    }
+
+    /// Requires tokens to be intended for the given audience
+    pub fn audience(mut self, audience: &str) -> JwtValidator {
+        self.audience = Some(audience.to_string());
+        self
+    }
+
+    /// Allows for clock skew between the issuer and the host when checking `exp` and `nbf`
+    pub fn leeway(mut self, seconds: u64) -> JwtValidator {
+        self.leeway_s = seconds;
+        self
+    }
+
+    /// Validates a token against the host's clock, returning its claims. The time is read
+    /// through the `wascc:extras` capability, so on hosts whose extras provider cannot tell
+    /// the time every token is rejected; use `validate_at` with a time from elsewhere there
+    pub fn validate(&self, token: &str) -> HandlerResult<Claims> {
+        self.validate_at(token, host_clock()?)
+    }
+
+    /// Validates a token as of the given time, in seconds since the Unix epoch
+    pub fn validate_at(&self, token: &str, now_s: u64) -> HandlerResult<Claims> {
+        let mut segments = token.split('.');
+        let (header, payload, signature) = match (
+            segments.next(),
+            segments.next(),
+            segments.next(),
+            segments.next(),
+        ) {
+            (Some(h), Some(p), Some(s), None) => (h, p, s),
+            _ => return Err("Token is not a JWS compact serialization".into()),
+        };
+        let header: Map<String, Value> = serde_json::from_slice(&decode_segment(header)?)?;
+        let algorithm = header.get("alg").and_then(|a| a.as_str()).unwrap_or("none");
+        let kid = header.get("kid").and_then(|k| k.as_str());
+
+        let message = &token[..token.len() - signature.len() - 1];
+        let signature = decode_segment(signature)?;
+        let verified = self
+            .keys
+            .iter()
+            .filter(|(key_id, key)| {
+                key.algorithm() == algorithm
+                    && (kid.is_none() || key_id.is_none() || key_id.as_deref() == kid)
+            })
+            .any(|(_, key)| key.verify(message.as_bytes(), &signature));
+        if !verified {
+            return Err(format!("Token signature ({}) could not be verified", algorithm).into());
+        }
+
+        let claims: Map<String, Value> = serde_json::from_slice(&decode_segment(payload)?)?;
+        if let Some(exp) = numeric_date(&claims, "exp")? {
+            if now_s.saturating_sub(self.leeway_s) as f64 > exp {
+                return Err("Token has expired".into());
+            }
+        }
+        if let Some(nbf) = numeric_date(&claims, "nbf")? {
+            if (now_s.saturating_add(self.leeway_s) as f64) < nbf {
+                return Err("Token is not yet valid".into());
+            }
+        }
+        if let Some(ref issuer) = self.issuer {
+            if claims.get("iss").and_then(|v| v.as_str()) != Some(issuer.as_str()) {
+                return Err("Token was not issued by the expected issuer".into());
+            }
+        }
+        if let Some(ref audience) = self.audience {
+            let matches = match claims.get("aud") {
+                Some(Value::String(aud)) => aud == audience,
+                Some(Value::Array(auds)) => auds.iter().any(|a| a.as_str() == Some(audience)),
+                _ => false,
+            };
+            if !matches {
+                return Err("Token is not intended for the expected audience".into());
+            }
+        }
+        Ok(Claims::from(claims))
+    }
+}
+
+/// Reads a NumericDate claim (seconds since the Unix epoch, possibly fractional), failing if
+/// the claim is present but not a number
+fn numeric_date(claims: &Map<String, Value>, name: &str) -> HandlerResult<Option<f64>> {
+    match claims.get(name) {
+        None => Ok(None),
+        Some(value) => match value.as_f64() {
+            Some(date) if date.is_finite() => Ok(Some(date)),
+            _ => Err(format!("Token's {} claim is not a NumericDate", name).into()),
+        },
+    }
+}
+
+fn host_clock() -> HandlerResult<u64> {
+    Ok(crate::extras::default().get_time()?.epoch_ms / 1000)
+}
+
+/// Middleware that authenticates requests with the Bearer token in their `Authorization`
+/// header. Requests without a valid token are rejected with a 401; for the rest, the
+/// token's `Claims` are stored in the context, where handlers can retrieve them with
+/// `ctx.get::<Claims>()` or by taking a `Claims` parameter.
+///
+/// Tokens are checked against the host's clock (see `JwtValidator::validate`), so on hosts
+/// that cannot tell the time every request is rejected unless another clock is supplied with
+/// `clock`
+pub struct JwtAuth {
+    validator: JwtValidator,
+    clock: fn() -> HandlerResult<u64>,
+}
+
+impl JwtAuth {
+    /// Creates middleware that validates tokens with the given validator
+    pub fn new(validator: JwtValidator) -> JwtAuth {
+        JwtAuth {
+            validator,
+            clock: host_clock,
+        }
+    }
+
+    /// Replaces the clock against which tokens are checked, a function returning the current
+    /// time in seconds since the Unix epoch
+    pub fn clock(mut self, clock: fn() -> HandlerResult<u64>) -> JwtAuth {
+        self.clock = clock;
+        self
+    }
+}
+
+impl Middleware for JwtAuth {
+    fn before(&self, ctx: &mut Context) -> HandlerResult<Option<Response>> {
+        let token = match ctx
+            .header("authorization")
+            .and_then(|h| h.split_once(' '))
+            .filter(|(scheme, _)| scheme.eq_ignore_ascii_case("bearer"))
+        {
+            Some((_, token)) => token.trim().to_string(),
+            None => return Ok(Some(unauthorized("missing_token"))),
+        };
+        match (self.clock)().and_then(|now| self.validator.validate_at(&token, now)) {
+            Ok(claims) => {
+                ctx.insert(claims);
+                Ok(None)
+            }
+            Err(e) => {
+                log::debug!("Rejected bearer token: {}", e);
+                Ok(Some(unauthorized("invalid_token")))
+            }
+        }
+    }
+}
+
+fn unauthorized(error: &str) -> Response {
+    Response::builder()
+        .status(401)
+        .header("WWW-Authenticate", &format!("Bearer error=\"{}\"", error))
+        .empty()
+}
+
+fn decode_segment(segment: &str) -> HandlerResult<Vec<u8>> {
+    base64::decode_config(segment, base64::URL_SAFE_NO_PAD)
+        .map_err(|e| format!("Invalid base64url in token: {}", e).into())
+}
@@ -0,0 +1,61 @@
+//! # Middleware
+//!
+//! This module contains the middleware abstraction through which cross-cutting concerns
+//! (logging, authentication, rate limiting, compression) wrap the handlers of a `Router`
+
+use super::{Context, Response};
+use crate::extras::Stopwatch;
+use crate::HandlerResult;
+
+/// Wraps the handling of every request that passes through a `Router`. Middleware runs in
+/// the order it was added to the router: each `before` hook runs before the request reaches
+/// its handler, and each `after` hook runs in reverse order once a response has been
+/// produced. A `before` hook can short-circuit the request by returning a response, in
+/// which case the handler and any later middleware are skipped, while the `after` hooks of
+/// the middleware that already ran still see the response. The same is true when the
+/// handler or a `before` hook fails: the router answers with a 500 response, and the
+/// `after` hooks see that
+pub trait Middleware: Send + Sync {
+    /// Runs before the handler. Returning a response skips the handler, and values stored
+    /// in the context with `Context::insert` are visible to the handler and later hooks
+    fn before(&self, _ctx: &mut Context) -> HandlerResult<Option<Response>> {
+        Ok(None)
+    }
+
+    /// Runs after the handler (or a short-circuiting `before` hook), and may modify the
+    /// response
+    fn after(&self, _ctx: &Context, _response: &mut Response) -> HandlerResult<()> {
+        Ok(())
+    }
+}
+
+/// Logs the method, path and status of every request at the info level, along with the
+/// time spent handling it
+#[derive(Default)]
+pub struct RequestLogger;
+
+struct RequestTimer(Stopwatch);
+
+impl Middleware for RequestLogger {
+    fn before(&self, ctx: &mut Context) -> HandlerResult<Option<Response>> {
+        if let Ok(stopwatch) = Stopwatch::start() {
+            ctx.insert(RequestTimer(stopwatch));
+        }
+        Ok(None)
+    }
+
+    fn after(&self, ctx: &Context, response: &mut Response) -> HandlerResult<()> {
+        let elapsed = ctx
+            .get::<RequestTimer>()
+            .and_then(|t| t.0.elapsed_ms().ok())
+            .map_or("".to_string(), |ms| format!(" in {}ms", ms));
+        log::info!(
+            "{} {} {}{}",
+            ctx.request().method,
+            ctx.request().path,
+            response.status_code,
+            elapsed
+        );
+        Ok(())
+    }
+}
@@ -0,0 +1,89 @@
+//! # Content Negotiation
+//!
+//! This module contains a helper that serializes a response body in whichever format the
+//! client asked for in its `Accept` header
+
+use super::{reason_phrase, Request, Response, ResponseExt, CONTENT_TYPE_JSON};
+use crate::HandlerResult;
+use serde::Serialize;
+use wascc_codec::serialize;
+
+/// The content type of MessagePack response bodies
+pub const CONTENT_TYPE_MSGPACK: &str = "application/msgpack";
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Format {
+    Json,
+    MessagePack,
+    Text,
+}
+
+/// Serializes the value in the format preferred by the request's `Accept` header: JSON
+/// (`application/json`), MessagePack (`application/msgpack` or `application/x-msgpack`) or
+/// plain text (`text/plain`). Quality values are honored, wildcards select JSON (or text for
+/// `text/*`), and a request without an `Accept` header receives JSON. Plain text bodies hold
+/// the value itself if it serializes to a string, and its JSON representation otherwise.
+/// If none of the formats is acceptable, the response is a `406 Not Acceptable`
+///
+/// ```
+/// # use wascc_actor::http::{respond_negotiated, Request};
+/// # use std::collections::HashMap;
+/// let mut header = HashMap::new();
+/// header.insert("Accept".to_string(), "text/html, text/plain;q=0.5".to_string());
+/// let req = Request { header, ..Default::default() };
+/// let response = respond_negotiated(&req, &"hello").unwrap();
+/// assert_eq!(response.body, b"hello");
+/// ```
+pub fn respond_negotiated<T: Serialize>(request: &Request, value: &T) -> HandlerResult<Response> {
+    let accept = request
+        .header
+        .iter()
+        .find(|(k, _)| k.eq_ignore_ascii_case("accept"))
+        .map(|(_, v)| v.as_str())
+        .unwrap_or("*/*");
+    let response = Response::builder();
+    match negotiate(accept) {
+        Some(Format::Json) => response.json(value),
+        Some(Format::MessagePack) => Ok(response
+            .content_type(CONTENT_TYPE_MSGPACK)
+            .body(serialize(value)?)),
+        Some(Format::Text) => {
+            let body = match serde_json::to_value(value)? {
+                serde_json::Value::String(s) => s,
+                other => other.to_string(),
+            };
+            Ok(response.text(&body))
+        }
+        None => Ok(response.status(406).text(&format!(
+            "{}: supported types are {}, {} and text/plain",
+            reason_phrase(406),
+            CONTENT_TYPE_JSON,
+            CONTENT_TYPE_MSGPACK
+        ))),
+    }
+}
+
+fn negotiate(accept: &str) -> Option<Format> {
+    let mut ranges: Vec<(String, f32)> = accept
+        .split(',')
+        .filter_map(|range| {
+            let mut params = range.split(';');
+            let media_type = params.next()?.trim().to_ascii_lowercase();
+            let quality = params
+                .filter_map(|p| p.trim().strip_prefix("q="))
+                .find_map(|q| q.trim().parse::<f32>().ok())
+                .unwrap_or(1.0);
+            Some((media_type, quality))
+        })
+        .filter(|(_, quality)| *quality > 0.0)
+        .collect();
+    ranges.sort_by(|a, b| b.1.partial_cmp(&a.1).unwrap_or(std::cmp::Ordering::Equal));
+    ranges
+        .iter()
+        .find_map(|(media_type, _)| match media_type.as_str() {
+            "application/json" | "application/*" | "*/*" => Some(Format::Json),
+            "application/msgpack" | "application/x-msgpack" => Some(Format::MessagePack),
+            "text/plain" | "text/*" => Some(Format::Text),
+            _ => None,
+        })
+}
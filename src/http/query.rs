@@ -0,0 +1,326 @@
+//! # Query Strings
+//!
+//! This module contains a parser for URL-encoded query strings, along with a serde
+//! deserializer that decodes them into typed values
+
+use crate::HandlerResult;
+use serde::de::value::{Error, StringDeserializer};
+use serde::de::{self, DeserializeOwned, DeserializeSeed, IntoDeserializer, Visitor};
+use serde::forward_to_deserialize_any;
+
+/// The decoded key/value pairs of a query string, in the order they appeared. Keys may
+/// be repeated (`?tag=a&tag=b`); `get` returns the first value for a key and `get_all`
+/// returns every value
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct QueryMap {
+    pairs: Vec<(String, String)>,
+}
+
+impl QueryMap {
+    /// Parses a query string (without the leading `?`), decoding percent-encoded bytes and
+    /// `+` as a space. Keys without a value (`?verbose`) have an empty value
+    pub fn parse(query: &str) -> QueryMap {
+        QueryMap {
+            pairs: query
+                .split('&')
+                .filter(|p| !p.is_empty())
+                .map(|p| {
+                    let mut pair = p.splitn(2, '=');
+                    let key = pair.next().unwrap_or_default();
+                    let value = pair.next().unwrap_or_default();
+                    (url_decode(key, true), url_decode(value, true))
+                })
+                .collect(),
+        }
+    }
+
+    /// The first value of the given key
+    pub fn get(&self, key: &str) -> Option<&str> {
+        self.pairs
+            .iter()
+            .find(|(k, _)| k == key)
+            .map(|(_, v)| v.as_str())
+    }
+
+    /// Every value of the given key, in order
+    pub fn get_all(&self, key: &str) -> Vec<&str> {
+        self.pairs
+            .iter()
+            .filter(|(k, _)| k == key)
+            .map(|(_, v)| v.as_str())
+            .collect()
+    }
+
+    /// Indicates whether the key appears in the query string
+    pub fn contains(&self, key: &str) -> bool {
+        self.pairs.iter().any(|(k, _)| k == key)
+    }
+
+    /// Iterates over every key/value pair
+    pub fn iter(&self) -> impl Iterator<Item = (&str, &str)> {
+        self.pairs.iter().map(|(k, v)| (k.as_str(), v.as_str()))
+    }
+
+    /// The number of key/value pairs
+    pub fn len(&self) -> usize {
+        self.pairs.len()
+    }
+
+    /// Indicates whether the query string is empty
+    pub fn is_empty(&self) -> bool {
+        self.pairs.is_empty()
+    }
+
+    /// Deserializes the query string into a type. Values are parsed into the field's type,
+    /// repeated keys can be collected into a `Vec`, missing keys can be captured with
+    /// `Option`, and fields holding a single value take the key's first value
+    pub fn deserialize<T: DeserializeOwned>(&self) -> HandlerResult<T> {
+        let mut entries: Vec<(String, Vec<String>)> = Vec::new();
+        for (key, value) in &self.pairs {
+            match entries.iter_mut().find(|(k, _)| k == key) {
+                Some((_, values)) => values.push(value.to_string()),
+                None => entries.push((key.to_string(), vec![value.to_string()])),
+            }
+        }
+        T::deserialize(QueryDeserializer { entries })
+            .map_err(|e| format!("Invalid query string: {}", e).into())
+    }
+}
+
+/// Decodes percent-encoded bytes in a URL component, and (in query strings) `+` as a space.
+/// Malformed escapes are left as they are, and invalid UTF-8 is replaced
+pub fn url_decode(value: &str, plus_as_space: bool) -> String {
+    let bytes = value.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        match bytes[i] {
+            b'%' => match bytes
+                .get(i + 1..i + 3)
+                .filter(|h| h.iter().all(u8::is_ascii_hexdigit))
+                .and_then(|h| u8::from_str_radix(std::str::from_utf8(h).ok()?, 16).ok())
+            {
+                Some(byte) => {
+                    decoded.push(byte);
+                    i += 3;
+                    continue;
+                }
+                None => decoded.push(b'%'),
+            },
+            b'+' if plus_as_space => decoded.push(b' '),
+            b => decoded.push(b),
+        }
+        i += 1;
+    }
+    String::from_utf8_lossy(&decoded).to_string()
+}
+
+struct QueryDeserializer {
+    entries: Vec<(String, Vec<String>)>,
+}
+
+impl<'de> de::Deserializer<'de> for QueryDeserializer {
+    type Error = Error;
+
+    fn deserialize_any<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        visitor.visit_map(QueryMapAccess {
+            entries: self.entries.into_iter(),
+            values: None,
+        })
+    }
+
+    forward_to_deserialize_any! {
+        bool i8 i16 i32 i64 i128 u8 u16 u32 u64 u128 f32 f64 char str string
+        bytes byte_buf option unit unit_struct newtype_struct seq tuple
+        tuple_struct map struct enum identifier ignored_any
+    }
+}
+
+struct QueryMapAccess {
+    entries: std::vec::IntoIter<(String, Vec<String>)>,
+    values: Option<Vec<String>>,
+}
+
+impl<'de> de::MapAccess<'de> for QueryMapAccess {
+    type Error = Error;
+
+    fn next_key_seed<K: DeserializeSeed<'de>>(
+        &mut self,
+        seed: K,
+    ) -> Result<Option<K::Value>, Error> {
+        match self.entries.next() {
+            Some((key, values)) => {
+                self.values = Some(values);
+                let key: StringDeserializer<Error> = key.into_deserializer();
+                seed.deserialize(key).map(Some)
+            }
+            None => Ok(None),
+        }
+    }
+
+    fn next_value_seed<V: DeserializeSeed<'de>>(&mut self, seed: V) -> Result<V::Value, Error> {
+        seed.deserialize(ValuesDeserializer(self.values.take().unwrap_or_default()))
+    }
+}
+
+struct ValuesDeserializer(Vec<String>);
+
+impl ValuesDeserializer {
+    fn first(self) -> ValueDeserializer {
+        ValueDeserializer(self.0.into_iter().next().unwrap_or_default())
+    }
+}
+
+macro_rules! deserialize_first(
+    { $($method:ident),* } => {
+        $(
+            fn $method<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+                self.first().$method(visitor)
+            }
+        )*
+    };
+);
+
+impl<'de> de::Deserializer<'de> for ValuesDeserializer {
+    type Error = Error;
+
+    fn deserialize_any<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        if self.0.len() == 1 {
+            self.first().deserialize_any(visitor)
+        } else {
+            self.deserialize_seq(visitor)
+        }
+    }
+
+    deserialize_first! {
+        deserialize_bool, deserialize_i8, deserialize_i16, deserialize_i32, deserialize_i64,
+        deserialize_u8, deserialize_u16, deserialize_u32, deserialize_u64, deserialize_f32,
+        deserialize_f64, deserialize_char, deserialize_str, deserialize_string,
+        deserialize_unit
+    }
+
+    fn deserialize_enum<V: Visitor<'de>>(
+        self,
+        name: &'static str,
+        variants: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, Error> {
+        self.first().deserialize_enum(name, variants, visitor)
+    }
+
+    fn deserialize_seq<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        visitor.visit_seq(de::value::SeqDeserializer::new(
+            self.0.into_iter().map(ValueDeserializer),
+        ))
+    }
+
+    fn deserialize_tuple<V: Visitor<'de>>(
+        self,
+        _len: usize,
+        visitor: V,
+    ) -> Result<V::Value, Error> {
+        self.deserialize_seq(visitor)
+    }
+
+    fn deserialize_option<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        visitor.visit_some(self)
+    }
+
+    fn deserialize_newtype_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        visitor: V,
+    ) -> Result<V::Value, Error> {
+        visitor.visit_newtype_struct(self)
+    }
+
+    forward_to_deserialize_any! {
+        i128 u128 bytes byte_buf unit_struct tuple_struct map struct identifier
+        ignored_any
+    }
+}
+
+struct ValueDeserializer(String);
+
+macro_rules! deserialize_parsed(
+    { $($method:ident => $visit:ident),* } => {
+        $(
+            fn $method<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+                match self.0.parse() {
+                    Ok(v) => visitor.$visit(v),
+                    Err(_) => Err(de::Error::custom(format!("invalid value '{}'", self.0))),
+                }
+            }
+        )*
+    };
+);
+
+impl<'de> de::Deserializer<'de> for ValueDeserializer {
+    type Error = Error;
+
+    fn deserialize_any<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        visitor.visit_string(self.0)
+    }
+
+    deserialize_parsed! {
+        deserialize_bool => visit_bool,
+        deserialize_i8 => visit_i8,
+        deserialize_i16 => visit_i16,
+        deserialize_i32 => visit_i32,
+        deserialize_i64 => visit_i64,
+        deserialize_u8 => visit_u8,
+        deserialize_u16 => visit_u16,
+        deserialize_u32 => visit_u32,
+        deserialize_u64 => visit_u64,
+        deserialize_f32 => visit_f32,
+        deserialize_f64 => visit_f64,
+        deserialize_char => visit_char
+    }
+
+    fn deserialize_option<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        if self.0.is_empty() {
+            visitor.visit_none()
+        } else {
+            visitor.visit_some(self)
+        }
+    }
+
+    fn deserialize_seq<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        ValuesDeserializer(vec![self.0]).deserialize_seq(visitor)
+    }
+
+    fn deserialize_newtype_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        visitor: V,
+    ) -> Result<V::Value, Error> {
+        visitor.visit_newtype_struct(self)
+    }
+
+    fn deserialize_enum<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        _variants: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, Error> {
+        let value: StringDeserializer<Error> = self.0.into_deserializer();
+        visitor.visit_enum(value)
+    }
+
+    fn deserialize_unit<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        visitor.visit_unit()
+    }
+
+    forward_to_deserialize_any! {
+        i128 u128 str string bytes byte_buf unit_struct tuple tuple_struct map
+        struct identifier ignored_any
+    }
+}
+
+impl<'de> IntoDeserializer<'de, Error> for ValueDeserializer {
+    type Deserializer = Self;
+
+    fn into_deserializer(self) -> Self {
+        self
+    }
+}
@@ -0,0 +1,183 @@
+//! # Streamed Responses
+//!
+//! This module contains a writer that produces the body of an HTTP response incrementally,
+//! sending it to the `wascc:http_server` capability provider one chunk at a time rather than
+//! buffering the entire body in the actor's memory
+
+use super::{Request, Response, ResponseExt};
+use crate::HandlerResult;
+use serde_derive::{Deserialize, Serialize};
+use std::io::{self, Read, Write};
+use wapc_guest::host_call;
+use wascc_codec::serialize;
+
+/// The reserved capability ID for the HTTP server functionality
+pub const CAPID_HTTP_SERVER: &str = "wascc:http_server";
+
+/// Guest sends a `ResponseHead` to the capability provider, receives nothing back
+pub const OP_BEGIN_RESPONSE_STREAM: &str = "BeginResponseStream";
+/// Guest sends a `ResponseChunk` to the capability provider, receives nothing back
+pub const OP_WRITE_RESPONSE_CHUNK: &str = "WriteResponseChunk";
+
+/// The header on the response returned from a handler that indicates its body has already
+/// been streamed to the provider. Its value is the ID of the stream
+pub const STREAMED_RESPONSE_HEADER: &str = "X-Wascc-Streamed";
+
+/// The request header from which the stream ID is taken, if the provider supplies it
+pub const REQUEST_ID_HEADER: &str = "X-Request-Id";
+
+/// The default number of bytes sent to the provider in each chunk
+pub const DEFAULT_STREAM_CHUNK_SIZE: usize = 64 * 1024;
+
+/// The status and headers of a streamed response, sent before any of its body
+#[derive(Debug, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ResponseHead {
+    /// The ID of the stream (see `ResponseStream::start`)
+    pub stream_id: String,
+    /// The status, headers and (empty) body of the response
+    pub response: Response,
+}
+
+/// A chunk of the body of a streamed response
+#[derive(Debug, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ResponseChunk {
+    /// The ID of the stream
+    pub stream_id: String,
+    /// The zero-based position of this chunk in the body
+    pub sequence_no: u64,
+    /// The bytes of the chunk
+    #[serde(with = "serde_bytes")]
+    #[serde(default)]
+    pub bytes: Vec<u8>,
+    /// Indicates this is the final chunk of the body
+    pub is_last: bool,
+}
+
+/// Writes the body of a response incrementally. Starting a stream sends the response's
+/// status and headers to the provider, each full chunk written is sent as it fills, and
+/// `finish` sends the final chunk and returns the response that the handler must return
+/// to tell the provider the body has already been sent.
+///
+/// Providers associate streamed chunks with the request that the calling actor is
+/// handling; the stream ID (the request's `X-Request-Id` header, if the provider supplies
+/// one) lets them disambiguate concurrent requests
+///
+/// ```
+/// extern crate wascc_actor as actor;
+/// use actor::prelude::*;
+/// use actor::http::ResponseStream;
+/// use std::io::Write;
+///
+/// fn export(req: codec::http::Request) -> HandlerResult<codec::http::Response> {
+///     let head = codec::http::Response::builder().content_type("text/csv").empty();
+///     let mut stream = ResponseStream::start(&req, head)?;
+///     for key in keyvalue::default().set_members("customers")? {
+///         writeln!(stream, "{}", key)?;
+///     }
+///     stream.finish()
+/// }
+/// ```
+pub struct ResponseStream {
+    binding: String,
+    stream_id: String,
+    sequence_no: u64,
+    chunk_size: usize,
+    buffer: Vec<u8>,
+}
+
+impl ResponseStream {
+    /// Starts streaming the response to a request through the default binding, sending the
+    /// status and headers of the given response. Any body the response holds is ignored
+    pub fn start(request: &Request, head: Response) -> HandlerResult<ResponseStream> {
+        ResponseStream::start_with("default", request, head, DEFAULT_STREAM_CHUNK_SIZE)
+    }
+
+    /// Starts streaming a response through the given binding, sending chunks of the given
+    /// size
+    pub fn start_with(
+        binding: &str,
+        request: &Request,
+        mut head: Response,
+        chunk_size: usize,
+    ) -> HandlerResult<ResponseStream> {
+        let stream_id = request
+            .header
+            .iter()
+            .find(|(k, _)| k.eq_ignore_ascii_case(REQUEST_ID_HEADER))
+            .map(|(_, v)| v.to_string())
+            .unwrap_or_default();
+        head.body = Vec::new();
+        let cmd = ResponseHead {
+            stream_id: stream_id.clone(),
+            response: head,
+        };
+        host_call(
+            binding,
+            CAPID_HTTP_SERVER,
+            OP_BEGIN_RESPONSE_STREAM,
+            &serialize(cmd)?,
+        )?;
+        Ok(ResponseStream {
+            binding: binding.to_string(),
+            stream_id,
+            sequence_no: 0,
+            chunk_size: chunk_size.max(1),
+            buffer: Vec::with_capacity(chunk_size.max(1)),
+        })
+    }
+
+    /// Copies everything from a reader (such as a blob's `ChunkReader`) into the body,
+    /// returning the number of bytes copied
+    pub fn copy_from<R: Read>(&mut self, reader: &mut R) -> HandlerResult<u64> {
+        Ok(io::copy(reader, self)?)
+    }
+
+    /// Sends any buffered bytes as the final chunk of the body, returning the response the
+    /// handler must return
+    pub fn finish(mut self) -> HandlerResult<Response> {
+        let bytes = std::mem::take(&mut self.buffer);
+        self.send(bytes, true)?;
+        Ok(Response::builder()
+            .header(STREAMED_RESPONSE_HEADER, &self.stream_id)
+            .empty())
+    }
+
+    fn send(&mut self, bytes: Vec<u8>, is_last: bool) -> HandlerResult<()> {
+        let cmd = ResponseChunk {
+            stream_id: self.stream_id.clone(),
+            sequence_no: self.sequence_no,
+            bytes,
+            is_last,
+        };
+        host_call(
+            &self.binding,
+            CAPID_HTTP_SERVER,
+            OP_WRITE_RESPONSE_CHUNK,
+            &serialize(cmd)?,
+        )?;
+        self.sequence_no += 1;
+        Ok(())
+    }
+}
+
+impl Write for ResponseStream {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let count = (self.chunk_size - self.buffer.len()).min(buf.len());
+        self.buffer.extend_from_slice(&buf[..count]);
+        if self.buffer.len() == self.chunk_size {
+            self.flush()?;
+        }
+        Ok(count)
+    }
+
+    /// Sends any buffered bytes to the provider as a chunk
+    fn flush(&mut self) -> io::Result<()> {
+        if self.buffer.is_empty() {
+            return Ok(());
+        }
+        let bytes = std::mem::replace(&mut self.buffer, Vec::with_capacity(self.chunk_size));
+        self.send(bytes, false).map_err(io::Error::other)
+    }
+}
@@ -3,6 +3,10 @@
 //! This module contains the HTTP client through which actors consume
 //! the currently bound `wascap:http_client` capability provider
 
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use serde_derive::{Deserialize, Serialize};
+use std::collections::HashMap;
 use wapc_guest::host_call;
 use wascc_codec::{deserialize, http::*, serialize};
 
@@ -10,6 +14,21 @@ use crate::HandlerResult;
 
 const CAPID_HTTPCLIENT: &str = "wascc:http_client";
 
+/// Wire-compatible with the codec's `Request`, with the addition of a timeout. Providers
+/// unaware of the extension will ignore the timeout
+#[derive(Debug, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct TimedRequest {
+    method: String,
+    path: String,
+    query_string: String,
+    header: HashMap<String, String>,
+    #[serde(with = "serde_bytes")]
+    body: Vec<u8>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    timeout_ms: Option<u64>,
+}
+
 /// An abstraction around a host runtime capability for an HTTP client
 pub struct HttpClientHostBinding {
     binding: String,
@@ -36,14 +55,174 @@ pub fn default() -> HttpClientHostBinding {
 }
 
 impl HttpClientHostBinding {
+    /// Performs a request and returns the response, whatever its status
     pub fn request(&self, request: Request) -> HandlerResult<Response> {
+        self.perform(&request)
+    }
+
+    /// Starts building a request with the given method and URL
+    ///
+    /// ```
+    /// # use wascc_actor::http_client;
+    /// # use wascc_actor::HandlerResult;
+    /// # #[derive(serde_derive::Deserialize)]
+    /// # struct Rates { base: String }
+    /// fn fetch_rates() -> HandlerResult<Rates> {
+    ///     http_client::default()
+    ///         .build("GET", "https://api.example.com/rates")
+    ///         .query("base", "EUR")
+    ///         .header("Accept", "application/json")
+    ///         .timeout_ms(2_000)
+    ///         .send_json()
+    /// }
+    /// ```
+    pub fn build(&self, method: &str, url: &str) -> RequestBuilder<'_> {
+        RequestBuilder {
+            client: self,
+            request: TimedRequest {
+                method: method.to_uppercase(),
+                path: url.to_string(),
+                query_string: String::new(),
+                header: HashMap::new(),
+                body: Vec::new(),
+                timeout_ms: None,
+            },
+        }
+    }
+
+    /// Performs a `GET` request
+    pub fn get(&self, url: &str) -> HandlerResult<Response> {
+        self.build("GET", url).send()
+    }
+
+    /// Performs a `POST` request with the given body
+    pub fn post(&self, url: &str, body: Vec<u8>) -> HandlerResult<Response> {
+        self.build("POST", url).body(body).send()
+    }
+
+    /// Performs a `PUT` request with the given body
+    pub fn put(&self, url: &str, body: Vec<u8>) -> HandlerResult<Response> {
+        self.build("PUT", url).body(body).send()
+    }
+
+    /// Performs a `DELETE` request
+    pub fn delete(&self, url: &str) -> HandlerResult<Response> {
+        self.build("DELETE", url).send()
+    }
+
+    /// Performs a `GET` request and deserializes the JSON response body. Responses with a
+    /// status outside the 2xx range produce an error
+    pub fn get_json<T: DeserializeOwned>(&self, url: &str) -> HandlerResult<T> {
+        self.build("GET", url).send_json()
+    }
+
+    /// Performs a `POST` request with the value serialized as a JSON body, and deserializes
+    /// the JSON response body. Responses with a status outside the 2xx range produce an error
+    pub fn post_json<B: Serialize, T: DeserializeOwned>(
+        &self,
+        url: &str,
+        body: &B,
+    ) -> HandlerResult<T> {
+        self.build("POST", url).json(body)?.send_json()
+    }
+
+    /// Performs a `PUT` request with the value serialized as a JSON body, and deserializes
+    /// the JSON response body. Responses with a status outside the 2xx range produce an error
+    pub fn put_json<B: Serialize, T: DeserializeOwned>(
+        &self,
+        url: &str,
+        body: &B,
+    ) -> HandlerResult<T> {
+        self.build("PUT", url).json(body)?.send_json()
+    }
+
+    fn perform<T: Serialize>(&self, request: &T) -> HandlerResult<Response> {
         host_call(
             &self.binding,
             CAPID_HTTPCLIENT,
             OP_PERFORM_REQUEST,
             &serialize(request)?,
         )
-        .map(|r| deserialize::<Response>(r.as_ref()).unwrap())
         .map_err(|e| e.into())
+        .and_then(|r| deserialize::<Response>(r.as_ref()))
     }
 }
+
+/// Builds an outbound request. Obtained from `HttpClientHostBinding::build`
+pub struct RequestBuilder<'a> {
+    client: &'a HttpClientHostBinding,
+    request: TimedRequest,
+}
+
+impl<'a> RequestBuilder<'a> {
+    /// Sets a header, replacing any previous value of the same header
+    pub fn header(mut self, name: &str, value: &str) -> Self {
+        self.request
+            .header
+            .insert(name.to_string(), value.to_string());
+        self
+    }
+
+    /// Appends a query string parameter, percent-encoding the name and value
+    pub fn query(mut self, name: &str, value: &str) -> Self {
+        if !self.request.query_string.is_empty() {
+            self.request.query_string.push('&');
+        }
+        self.request.query_string.push_str(&url_encode(name));
+        self.request.query_string.push('=');
+        self.request.query_string.push_str(&url_encode(value));
+        self
+    }
+
+    /// Sets the body
+    pub fn body(mut self, body: Vec<u8>) -> Self {
+        self.request.body = body;
+        self
+    }
+
+    /// Sets the body to the value serialized as JSON, along with a JSON `Content-Type`
+    pub fn json<T: Serialize>(self, body: &T) -> HandlerResult<Self> {
+        let body = serde_json::to_vec(body)?;
+        Ok(self.header("Content-Type", "application/json").body(body))
+    }
+
+    /// Sets the number of milliseconds the provider waits for a response before failing
+    /// the request
+    pub fn timeout_ms(mut self, timeout_ms: u64) -> Self {
+        self.request.timeout_ms = Some(timeout_ms);
+        self
+    }
+
+    /// Performs the request and returns the response, whatever its status
+    pub fn send(self) -> HandlerResult<Response> {
+        self.client.perform(&self.request)
+    }
+
+    /// Performs the request and deserializes the JSON response body. Responses with a
+    /// status outside the 2xx range produce an error
+    pub fn send_json<T: DeserializeOwned>(self) -> HandlerResult<T> {
+        let response = self.send()?;
+        if !(200..300).contains(&response.status_code) {
+            return Err(format!(
+                "HTTP request failed with {} {}: {}",
+                response.status_code,
+                response.status,
+                String::from_utf8_lossy(&response.body)
+            )
+            .into());
+        }
+        Ok(serde_json::from_slice(&response.body)?)
+    }
+}
+
+fn url_encode(value: &str) -> String {
+    value
+        .bytes()
+        .map(|b| match b {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' => {
+                (b as char).to_string()
+            }
+            _ => format!("%{:02X}", b),
+        })
+        .collect()
+}
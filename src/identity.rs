@@ -0,0 +1,175 @@
+//! # Identity
+//!
+//! This module contains the client through which actors validate and obtain tokens from
+//! the identity provider (IdP) configured on the currently bound `wascc:identity`
+//! capability provider. Key discovery, signature verification and the OAuth 2.0 token
+//! exchanges are performed by the provider, so actors deal only in tokens and claims
+//!
+//! ```
+//! extern crate wascc_actor as actor;
+//! use actor::prelude::*;
+//!
+//! fn authorize(bearer_token: &str) -> HandlerResult<String> {
+//!     let claims = identity::default().validate(bearer_token)?;
+//!     if !claims.has_scope("orders:write") {
+//!         return Err("token lacks the orders:write scope".into());
+//!     }
+//!     Ok(claims.subject().unwrap_or_default().to_string())
+//! }
+//!
+//! fn inventory_token() -> HandlerResult<String> {
+//!     Ok(identity::default().client_credentials("inventory:read")?.access_token)
+//! }
+//! ```
+
+use serde_derive::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+use wapc_guest::host_call;
+use wascc_codec::{deserialize, serialize};
+
+pub use crate::claims::Claims;
+use crate::HandlerResult;
+
+/// The reserved capability ID for identity functionality
+pub const CAPID_IDENTITY: &str = "wascc:identity";
+
+/// Guest sends a `ValidateTokenRequest` to the capability provider, receives a
+/// `ValidateTokenResponse` back
+pub const OP_VALIDATE_TOKEN: &str = "ValidateToken";
+/// Guest sends a `ClientCredentialsRequest` to the capability provider, receives a
+/// `TokenResponse` back
+pub const OP_CLIENT_CREDENTIALS: &str = "ClientCredentials";
+/// Guest sends a `RefreshTokenRequest` to the capability provider, receives a
+/// `TokenResponse` back
+pub const OP_REFRESH_TOKEN: &str = "RefreshToken";
+
+/// A request to validate an access or ID token
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ValidateTokenRequest {
+    /// The token
+    pub token: String,
+}
+
+/// The outcome of a `ValidateTokenRequest`
+#[derive(Debug, Clone, PartialEq, Default, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ValidateTokenResponse {
+    /// Indicates whether the token is valid: correctly signed by the IdP, unexpired, and
+    /// intended for the configured audience
+    pub valid: bool,
+    /// The token's claims, if it is valid
+    #[serde(default)]
+    pub claims: Map<String, Value>,
+    /// The reason the token is invalid, if it is not
+    #[serde(default)]
+    pub error: String,
+}
+
+/// A request for a token using the client credentials configured on the provider
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ClientCredentialsRequest {
+    /// The space-separated scopes requested
+    pub scope: String,
+}
+
+/// A request for a new access token in exchange for a refresh token
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RefreshTokenRequest {
+    /// The refresh token
+    pub refresh_token: String,
+}
+
+/// Tokens issued by the IdP
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TokenResponse {
+    /// The access token
+    pub access_token: String,
+    /// The type of the access token, usually `Bearer`
+    pub token_type: String,
+    /// The number of seconds until the access token expires
+    pub expires_in_s: u64,
+    /// A refresh token, if the IdP issued one
+    #[serde(default)]
+    pub refresh_token: Option<String>,
+    /// An ID token, if the IdP issued one
+    #[serde(default)]
+    pub id_token: Option<String>,
+    /// The space-separated scopes granted, if they differ from those requested
+    #[serde(default)]
+    pub scope: Option<String>,
+}
+
+/// An abstraction around a host runtime capability for an identity provider
+pub struct IdentityHostBinding {
+    binding: String,
+}
+
+impl Default for IdentityHostBinding {
+    fn default() -> Self {
+        IdentityHostBinding {
+            binding: "default".to_string(),
+        }
+    }
+}
+
+/// Creates a named host binding for the identity capability
+pub fn host(binding: &str) -> IdentityHostBinding {
+    IdentityHostBinding {
+        binding: binding.to_string(),
+    }
+}
+
+/// Creates the default host binding for the identity capability
+pub fn default() -> IdentityHostBinding {
+    IdentityHostBinding::default()
+}
+
+impl IdentityHostBinding {
+    /// Validates a token, returning its claims. Invalid tokens produce an error describing
+    /// why they were rejected
+    pub fn validate(&self, token: &str) -> HandlerResult<Claims> {
+        let cmd = ValidateTokenRequest {
+            token: token.to_string(),
+        };
+        let response = host_call(
+            &self.binding,
+            CAPID_IDENTITY,
+            OP_VALIDATE_TOKEN,
+            &serialize(cmd)?,
+        )
+        .map_err(|e| e.into())
+        .and_then(|v| deserialize::<ValidateTokenResponse>(v.as_ref()))?;
+        if response.valid {
+            Ok(Claims::from(response.claims))
+        } else {
+            Err(format!("Token is invalid: {}", response.error).into())
+        }
+    }
+
+    /// Obtains an access token for the provider's own client credentials, with the given
+    /// space-separated scopes
+    pub fn client_credentials(&self, scope: &str) -> HandlerResult<TokenResponse> {
+        let cmd = ClientCredentialsRequest {
+            scope: scope.to_string(),
+        };
+        self.token(OP_CLIENT_CREDENTIALS, &serialize(cmd)?)
+    }
+
+    /// Exchanges a refresh token for a new access token
+    pub fn refresh(&self, refresh_token: &str) -> HandlerResult<TokenResponse> {
+        let cmd = RefreshTokenRequest {
+            refresh_token: refresh_token.to_string(),
+        };
+        self.token(OP_REFRESH_TOKEN, &serialize(cmd)?)
+    }
+
+    fn token(&self, operation: &str, payload: &[u8]) -> HandlerResult<TokenResponse> {
+        host_call(&self.binding, CAPID_IDENTITY, operation, payload)
+            .map_err(|e| e.into())
+            .and_then(|v| deserialize::<TokenResponse>(v.as_ref()))
+    }
+}
@@ -0,0 +1,268 @@
+//! # Inference
+//!
+//! This module contains the client through which actors run machine learning models held
+//! by the currently bound `wascc:inference` capability provider, so model weights and
+//! inference runtimes stay on the host rather than inside the WebAssembly module. Inputs
+//! and outputs are tensors: a flat buffer of little-endian values with an element type and
+//! a shape
+//!
+//! ```
+//! extern crate wascc_actor as actor;
+//! use actor::prelude::*;
+//! use actor::inference::Tensor;
+//!
+//! fn classify(pixels: &[f32]) -> HandlerResult<usize> {
+//!     let input = Tensor::from_f32(&[1, 28, 28], pixels)?;
+//!     let scores = inference::default().predict("digits", input)?.to_f32()?;
+//!     Ok(scores
+//!         .iter()
+//!         .enumerate()
+//!         .fold((0, f32::MIN), |best, (i, &s)| if s > best.1 { (i, s) } else { best })
+//!         .0)
+//! }
+//! ```
+
+use serde_derive::{Deserialize, Serialize};
+use std::convert::TryInto;
+use wapc_guest::host_call;
+use wascc_codec::{deserialize, serialize};
+
+use crate::HandlerResult;
+
+/// The reserved capability ID for inference functionality
+pub const CAPID_INFERENCE: &str = "wascc:inference";
+
+/// Guest sends a `PredictRequest` to the capability provider, receives a `PredictResponse` back
+pub const OP_PREDICT: &str = "Predict";
+/// Guest sends a `ModelInfoRequest` to the capability provider, receives a `ModelInfo` back
+pub const OP_MODEL_INFO: &str = "ModelInfo";
+
+/// The type of a tensor's elements
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum TensorType {
+    /// 8-bit unsigned integers
+    U8,
+    /// 32-bit signed integers
+    I32,
+    /// 64-bit signed integers
+    I64,
+    /// 32-bit floating point numbers
+    F32,
+    /// 64-bit floating point numbers
+    F64,
+}
+
+impl TensorType {
+    /// The size of an element in bytes
+    pub fn element_size(&self) -> usize {
+        match self {
+            TensorType::U8 => 1,
+            TensorType::I32 | TensorType::F32 => 4,
+            TensorType::I64 | TensorType::F64 => 8,
+        }
+    }
+}
+
+/// A tensor: a buffer of little-endian elements of a single type, laid out in row-major
+/// order according to its shape
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Tensor {
+    /// The type of the elements
+    pub dtype: TensorType,
+    /// The size of each dimension
+    pub shape: Vec<u64>,
+    /// The elements
+    #[serde(with = "serde_bytes")]
+    pub data: Vec<u8>,
+}
+
+macro_rules! tensor_conversions(
+    { $($from:ident, $to:ident => $t:ty, $dtype:ident);* } => {
+        impl Tensor {
+            $(
+                #[doc = concat!("Creates a `", stringify!($t), "` tensor with the given shape")]
+                pub fn $from(shape: &[u64], values: &[$t]) -> HandlerResult<Tensor> {
+                    let data = values.iter().flat_map(|v| v.to_le_bytes().to_vec()).collect();
+                    Tensor::new(TensorType::$dtype, shape, data)
+                }
+
+                #[doc = concat!("The elements of a `", stringify!($t), "` tensor")]
+                pub fn $to(&self) -> HandlerResult<Vec<$t>> {
+                    self.expect(TensorType::$dtype)?;
+                    Tensor::check_len(self.dtype, &self.shape, self.data.len())?;
+                    Ok(self
+                        .data
+                        .chunks_exact(std::mem::size_of::<$t>())
+                        .map(|c| <$t>::from_le_bytes(c.try_into().unwrap()))
+                        .collect())
+                }
+            )*
+        }
+    };
+);
+
+tensor_conversions! {
+    from_u8, to_u8 => u8, U8;
+    from_i32, to_i32 => i32, I32;
+    from_i64, to_i64 => i64, I64;
+    from_f32, to_f32 => f32, F32;
+    from_f64, to_f64 => f64, F64
+}
+
+impl Tensor {
+    /// Creates a tensor from raw little-endian element data, checking that the data's
+    /// length matches the shape
+    pub fn new(dtype: TensorType, shape: &[u64], data: Vec<u8>) -> HandlerResult<Tensor> {
+        Tensor::check_len(dtype, shape, data.len())?;
+        Ok(Tensor {
+            dtype,
+            shape: shape.to_vec(),
+            data,
+        })
+    }
+
+    // Checks that a shape (which may come from a caller or a provider) describes exactly
+    // `len` bytes of elements, without overflowing
+    fn check_len(dtype: TensorType, shape: &[u64], len: usize) -> HandlerResult<()> {
+        let elements = shape
+            .iter()
+            .try_fold(1u64, |n, &d| n.checked_mul(d))
+            .ok_or_else(|| format!("Tensor shape {:?} is too large", shape))?;
+        let size = elements
+            .checked_mul(dtype.element_size() as u64)
+            .ok_or_else(|| format!("Tensor shape {:?} is too large", shape))?;
+        if size != len as u64 {
+            return Err(format!(
+                "Tensor of shape {:?} needs {} {:?} elements, got {} bytes",
+                shape, elements, dtype, len
+            )
+            .into());
+        }
+        Ok(())
+    }
+
+    fn expect(&self, dtype: TensorType) -> HandlerResult<()> {
+        if self.dtype == dtype {
+            Ok(())
+        } else {
+            Err(format!("Expected a {:?} tensor, got {:?}", dtype, self.dtype).into())
+        }
+    }
+}
+
+/// A request to run a model
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PredictRequest {
+    /// The name of the model
+    pub model: String,
+    /// The input tensors, in the order of the model's inputs
+    pub inputs: Vec<Tensor>,
+}
+
+/// The outputs of a model run
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PredictResponse {
+    /// The output tensors, in the order of the model's outputs
+    pub outputs: Vec<Tensor>,
+}
+
+/// A request for a model's description
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ModelInfoRequest {
+    /// The name of the model
+    pub model: String,
+}
+
+/// The description of one of a model's inputs or outputs
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TensorSpec {
+    /// The name of the input or output
+    pub name: String,
+    /// The type of its elements
+    pub dtype: TensorType,
+    /// The size of each dimension, with -1 for dimensions of any size (such as the batch)
+    pub shape: Vec<i64>,
+}
+
+/// The description of a model
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ModelInfo {
+    /// The name of the model
+    pub name: String,
+    /// The version of the model loaded by the provider
+    #[serde(default)]
+    pub version: String,
+    /// The model's inputs
+    pub inputs: Vec<TensorSpec>,
+    /// The model's outputs
+    pub outputs: Vec<TensorSpec>,
+}
+
+/// An abstraction around a host runtime capability for model inference
+pub struct InferenceHostBinding {
+    binding: String,
+}
+
+impl Default for InferenceHostBinding {
+    fn default() -> Self {
+        InferenceHostBinding {
+            binding: "default".to_string(),
+        }
+    }
+}
+
+/// Creates a named host binding for the inference capability
+pub fn host(binding: &str) -> InferenceHostBinding {
+    InferenceHostBinding {
+        binding: binding.to_string(),
+    }
+}
+
+/// Creates the default host binding for the inference capability
+pub fn default() -> InferenceHostBinding {
+    InferenceHostBinding::default()
+}
+
+impl InferenceHostBinding {
+    /// Runs a model with a single input, returning its first output
+    pub fn predict(&self, model: &str, input: Tensor) -> HandlerResult<Tensor> {
+        self.predict_many(model, vec![input])?
+            .into_iter()
+            .next()
+            .ok_or_else(|| format!("Model {} produced no outputs", model).into())
+    }
+
+    /// Runs a model with several inputs, returning all of its outputs
+    pub fn predict_many(&self, model: &str, inputs: Vec<Tensor>) -> HandlerResult<Vec<Tensor>> {
+        let cmd = PredictRequest {
+            model: model.to_string(),
+            inputs,
+        };
+        host_call(&self.binding, CAPID_INFERENCE, OP_PREDICT, &serialize(cmd)?)
+            .map_err(|e| e.into())
+            .and_then(|v| deserialize::<PredictResponse>(v.as_ref()))
+            .map(|r| r.outputs)
+    }
+
+    /// Describes a model's inputs and outputs
+    pub fn model_info(&self, model: &str) -> HandlerResult<ModelInfo> {
+        let cmd = ModelInfoRequest {
+            model: model.to_string(),
+        };
+        host_call(
+            &self.binding,
+            CAPID_INFERENCE,
+            OP_MODEL_INFO,
+            &serialize(cmd)?,
+        )
+        .map_err(|e| e.into())
+        .and_then(|v| deserialize::<ModelInfo>(v.as_ref()))
+    }
+}
@@ -47,12 +47,22 @@ macro_rules! actor_handlers(
         wapc_handler!(handle_wapc);
         fn handle_wapc(operation: &str, msg: &[u8]) -> CallResult {
             $crate::logger::ensure_logger();
-            match operation {
-                $( $key => $user_handler(deserialize(msg)?)
+            $crate::logger::ensure_panic_hook();
+            $crate::messaging::capture_correlation_id(operation, msg);
+            $crate::logger::begin_invocation(
+                env!("CARGO_PKG_NAME"),
+                env!("CARGO_PKG_VERSION"),
+                operation,
+            );
+            let result = match operation {
+                $( $key => deserialize(msg)
+                            .and_then(|m| $user_handler(m))
                             .and_then(|r| serialize(r))
                             .map_err(|e| e.into()), )*
                 _ => Err("bad dispatch".into())
-            }
+            };
+            $crate::logger::flush();
+            result
         }
 
      };
@@ -63,13 +73,32 @@ pub fn println(msg: &str) {
     console_log(msg)
 }
 
+pub mod actors;
+pub mod cache;
+pub mod claims;
+pub mod config;
+pub mod crypto;
+pub mod device;
 pub mod errors;
 pub mod events;
 pub mod extras;
+pub mod flags;
+pub mod graphdb;
+pub mod grpc;
+pub mod http;
 pub mod http_client;
+pub mod identity;
+pub mod inference;
 pub mod keyvalue;
 pub mod logger;
+pub mod mail;
 pub mod messaging;
+pub mod notify;
 pub mod objectstore;
 pub mod prelude;
+pub mod scheduler;
+pub mod secrets;
+pub mod sql;
+pub mod telemetry;
 pub mod untyped;
+pub mod ws;
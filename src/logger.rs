@@ -1,181 +1,834 @@
+#[cfg(feature = "tracing")]
+pub mod subscriber;
+
+use crate::extras::Stopwatch;
 use crate::HandlerResult;
-use log::{Metadata, Record};
-use std::sync::{Arc, RwLock};
+use log::{Level, LevelFilter, Metadata, Record};
+use serde_derive::{Deserialize, Serialize};
+use std::any::Any;
+use std::collections::{HashMap, HashSet};
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{Arc, Once, RwLock, RwLockReadGuard, RwLockWriteGuard};
 use wapc_guest::host_call;
 use wascc_codec::logging::*;
-use wascc_codec::serialize;
+use wascc_codec::{deserialize, serialize};
 
 /// The reserved capability ID for the logging functionality
 pub const CAPID_LOGGING: &str = "wascc:logging";
 
-const ERROR: u32 = 1;
-const WARN: u32 = 2;
-const INFO: u32 = 3;
-const DEBUG: u32 = 4;
-const TRACE: u32 = 5;
+/// Guest sends an empty payload to the capability provider, receives a `LogLevelConfig` back
+pub const OP_QUERY_LOG_LEVEL: &str = "QueryLogLevel";
+/// Guest sends a batch of log entries to the capability provider, receives nothing back
+pub const OP_LOG_BATCH: &str = "WriteLogBatch";
+
+#[doc(hidden)]
+pub const ERROR: u32 = 1;
+#[doc(hidden)]
+pub const WARN: u32 = 2;
+#[doc(hidden)]
+pub const INFO: u32 = 3;
+#[doc(hidden)]
+pub const DEBUG: u32 = 4;
+#[doc(hidden)]
+pub const TRACE: u32 = 5;
+
+/// Wire-compatible with the codec's `WriteLogRequest`, with the addition of structured
+/// fields. Providers unaware of the extension will ignore the fields and log the body
+#[derive(Debug, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct StructuredLogRequest {
+    level: u32,
+    body: String,
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    fields: HashMap<String, String>,
+}
+
+#[derive(Debug, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct LogBatch {
+    entries: Vec<StructuredLogRequest>,
+}
+
+#[derive(Default)]
+struct LogBuffer {
+    max_entries: Option<usize>,
+    entries: Vec<(String, StructuredLogRequest)>,
+}
 
 lazy_static! {
-    static ref CURRENT_BINDING: Arc<RwLock<String>> =
-        { Arc::new(RwLock::new("default".to_string())) };
+    static ref CURRENT_BINDING: Arc<RwLock<String>> = Arc::new(RwLock::new("default".to_string()));
+    static ref FILTER: RwLock<LogFilter> = RwLock::new(LogFilter::default());
+    static ref FORMAT: RwLock<LogFormat> = RwLock::new(LogFormat::Text);
+    static ref INVOCATION_ID: RwLock<Option<String>> = RwLock::new(None);
+    static ref SPANS: RwLock<Vec<String>> = RwLock::new(Vec::new());
+    static ref SAMPLER: RwLock<Sampler> = RwLock::new(Sampler::default());
+    static ref BUFFER: RwLock<LogBuffer> = RwLock::new(LogBuffer::default());
+    static ref METADATA: RwLock<Vec<(&'static str, String)>> = RwLock::new(Vec::new());
+    static ref NO_BATCH_BINDINGS: RwLock<HashSet<String>> = RwLock::new(HashSet::new());
 }
 
-static LOGGER: AutomaticLoggerHostBinding = AutomaticLoggerHostBinding {};
+/// The target of the entries written when spans are entered and exited, which can be used
+/// to filter them with a `LogFilter`
+pub const SPAN_TARGET: &str = "wascc_actor::span";
+/// The log field holding the path of the spans (outermost first, separated by `/`) that
+/// were active when an entry was written
+pub const FIELD_SPAN: &str = "span";
 
-#[allow(dead_code)]
+static INVOCATION_COUNT: AtomicU64 = AtomicU64::new(0);
+static JSON_TIMESTAMPS: AtomicBool = AtomicBool::new(false);
+static ULID_INVOCATION_IDS: AtomicBool = AtomicBool::new(false);
+static PANIC_HOOK: Once = Once::new();
+static HOST_LEVEL: Once = Once::new();
+
+/// The target of the entries written when the actor panics
+pub const PANIC_TARGET: &str = "wascc_actor::panic";
+
+/// Enters a named span, returning a guard that exits the span when dropped. Entering and
+/// exiting are logged at the debug level (with the time spent in the span), and every entry
+/// written while the span is active carries the path of the active spans in the `span`
+/// field, giving lightweight tracing through the logging capability
+pub fn span(name: &str) -> SpanGuard {
+    let depth = {
+        let mut spans = write_lock(&SPANS);
+        spans.push(name.to_string());
+        spans.len()
+    };
+    span_event(&format!("enter {}", name), HashMap::new());
+    SpanGuard {
+        name: name.to_string(),
+        depth,
+        stopwatch: Stopwatch::start().ok(),
+    }
+}
+
+/// An active span, created by `span`. The span is exited when the guard is dropped
+pub struct SpanGuard {
+    name: String,
+    depth: usize,
+    stopwatch: Option<Stopwatch>,
+}
+
+impl Drop for SpanGuard {
+    fn drop(&mut self) {
+        let mut fields = HashMap::new();
+        if let Some(elapsed) = self.stopwatch.as_ref().and_then(|s| s.elapsed_ms().ok()) {
+            fields.insert("elapsed_ms".to_string(), elapsed.to_string());
+        }
+        span_event(&format!("exit {}", self.name), fields);
+        write_lock(&SPANS).truncate(self.depth - 1);
+    }
+}
+
+fn span_event(message: &str, fields: HashMap<String, String>) {
+    if Level::Debug <= read_lock(&FILTER).level_for(SPAN_TARGET) {
+        let _ = LOGGER.emit(DEBUG, SPAN_TARGET, message, fields);
+    }
+}
+
+/// The log field holding the ID of the handler invocation that wrote an entry
+pub const FIELD_INVOCATION_ID: &str = "invocation_id";
+/// The log field holding the correlation ID of the message being handled, if any
+pub const FIELD_CORRELATION_ID: &str = "correlation_id";
+/// The log field holding the name of the actor's crate
+pub const FIELD_MODULE: &str = "module";
+/// The log field holding the version of the actor's crate
+pub const FIELD_MODULE_VERSION: &str = "module_version";
+/// The log field holding the operation being handled
+pub const FIELD_OPERATION: &str = "operation";
+
+/// Records the actor's crate name and version and the operation being handled, all of which
+/// are attached to every entry written during the invocation so that hosts running many
+/// actors can attribute each entry. Called by the `actor_handlers!` macro, which captures
+/// the name and version from the actor's own Cargo manifest
 #[doc(hidden)]
-pub fn ensure_logger() {
-    match log::set_logger(&LOGGER) {
-        Ok(_) => {}
-        Err(_) => {}
+pub fn begin_invocation(module: &str, version: &str, operation: &str) {
+    *write_lock(&METADATA) = vec![
+        (FIELD_MODULE, module.to_string()),
+        (FIELD_MODULE_VERSION, version.to_string()),
+        (FIELD_OPERATION, operation.to_string()),
+    ];
+    *write_lock(&INVOCATION_ID) = None;
+    write_lock(&SPANS).clear();
+    write_lock(&SAMPLER).ticked = false;
+}
+
+/// Returns the ID of the handler invocation currently in progress. Every log entry written
+/// during the invocation carries this ID (and the inbound correlation ID, if any) as
+/// structured fields. By default the ID is a counter local to this instance of the actor,
+/// so it is only unique within the instance: several instances of the same actor (or one
+/// that has been restarted) reuse the same IDs. Use `set_ulid_invocation_ids` for IDs that
+/// are unique across instances
+pub fn invocation_id() -> String {
+    if let Some(ref id) = *read_lock(&INVOCATION_ID) {
+        return id.to_string();
     }
-    log::set_max_level(log::LevelFilter::Trace);
+    let ulid = if ULID_INVOCATION_IDS.load(Ordering::Relaxed) {
+        crate::extras::default().get_ulid().ok()
+    } else {
+        None
+    };
+    let id = ulid.unwrap_or_else(|| INVOCATION_COUNT.fetch_add(1, Ordering::Relaxed).to_string());
+    *write_lock(&INVOCATION_ID) = Some(id.clone());
+    id
 }
 
-/// A host binding for the wascc:logging capability
-pub struct AutomaticLoggerHostBinding {}
+/// Makes invocation IDs ULIDs generated by the host (`wascc:extras`), which are unique across
+/// actor instances. This costs extra host calls the first time each invocation's ID is
+/// needed, and falls back to the local counter on hosts that cannot generate one, so it is
+/// off by default
+pub fn set_ulid_invocation_ids(enabled: bool) {
+    ULID_INVOCATION_IDS.store(enabled, Ordering::Relaxed);
+}
 
-impl Default for AutomaticLoggerHostBinding {
+/// The format of the body of each log entry
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum LogFormat {
+    /// The body is the plain log message
+    Text,
+    /// The body is a JSON object holding the level, target, message and any structured
+    /// fields, for hosts that ship logs to aggregators. The host stamps each entry with the
+    /// time it received it; see `set_json_timestamps` to have the actor add its own
+    Json,
+}
+
+/// Adds a `timestamp` (milliseconds since the epoch) to the body of JSON entries. The time
+/// is read from the host clock (`wascc:extras`), which costs an extra host call per entry
+/// and is skipped on hosts without a clock, so this is off by default
+pub fn set_json_timestamps(enabled: bool) {
+    JSON_TIMESTAMPS.store(enabled, Ordering::Relaxed);
+}
+
+/// Sets the format of entries written by the `log` macros and by bindings that have not
+/// been given a format of their own with `with_format`
+pub fn set_format(format: LogFormat) {
+    *write_lock(&FORMAT) = format;
+}
+
+/// Determines which log records are written, by level, according to their target (usually
+/// the module path of the code that logged them). The most specific matching target prefix
+/// applies; records whose target matches no prefix use the default level. This lets noisy
+/// dependencies compiled into an actor be silenced without losing the actor's own logs
+///
+/// ```
+/// # use wascc_actor::logger::LogFilter;
+/// # use log::LevelFilter;
+/// let filter = LogFilter::new(LevelFilter::Debug)
+///     .target("hyper", LevelFilter::Warn)
+///     .target("hyper::client::pool", LevelFilter::Off);
+/// assert_eq!(filter.level_for("hyper::client"), LevelFilter::Warn);
+/// assert_eq!(filter.level_for("hyper::client::pool"), LevelFilter::Off);
+/// assert_eq!(filter.level_for("hyperactive"), LevelFilter::Debug);
+/// ```
+#[derive(Debug, Clone, PartialEq)]
+pub struct LogFilter {
+    default: LevelFilter,
+    targets: Vec<(String, LevelFilter)>,
+}
+
+impl Default for LogFilter {
     fn default() -> Self {
-        AutomaticLoggerHostBinding {}
+        LogFilter::new(LevelFilter::Trace)
+    }
+}
+
+impl LogFilter {
+    /// Creates a filter that applies the given level to every target
+    pub fn new(default: LevelFilter) -> LogFilter {
+        LogFilter {
+            default,
+            targets: Vec::new(),
+        }
+    }
+
+    /// Applies a level to a target and every target nested beneath it (`prefix::...`)
+    pub fn target(mut self, prefix: &str, level: LevelFilter) -> LogFilter {
+        self.targets.retain(|(p, _)| p != prefix);
+        self.targets.push((prefix.to_string(), level));
+        self
+    }
+
+    /// The level that applies to the given target
+    pub fn level_for(&self, target: &str) -> LevelFilter {
+        self.targets
+            .iter()
+            .filter(|(prefix, _)| {
+                target == prefix
+                    || (target.starts_with(prefix.as_str())
+                        && target[prefix.len()..].starts_with("::"))
+            })
+            .max_by_key(|(prefix, _)| prefix.len())
+            .map_or(self.default, |(_, level)| *level)
+    }
+
+    /// The most verbose level enabled for any target
+    pub fn max_level(&self) -> LevelFilter {
+        self.targets
+            .iter()
+            .map(|(_, level)| *level)
+            .fold(self.default, |a, b| a.max(b))
+    }
+}
+
+/// The log levels the host wants the actor to write, as configured for its logging binding.
+/// Levels use the same numbering as log entries: `OFF`=0, `ERROR`=1, `WARN`=2, `INFO`=3,
+/// `DEBUG`=4, `TRACE`=5
+#[derive(Debug, Clone, Default, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct LogLevelConfig {
+    /// The level applied to every target without a level of its own
+    pub level: u32,
+    /// Levels for specific target prefixes
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    pub targets: HashMap<String, u32>,
+}
+
+impl LogLevelConfig {
+    /// Converts the configuration into a filter that can be passed to `set_filter`
+    pub fn to_filter(&self) -> LogFilter {
+        self.targets.iter().fold(
+            LogFilter::new(level_filter(self.level)),
+            |filter, (prefix, level)| filter.target(prefix, level_filter(*level)),
+        )
+    }
+}
+
+fn level_filter(level: u32) -> LevelFilter {
+    match level {
+        0 => LevelFilter::Off,
+        ERROR => LevelFilter::Error,
+        WARN => LevelFilter::Warn,
+        INFO => LevelFilter::Info,
+        DEBUG => LevelFilter::Debug,
+        _ => LevelFilter::Trace,
+    }
+}
+
+/// Asks the provider bound to the `log` macros' binding for the configured log levels and
+/// applies them with `set_filter`, so that records the host doesn't want are discarded
+/// without a host call. This happens automatically the first time a handler runs; if the
+/// provider does not support the query, the filter is left unchanged
+pub fn configure_from_host() -> HandlerResult<LogFilter> {
+    let binding = read_lock(&CURRENT_BINDING).to_string();
+    let filter = host_call(&binding, CAPID_LOGGING, OP_QUERY_LOG_LEVEL, &[])
+        .map_err(|e| e.into())
+        .and_then(|v| deserialize::<LogLevelConfig>(v.as_ref()))?
+        .to_filter();
+    set_filter(filter.clone());
+    Ok(filter)
+}
+
+/// Replaces the filter applied to records written through the `log` macros, `ctx_log!` and
+/// the `log` methods of any binding. Records that are filtered out never cross the host
+/// boundary
+pub fn set_filter(filter: LogFilter) {
+    log::set_max_level(filter.max_level());
+    *write_lock(&FILTER) = filter;
+}
+
+/// Limits the number of entries written, so that a hot handler can keep verbose logging
+/// enabled without making a host call for every record. Entries can be capped to a number
+/// of identical (same level, target and message) entries per second, and individual levels
+/// can be sampled so that only one in every `k` of their entries is written. Error-level
+/// entries and panics are never sampled
+///
+/// Because the actor has no clock of its own, the current second is read from the host
+/// (`wascc:extras`) at the first capped entry of each handler invocation, and again after 1,
+/// 2, 4, 8... entries have been discarded, so that the window still advances during a long
+/// invocation. On hosts without a clock the cap on identical entries is disabled
+///
+/// ```
+/// # use wascc_actor::logger::LogSampling;
+/// # use log::Level;
+/// let sampling = LogSampling::new()
+///     .max_identical_per_second(5)
+///     .one_in(Level::Debug, 10)
+///     .one_in(Level::Trace, 100);
+/// ```
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct LogSampling {
+    max_identical: Option<u32>,
+    one_in: HashMap<u32, u64>,
+}
+
+impl LogSampling {
+    /// Creates a sampling configuration that writes every entry
+    pub fn new() -> LogSampling {
+        LogSampling::default()
+    }
+
+    /// Writes at most `max` identical entries per second, discarding the rest
+    pub fn max_identical_per_second(mut self, max: u32) -> LogSampling {
+        self.max_identical = Some(max);
+        self
+    }
+
+    /// Writes only one in every `k` entries at the given level
+    pub fn one_in(mut self, level: Level, k: u64) -> LogSampling {
+        self.one_in.insert(level as u32, k.max(1));
+        self
+    }
+}
+
+#[derive(Default)]
+struct Sampler {
+    config: LogSampling,
+    window: Option<u64>,
+    ticked: bool,
+    no_clock: bool,
+    discarded: u64,
+    identical: HashMap<String, u32>,
+    seen: HashMap<u32, u64>,
+}
+
+impl Sampler {
+    // `is_multiple_of` would need a newer compiler than the crate otherwise requires
+    #[allow(clippy::manual_is_multiple_of)]
+    fn admit(&mut self, level: u32, target: &str, message: &str) -> bool {
+        if level <= ERROR {
+            return true;
+        }
+        if let Some(&k) = self.config.one_in.get(&level) {
+            let seen = self.seen.entry(level).or_insert(0);
+            let admit = *seen % k == 0;
+            *seen += 1;
+            if !admit {
+                return false;
+            }
+        }
+        if let Some(max) = self.config.max_identical {
+            if self.no_clock || (!self.ticked && !self.tick()) {
+                return true;
+            }
+            let key = format!("{}|{}|{}", level, target, message);
+            if self.identical.get(&key).is_some_and(|&count| count >= max) {
+                self.discarded += 1;
+                if !self.discarded.is_power_of_two() {
+                    return false;
+                }
+                if !self.tick() {
+                    return true;
+                }
+                if self.identical.contains_key(&key) {
+                    return false;
+                }
+            }
+            *self.identical.entry(key).or_insert(0) += 1;
+        }
+        true
+    }
+
+    // Reads the host clock, starting a new window if the second has changed. Returns false
+    // (and disables the cap on identical entries) if the host has no clock
+    fn tick(&mut self) -> bool {
+        match crate::extras::default().get_time() {
+            Ok(time) => {
+                self.ticked = true;
+                let second = time.epoch_ms / 1000;
+                if self.window != Some(second) {
+                    self.window = Some(second);
+                    self.discarded = 0;
+                    self.identical.clear();
+                }
+                true
+            }
+            Err(_) => {
+                self.no_clock = true;
+                self.identical.clear();
+                false
+            }
+        }
+    }
+}
+
+/// Replaces the sampling applied to entries written through the `log` macros and the
+/// `log` methods of any binding
+pub fn set_sampling(sampling: LogSampling) {
+    *write_lock(&SAMPLER) = Sampler {
+        config: sampling,
+        ..Sampler::default()
+    };
+}
+
+fn sampled(level: u32, target: &str, message: &str) -> bool {
+    write_lock(&SAMPLER).admit(level, target, message)
+}
+
+/// Enables buffering of log entries. Rather than making a host call for every entry, entries
+/// accumulate in the actor and are sent to the provider in a single batched call once
+/// `max_entries` have been buffered, and whenever a handler completes. Passing `None`
+/// disables buffering, flushing any entries already buffered. Panics flush the buffer before
+/// the module traps. Providers that don't support batches are detected on the first flush,
+/// after which their entries are written one call at a time
+pub fn set_buffering(max_entries: Option<usize>) {
+    write_lock(&BUFFER).max_entries = max_entries.map(|m| m.max(1));
+    if max_entries.is_none() {
+        flush();
+    }
+}
+
+/// Sends any buffered log entries to the host. Called automatically when a handler completes
+pub fn flush() {
+    let entries = std::mem::take(&mut write_lock(&BUFFER).entries);
+    let mut entries = entries.into_iter().peekable();
+    while let Some((binding, entry)) = entries.next() {
+        let mut batch = vec![entry];
+        while let Some((_, entry)) = entries.next_if(|(b, _)| *b == binding) {
+            batch.push(entry);
+        }
+        write_batch(&binding, batch);
+    }
+}
+
+fn write_batch(binding: &str, entries: Vec<StructuredLogRequest>) {
+    let batch = LogBatch { entries };
+    // Once a provider has turned down a batch as an unsupported operation, its entries are
+    // written one by one without trying another batch first
+    let sent = !read_lock(&NO_BATCH_BINDINGS).contains(binding)
+        && match serialize(&batch) {
+            Ok(payload) => match host_call(binding, CAPID_LOGGING, OP_LOG_BATCH, &payload) {
+                Ok(_) => true,
+                Err(e) => {
+                    let e: Box<dyn std::error::Error + Send + Sync> = e.into();
+                    if crate::errors::is_unsupported_operation(e.as_ref()) {
+                        write_lock(&NO_BATCH_BINDINGS).insert(binding.to_string());
+                    }
+                    false
+                }
+            },
+            Err(_) => false,
+        };
+    if !sent {
+        for entry in batch.entries {
+            write_entry(binding, &entry);
+        }
     }
 }
 
-fn set_binding(binding: &str) {
-    *CURRENT_BINDING.write().unwrap() = binding.to_string();
+fn write_entry(binding: &str, entry: &StructuredLogRequest) {
+    if let Ok(payload) = serialize(entry) {
+        let _ = host_call(binding, CAPID_LOGGING, OP_LOG, &payload);
+    }
+}
+
+static LOGGER: AutomaticLoggerHostBinding = AutomaticLoggerHostBinding {
+    binding: None,
+    format: None,
+};
+
+#[allow(dead_code)]
+#[doc(hidden)]
+pub fn ensure_logger() {
+    let _ = log::set_logger(&LOGGER);
+    log::set_max_level(read_lock(&FILTER).max_level());
+    HOST_LEVEL.call_once(|| {
+        let _ = configure_from_host();
+    });
 }
 
-/// Sets the current binding for the logger. Note that because the logger
-/// is statically available in order to support Rust's `log` macros,
-/// the _msot recent binding_ you set is the one that will
-/// be active for your logging macros. You can't maintain two different logger
-/// bindings that send to two different host bindings, you must instead toggle
-/// between them.
+/// Installs a panic hook that writes the panic message and location to the host as an
+/// error-level entry before the module traps, so that actor panics are visible to the host.
+/// Any previously installed hook still runs afterwards. Called by the `actor_handlers!` macro
+#[doc(hidden)]
+pub fn ensure_panic_hook() {
+    PANIC_HOOK.call_once(|| {
+        let previous = std::panic::take_hook();
+        std::panic::set_hook(Box::new(move |info| {
+            let mut fields = HashMap::new();
+            if let Some(location) = info.location() {
+                fields.insert(
+                    "location".to_string(),
+                    format!(
+                        "{}:{}:{}",
+                        location.file(),
+                        location.line(),
+                        location.column()
+                    ),
+                );
+            }
+            let message = format!("panicked: {}", panic_message(info.payload()));
+            let _ = LOGGER.emit(ERROR, PANIC_TARGET, &message, fields);
+            flush();
+            previous(info);
+        }));
+    });
+}
+
+// The logger's state is read and written through these rather than with `unwrap`, so that a
+// panic while one of its locks is held (which poisons the lock) doesn't make the panic hook,
+// or any later logging, panic in turn
+fn read_lock<T>(lock: &RwLock<T>) -> RwLockReadGuard<'_, T> {
+    lock.read().unwrap_or_else(|e| e.into_inner())
+}
+
+fn write_lock<T>(lock: &RwLock<T>) -> RwLockWriteGuard<'_, T> {
+    lock.write().unwrap_or_else(|e| e.into_inner())
+}
+
+fn panic_message(payload: &(dyn Any + Send)) -> &str {
+    if let Some(s) = payload.downcast_ref::<&str>() {
+        s
+    } else if let Some(s) = payload.downcast_ref::<String>() {
+        s
+    } else {
+        "Box<dyn Any>"
+    }
+}
+
+/// A host binding for the wascc:logging capability. Which binding a logger writes to depends
+/// on how it was created:
+///
+/// * `logger::host(name)` writes to the binding with the given name, and `logger::default()`
+///   writes to the binding named `default`. Neither is affected by `set_macro_binding`
+/// * `AutomaticLoggerHostBinding::default()`, which is what the `log` macros and `ctx_log!`
+///   use, writes to the binding most recently chosen with `set_macro_binding` (the binding
+///   named `default` until one is chosen)
+#[derive(Default)]
+pub struct AutomaticLoggerHostBinding {
+    binding: Option<String>,
+    format: Option<LogFormat>,
+}
+
+/// Sets the binding to which the `log` macros write. Because the logger is statically
+/// available in order to support Rust's `log` macros, only one binding can be active for
+/// the macros at a time. Use `host` to write to other bindings explicitly
+pub fn set_macro_binding(binding: &str) {
+    *write_lock(&CURRENT_BINDING) = binding.to_string();
+}
+
+/// Creates a logger that writes to the given named binding, independently of the binding
+/// used by the `log` macros
 pub fn host(binding: &str) -> AutomaticLoggerHostBinding {
-    set_binding(binding);
-    AutomaticLoggerHostBinding {}
+    AutomaticLoggerHostBinding {
+        binding: Some(binding.to_string()),
+        format: None,
+    }
 }
 
-/// Resets the current logger binding name to the default.
+/// Creates a logger that always writes to the binding named `default`, even after the `log`
+/// macros have been pointed at another binding with `set_macro_binding`. Use
+/// `AutomaticLoggerHostBinding::default()` for a logger that writes wherever the macros do
 pub fn default() -> AutomaticLoggerHostBinding {
-    set_binding("default");
-    AutomaticLoggerHostBinding {}
+    host("default")
 }
 
 impl log::Log for AutomaticLoggerHostBinding {
-    fn enabled(&self, _metadata: &Metadata) -> bool {
-        true
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        metadata.level() <= read_lock(&FILTER).level_for(metadata.target())
     }
 
     fn log(&self, record: &Record) {
         if self.enabled(record.metadata()) {
-            let l = WriteLogRequest {
-                level: record.level() as _,
-                body: format!("{}", record.args()),
-            };
-            self._log(l)
+            let message = format!("{}", record.args());
+            if sampled(record.level() as _, record.target(), &message) {
+                let _ = self.emit(
+                    record.level() as _,
+                    record.target(),
+                    &message,
+                    HashMap::new(),
+                );
+            }
         }
     }
 
-    fn flush(&self) {}
+    fn flush(&self) {
+        flush();
+    }
 }
 
 impl AutomaticLoggerHostBinding {
-    fn _log(&self, req: WriteLogRequest) {
-        let _ = host_call(
-            &CURRENT_BINDING.read().unwrap(),
-            CAPID_LOGGING,
-            OP_LOG,
-            &serialize(req).unwrap(),
-        );
+    /// Sets the format of the entries written through this binding, overriding the format
+    /// chosen with `set_format`
+    pub fn with_format(mut self, format: LogFormat) -> Self {
+        self.format = Some(format);
+        self
     }
 
-    /// Write a log entry on the host
-    pub fn log(&self, level: u32, body: &str) -> HandlerResult<()> {
-        let l = WriteLogRequest {
-            level: level,
-            body: body.to_string(),
+    fn emit(
+        &self,
+        level: u32,
+        target: &str,
+        message: &str,
+        mut fields: HashMap<String, String>,
+    ) -> HandlerResult<()> {
+        fields
+            .entry(FIELD_INVOCATION_ID.to_string())
+            .or_insert_with(invocation_id);
+        for (name, value) in read_lock(&METADATA).iter() {
+            fields
+                .entry(name.to_string())
+                .or_insert_with(|| value.to_string());
+        }
+        if let Some(id) = crate::messaging::correlation_id() {
+            fields.entry(FIELD_CORRELATION_ID.to_string()).or_insert(id);
+        }
+        {
+            let spans = read_lock(&SPANS);
+            if !spans.is_empty() {
+                fields
+                    .entry(FIELD_SPAN.to_string())
+                    .or_insert_with(|| spans.join("/"));
+            }
+        }
+        let format = self.format.unwrap_or_else(|| *read_lock(&FORMAT));
+        let body = match format {
+            LogFormat::Text => message.to_string(),
+            LogFormat::Json => json_body(level, target, message, &fields),
         };
-        let _ = host_call(
-            &CURRENT_BINDING.read().unwrap(),
-            CAPID_LOGGING,
-            OP_LOG,
-            &serialize(l)?,
-        );
+        self.write(StructuredLogRequest {
+            level,
+            body,
+            fields,
+        });
         Ok(())
     }
 
+    fn write(&self, entry: StructuredLogRequest) {
+        let binding = match self.binding {
+            Some(ref binding) => binding.to_string(),
+            None => read_lock(&CURRENT_BINDING).to_string(),
+        };
+        let full = {
+            let mut buffer = write_lock(&BUFFER);
+            match buffer.max_entries {
+                Some(max) => {
+                    buffer.entries.push((binding, entry));
+                    buffer.entries.len() >= max
+                }
+                None => {
+                    drop(buffer);
+                    write_entry(&binding, &entry);
+                    false
+                }
+            }
+        };
+        if full {
+            flush();
+        }
+    }
+
+    /// Write a log entry on the host
+    pub fn log(&self, level: u32, body: &str) -> HandlerResult<()> {
+        self.log_fields(level, body, HashMap::new())
+    }
+
+    /// Write a log entry carrying structured key/value fields, which are serialized alongside
+    /// the body so that log aggregators can index them. You should instead use the
+    /// `ctx_log!` macro
+    pub fn log_fields(
+        &self,
+        level: u32,
+        body: &str,
+        fields: HashMap<String, String>,
+    ) -> HandlerResult<()> {
+        self.log_record(level, "", body, fields)
+    }
+
+    /// Write a log entry with structured fields on behalf of the given target (usually a
+    /// module path), subject to the same filtering and sampling as records written through
+    /// the `log` macros
+    pub fn log_record(
+        &self,
+        level: u32,
+        target: &str,
+        body: &str,
+        fields: HashMap<String, String>,
+    ) -> HandlerResult<()> {
+        if level as usize <= read_lock(&FILTER).level_for(target) as usize
+            && sampled(level, target, body)
+        {
+            self.emit(level, target, body, fields)
+        } else {
+            Ok(())
+        }
+    }
+
     /// Write a log entry at the error level. You should instead use the `error!` macro
     pub fn error(&self, body: &str) -> HandlerResult<()> {
-        let l = WriteLogRequest {
-            level: ERROR,
-            body: body.to_string(),
-        };
-        let _ = host_call(
-            &CURRENT_BINDING.read().unwrap(),
-            CAPID_LOGGING,
-            OP_LOG,
-            &serialize(l)?,
-        );
-        Ok(())
+        self.log(ERROR, body)
     }
 
     /// Write a log entry at the warn level. You should instead use the `warn!` macro
     pub fn warn(&self, body: &str) -> HandlerResult<()> {
-        let l = WriteLogRequest {
-            level: WARN,
-            body: body.to_string(),
-        };
-        let _ = host_call(
-            &CURRENT_BINDING.read().unwrap(),
-            CAPID_LOGGING,
-            OP_LOG,
-            &serialize(l)?,
-        );
-        Ok(())
+        self.log(WARN, body)
     }
 
     /// Write a log entry at the info level. You should instead use the `info!` macro
     pub fn info(&self, body: &str) -> HandlerResult<()> {
-        let l = WriteLogRequest {
-            level: INFO,
-            body: body.to_string(),
-        };
-        let _ = host_call(
-            &CURRENT_BINDING.read().unwrap(),
-            CAPID_LOGGING,
-            OP_LOG,
-            &serialize(l)?,
-        );
-        Ok(())
+        self.log(INFO, body)
     }
 
     /// Write a log entry at the debug level. You should instead use the `debug!` macro
     pub fn debug(&self, body: &str) -> HandlerResult<()> {
-        let l = WriteLogRequest {
-            level: DEBUG,
-            body: body.to_string(),
-        };
-        let _ = host_call(
-            &CURRENT_BINDING.read().unwrap(),
-            CAPID_LOGGING,
-            OP_LOG,
-            &serialize(l)?,
-        );
-        Ok(())
+        self.log(DEBUG, body)
     }
 
     /// Write a log entry at the trace level. You should instead use the `trace!` macro
     pub fn trace(&self, body: &str) -> HandlerResult<()> {
-        let l = WriteLogRequest {
-            level: TRACE,
-            body: body.to_string(),
-        };
-        let _ = host_call(
-            &CURRENT_BINDING.read().unwrap(),
-            CAPID_LOGGING,
-            OP_LOG,
-            &serialize(l)?,
+        self.log(TRACE, body)
+    }
+}
+
+fn json_body(level: u32, target: &str, message: &str, fields: &HashMap<String, String>) -> String {
+    let mut entry = serde_json::Map::new();
+    if JSON_TIMESTAMPS.load(Ordering::Relaxed) {
+        if let Ok(time) = crate::extras::default().get_time() {
+            entry.insert("timestamp".to_string(), time.epoch_ms.into());
+        }
+    }
+    entry.insert("level".to_string(), level_name(level).into());
+    if !target.is_empty() {
+        entry.insert("target".to_string(), target.into());
+    }
+    entry.insert("message".to_string(), message.into());
+    if !fields.is_empty() {
+        entry.insert(
+            "fields".to_string(),
+            serde_json::to_value(fields).unwrap_or_default(),
         );
-        Ok(())
     }
+    serde_json::Value::Object(entry).to_string()
 }
+
+fn level_name(level: u32) -> &'static str {
+    match level {
+        ERROR => "ERROR",
+        WARN => "WARN",
+        INFO => "INFO",
+        DEBUG => "DEBUG",
+        _ => "TRACE",
+    }
+}
+
+/// Writes a log entry with structured key/value fields through the current logger binding.
+/// The first argument is the level (`error`, `warn`, `info`, `debug` or `trace`), followed
+/// by the message and any number of `key = value` fields. Values may be of any type that
+/// implements `Display`. Any other level fails to compile
+///
+/// ```
+/// # #[macro_use] extern crate wascc_actor;
+/// fn place_order(order_id: u64, total: f64) {
+///     ctx_log!(info, "order placed", order_id = order_id, total = total);
+/// }
+/// # fn main() {}
+/// ```
+#[macro_export]
+macro_rules! ctx_log(
+    { error, $($rest:tt)+ } => { $crate::ctx_log!(@log $crate::logger::ERROR, $($rest)+) };
+    { warn, $($rest:tt)+ } => { $crate::ctx_log!(@log $crate::logger::WARN, $($rest)+) };
+    { info, $($rest:tt)+ } => { $crate::ctx_log!(@log $crate::logger::INFO, $($rest)+) };
+    { debug, $($rest:tt)+ } => { $crate::ctx_log!(@log $crate::logger::DEBUG, $($rest)+) };
+    { trace, $($rest:tt)+ } => { $crate::ctx_log!(@log $crate::logger::TRACE, $($rest)+) };
+    { @log $level:expr, $body:expr $(, $key:ident = $value:expr)* $(,)? } => {
+        {
+            #[allow(unused_mut)]
+            let mut fields = ::std::collections::HashMap::new();
+            $( fields.insert(stringify!($key).to_string(), $value.to_string()); )*
+            let _ = $crate::logger::AutomaticLoggerHostBinding::default().log_record(
+                $level,
+                module_path!(),
+                &$body.to_string(),
+                fields,
+            );
+        }
+    };
+);
@@ -0,0 +1,220 @@
+//! # Tracing Bridge
+//!
+//! This module contains a `tracing::Subscriber` that writes events and span lifecycles
+//! through the logging capability, so that actors (and the dependencies compiled into them)
+//! which are instrumented with the `tracing` crate emit to the host rather than being
+//! silently dropped. Requires the `tracing` feature
+
+use super::{read_lock, sampled, span_event, write_lock, FILTER, LOGGER, SPANS};
+use super::{DEBUG, ERROR, INFO, TRACE, WARN};
+use crate::extras::Stopwatch;
+use std::collections::HashMap;
+use std::fmt;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::RwLock;
+use tracing::field::{Field, Visit};
+use tracing::span::{Attributes, Id, Record};
+use tracing::{Event, Level, Metadata, Subscriber};
+
+lazy_static! {
+    static ref ENTERED: RwLock<Vec<u64>> = RwLock::new(Vec::new());
+}
+
+/// A subscriber that writes `tracing` events as log entries, with the event's fields as
+/// structured fields (the `message` field becomes the body). Entering and exiting spans
+/// behaves like `logger::span`, and a span's fields are attached to every event recorded
+/// within it. Records are filtered, sampled, formatted and buffered exactly like those
+/// written through the `log` macros
+#[derive(Default)]
+pub struct HostSubscriber {
+    next_id: AtomicU64,
+    spans: RwLock<HashMap<u64, SpanState>>,
+}
+
+struct SpanState {
+    name: &'static str,
+    fields: HashMap<String, String>,
+    refs: usize,
+    stopwatch: Option<Stopwatch>,
+}
+
+/// Installs a `HostSubscriber` as the global default subscriber. Fails if a global
+/// subscriber has already been installed
+pub fn init() -> crate::HandlerResult<()> {
+    tracing::subscriber::set_global_default(HostSubscriber::new())
+        .map_err(|e| format!("Failed to install tracing subscriber: {}", e).into())
+}
+
+impl HostSubscriber {
+    /// Creates a new subscriber
+    pub fn new() -> HostSubscriber {
+        HostSubscriber::default()
+    }
+
+    fn span_fields(&self) -> HashMap<String, String> {
+        let spans = read_lock(&self.spans);
+        let mut fields = HashMap::new();
+        for id in read_lock(&ENTERED).iter() {
+            if let Some(span) = spans.get(id) {
+                fields.extend(span.fields.iter().map(|(k, v)| (k.clone(), v.clone())));
+            }
+        }
+        fields
+    }
+}
+
+impl Subscriber for HostSubscriber {
+    fn enabled(&self, metadata: &Metadata<'_>) -> bool {
+        log_level(metadata.level()) <= read_lock(&FILTER).level_for(metadata.target())
+    }
+
+    fn new_span(&self, attrs: &Attributes<'_>) -> Id {
+        let id = self.next_id.fetch_add(1, Ordering::Relaxed) + 1;
+        let mut visitor = FieldVisitor::default();
+        attrs.record(&mut visitor);
+        write_lock(&self.spans).insert(
+            id,
+            SpanState {
+                name: attrs.metadata().name(),
+                fields: visitor.fields,
+                refs: 1,
+                stopwatch: None,
+            },
+        );
+        Id::from_u64(id)
+    }
+
+    fn record(&self, span: &Id, values: &Record<'_>) {
+        if let Some(state) = write_lock(&self.spans).get_mut(&span.into_u64()) {
+            let mut visitor = FieldVisitor::default();
+            values.record(&mut visitor);
+            state.fields.extend(visitor.fields);
+        }
+    }
+
+    fn record_follows_from(&self, _span: &Id, _follows: &Id) {}
+
+    fn event(&self, event: &Event<'_>) {
+        let metadata = event.metadata();
+        let mut visitor = FieldVisitor::default();
+        event.record(&mut visitor);
+        let message = visitor
+            .message
+            .unwrap_or_else(|| metadata.name().to_string());
+        let level = level_number(metadata.level());
+        if sampled(level, metadata.target(), &message) {
+            let mut fields = self.span_fields();
+            fields.extend(visitor.fields);
+            let _ = LOGGER.emit(level, metadata.target(), &message, fields);
+        }
+    }
+
+    fn enter(&self, span: &Id) {
+        let name = match write_lock(&self.spans).get_mut(&span.into_u64()) {
+            Some(state) => {
+                state.stopwatch = Stopwatch::start().ok();
+                state.name
+            }
+            None => return,
+        };
+        write_lock(&ENTERED).push(span.into_u64());
+        write_lock(&SPANS).push(name.to_string());
+        span_event(&format!("enter {}", name), self.span_fields());
+    }
+
+    fn exit(&self, span: &Id) {
+        let (name, elapsed) = match read_lock(&self.spans).get(&span.into_u64()) {
+            Some(state) => (
+                state.name,
+                state.stopwatch.as_ref().and_then(|s| s.elapsed_ms().ok()),
+            ),
+            None => return,
+        };
+        let mut fields = self.span_fields();
+        if let Some(elapsed) = elapsed {
+            fields.insert("elapsed_ms".to_string(), elapsed.to_string());
+        }
+        span_event(&format!("exit {}", name), fields);
+
+        let mut entered = write_lock(&ENTERED);
+        if let Some(pos) = entered.iter().rposition(|id| *id == span.into_u64()) {
+            entered.remove(pos);
+            let mut spans = write_lock(&SPANS);
+            if let Some(pos) = spans.iter().rposition(|s| s == name) {
+                spans.remove(pos);
+            }
+        }
+    }
+
+    fn clone_span(&self, span: &Id) -> Id {
+        if let Some(state) = write_lock(&self.spans).get_mut(&span.into_u64()) {
+            state.refs += 1;
+        }
+        span.clone()
+    }
+
+    fn try_close(&self, span: Id) -> bool {
+        let mut spans = write_lock(&self.spans);
+        let closed = match spans.get_mut(&span.into_u64()) {
+            Some(state) => {
+                state.refs -= 1;
+                state.refs == 0
+            }
+            None => false,
+        };
+        if closed {
+            spans.remove(&span.into_u64());
+        }
+        closed
+    }
+}
+
+#[derive(Default)]
+struct FieldVisitor {
+    message: Option<String>,
+    fields: HashMap<String, String>,
+}
+
+impl FieldVisitor {
+    fn insert(&mut self, field: &Field, value: String) {
+        if field.name() == "message" {
+            self.message = Some(value);
+        } else {
+            self.fields.insert(field.name().to_string(), value);
+        }
+    }
+}
+
+impl Visit for FieldVisitor {
+    fn record_str(&mut self, field: &Field, value: &str) {
+        self.insert(field, value.to_string());
+    }
+
+    fn record_debug(&mut self, field: &Field, value: &dyn fmt::Debug) {
+        self.insert(field, format!("{:?}", value));
+    }
+}
+
+fn level_number(level: &Level) -> u32 {
+    if *level == Level::ERROR {
+        ERROR
+    } else if *level == Level::WARN {
+        WARN
+    } else if *level == Level::INFO {
+        INFO
+    } else if *level == Level::DEBUG {
+        DEBUG
+    } else {
+        TRACE
+    }
+}
+
+fn log_level(level: &Level) -> log::Level {
+    match level_number(level) {
+        ERROR => log::Level::Error,
+        WARN => log::Level::Warn,
+        INFO => log::Level::Info,
+        DEBUG => log::Level::Debug,
+        _ => log::Level::Trace,
+    }
+}
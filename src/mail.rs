@@ -0,0 +1,449 @@
+//! # Email
+//!
+//! This module contains the client through which actors send email with the currently
+//! bound `wascc:mail` capability provider. Attachments may be held in memory or stored in
+//! the blobstore; blob attachments are streamed to the provider one chunk at a time, so
+//! large files never need to fit in the actor's memory
+//!
+//! ```
+//! extern crate wascc_actor as actor;
+//! use actor::prelude::*;
+//! use actor::mail::Message;
+//!
+//! fn send_invoice(customer: &str, invoice_id: &str) -> HandlerResult<String> {
+//!     let message = Message::new("billing@example.com")
+//!         .to(customer)
+//!         .subject(&format!("Your invoice {}", invoice_id))
+//!         .body("Your invoice is attached. Thank you for your business!")
+//!         .attach_blob("invoices", &format!("{}.pdf", invoice_id), "invoice.pdf", "application/pdf");
+//!     mail::default().send(&message)
+//! }
+//! ```
+
+use serde_derive::{Deserialize, Serialize};
+use std::io::Read;
+use wapc_guest::host_call;
+use wascc_codec::{deserialize, serialize};
+
+use crate::objectstore;
+use crate::HandlerResult;
+
+/// The reserved capability ID for email functionality
+pub const CAPID_MAIL: &str = "wascc:mail";
+
+/// Guest sends a `MailHeader` to the capability provider, receives a `BeginMailResponse` back
+pub const OP_BEGIN_MAIL: &str = "BeginMail";
+/// Guest sends an `AttachmentChunk` to the capability provider, receives nothing back
+pub const OP_WRITE_ATTACHMENT_CHUNK: &str = "WriteAttachmentChunk";
+/// Guest sends a `SendMailRequest` to the capability provider, receives a `SendMailResponse` back
+pub const OP_SEND_MAIL: &str = "SendMail";
+/// Guest sends an `AbortMailRequest` to the capability provider, receives nothing back
+pub const OP_ABORT_MAIL: &str = "AbortMail";
+
+/// The number of bytes of an attachment sent to the provider in each chunk
+pub const ATTACHMENT_CHUNK_SIZE: usize = 64 * 1024;
+
+/// An email message
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct Message {
+    /// The sender's address
+    pub from: String,
+    /// The primary recipients' addresses
+    pub to: Vec<String>,
+    /// The carbon-copied recipients' addresses
+    pub cc: Vec<String>,
+    /// The blind carbon-copied recipients' addresses
+    pub bcc: Vec<String>,
+    /// The address replies should be sent to, if not the sender's
+    pub reply_to: Option<String>,
+    /// The subject line
+    pub subject: String,
+    /// The plain text body
+    pub body: String,
+    /// An HTML alternative to the plain text body
+    pub html_body: Option<String>,
+    /// The attached files
+    pub attachments: Vec<Attachment>,
+}
+
+impl Message {
+    /// Creates a message from the given sender
+    pub fn new(from: &str) -> Message {
+        Message {
+            from: from.to_string(),
+            ..Default::default()
+        }
+    }
+
+    /// Adds a primary recipient
+    pub fn to(mut self, address: &str) -> Message {
+        self.to.push(address.to_string());
+        self
+    }
+
+    /// Adds a carbon-copied recipient
+    pub fn cc(mut self, address: &str) -> Message {
+        self.cc.push(address.to_string());
+        self
+    }
+
+    /// Adds a blind carbon-copied recipient
+    pub fn bcc(mut self, address: &str) -> Message {
+        self.bcc.push(address.to_string());
+        self
+    }
+
+    /// Sets the address replies should be sent to
+    pub fn reply_to(mut self, address: &str) -> Message {
+        self.reply_to = Some(address.to_string());
+        self
+    }
+
+    /// Sets the subject line
+    pub fn subject(mut self, subject: &str) -> Message {
+        self.subject = subject.to_string();
+        self
+    }
+
+    /// Sets the plain text body
+    pub fn body(mut self, body: &str) -> Message {
+        self.body = body.to_string();
+        self
+    }
+
+    /// Sets an HTML alternative to the plain text body
+    pub fn html_body(mut self, html: &str) -> Message {
+        self.html_body = Some(html.to_string());
+        self
+    }
+
+    /// Attaches a file held in memory
+    pub fn attach(mut self, filename: &str, content_type: &str, bytes: Vec<u8>) -> Message {
+        self.attachments.push(Attachment {
+            filename: filename.to_string(),
+            content_type: content_type.to_string(),
+            source: AttachmentSource::Inline(bytes),
+        });
+        self
+    }
+
+    /// Attaches a blob from the default blobstore binding
+    pub fn attach_blob(
+        self,
+        container: &str,
+        id: &str,
+        filename: &str,
+        content_type: &str,
+    ) -> Message {
+        self.attach_blob_from("default", container, id, filename, content_type)
+    }
+
+    /// Attaches a blob from the named blobstore binding
+    pub fn attach_blob_from(
+        mut self,
+        binding: &str,
+        container: &str,
+        id: &str,
+        filename: &str,
+        content_type: &str,
+    ) -> Message {
+        self.attachments.push(Attachment {
+            filename: filename.to_string(),
+            content_type: content_type.to_string(),
+            source: AttachmentSource::Blob {
+                binding: binding.to_string(),
+                container: container.to_string(),
+                id: id.to_string(),
+            },
+        });
+        self
+    }
+}
+
+/// A file attached to a message
+#[derive(Debug, Clone, PartialEq)]
+pub struct Attachment {
+    /// The name of the file as presented to the recipient
+    pub filename: String,
+    /// The MIME type of the file
+    pub content_type: String,
+    /// Where the contents of the file come from
+    pub source: AttachmentSource,
+}
+
+/// Where the contents of an attachment come from
+#[derive(Debug, Clone, PartialEq)]
+pub enum AttachmentSource {
+    /// Bytes held in memory
+    Inline(Vec<u8>),
+    /// A blob, read through the named blobstore binding as the message is sent
+    Blob {
+        /// The name of the blobstore binding
+        binding: String,
+        /// The container holding the blob
+        container: String,
+        /// The ID of the blob
+        id: String,
+    },
+}
+
+/// The addressing, content and attachment list of a message, sent before the contents of
+/// its attachments
+#[derive(Debug, Clone, PartialEq, Default, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct MailHeader {
+    /// The sender's address
+    pub from: String,
+    /// The primary recipients' addresses
+    pub to: Vec<String>,
+    /// The carbon-copied recipients' addresses
+    #[serde(default)]
+    pub cc: Vec<String>,
+    /// The blind carbon-copied recipients' addresses
+    #[serde(default)]
+    pub bcc: Vec<String>,
+    /// The address replies should be sent to, if not the sender's
+    #[serde(default)]
+    pub reply_to: Option<String>,
+    /// The subject line
+    pub subject: String,
+    /// The plain text body
+    pub body: String,
+    /// An HTML alternative to the plain text body
+    #[serde(default)]
+    pub html_body: Option<String>,
+    /// The attachments, whose contents follow as `AttachmentChunk`s in this order
+    #[serde(default)]
+    pub attachments: Vec<AttachmentHeader>,
+}
+
+/// Describes an attachment whose contents follow the `MailHeader`
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AttachmentHeader {
+    /// The name of the file as presented to the recipient
+    pub filename: String,
+    /// The MIME type of the file
+    pub content_type: String,
+    /// The size of the file in bytes
+    pub byte_size: u64,
+}
+
+/// The provider's answer to a `MailHeader`
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BeginMailResponse {
+    /// The ID under which the provider is assembling the message
+    pub message_id: String,
+}
+
+/// A chunk of the contents of an attachment
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AttachmentChunk {
+    /// The ID of the message being assembled
+    pub message_id: String,
+    /// The zero-based position of the attachment in the message's attachment list
+    pub attachment_index: u32,
+    /// The zero-based position of this chunk in the attachment
+    pub sequence_no: u64,
+    /// The bytes of the chunk
+    #[serde(with = "serde_bytes")]
+    #[serde(default)]
+    pub bytes: Vec<u8>,
+    /// Indicates this is the final chunk of the attachment
+    pub is_last: bool,
+}
+
+/// A request to send an assembled message
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SendMailRequest {
+    /// The ID of the message being assembled
+    pub message_id: String,
+}
+
+/// A request to discard a message whose attachments could not be written, so the provider
+/// can release what it has assembled so far
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AbortMailRequest {
+    /// The ID of the message being assembled
+    pub message_id: String,
+}
+
+/// The provider's answer to a `SendMailRequest`
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SendMailResponse {
+    /// The ID assigned to the message by the mail service, for tracing its delivery
+    pub delivery_id: String,
+}
+
+/// An abstraction around a host runtime capability for sending email
+pub struct MailHostBinding {
+    binding: String,
+}
+
+impl Default for MailHostBinding {
+    fn default() -> Self {
+        MailHostBinding {
+            binding: "default".to_string(),
+        }
+    }
+}
+
+/// Creates a named host binding for the email capability
+pub fn host(binding: &str) -> MailHostBinding {
+    MailHostBinding {
+        binding: binding.to_string(),
+    }
+}
+
+/// Creates the default host binding for the email capability
+pub fn default() -> MailHostBinding {
+    MailHostBinding::default()
+}
+
+impl MailHostBinding {
+    /// Sends a message, returning the ID the mail service assigned to it. Blob attachments
+    /// that do not exist cause the send to fail before anything is sent to the provider. If
+    /// an attachment fails partway through, the provider is asked to discard the message
+    pub fn send(&self, message: &Message) -> HandlerResult<String> {
+        let mut attachments = Vec::with_capacity(message.attachments.len());
+        for a in &message.attachments {
+            let byte_size = match &a.source {
+                AttachmentSource::Inline(bytes) => bytes.len() as u64,
+                AttachmentSource::Blob {
+                    binding,
+                    container,
+                    id,
+                } => {
+                    objectstore::host(binding)
+                        .get_blob_info(container, id)?
+                        .ok_or_else(|| format!("Attachment blob {}/{} not found", container, id))?
+                        .byte_size
+                }
+            };
+            attachments.push(AttachmentHeader {
+                filename: a.filename.clone(),
+                content_type: a.content_type.clone(),
+                byte_size,
+            });
+        }
+        let header = MailHeader {
+            from: message.from.clone(),
+            to: message.to.clone(),
+            cc: message.cc.clone(),
+            bcc: message.bcc.clone(),
+            reply_to: message.reply_to.clone(),
+            subject: message.subject.clone(),
+            body: message.body.clone(),
+            html_body: message.html_body.clone(),
+            attachments,
+        };
+        let message_id = host_call(
+            &self.binding,
+            CAPID_MAIL,
+            OP_BEGIN_MAIL,
+            &serialize(header)?,
+        )
+        .map_err(|e| e.into())
+        .and_then(|v| deserialize::<BeginMailResponse>(v.as_ref()))?
+        .message_id;
+
+        if let Err(e) = self.write_attachments(message, &message_id) {
+            if let Ok(payload) = serialize(AbortMailRequest { message_id }) {
+                let _ = host_call(&self.binding, CAPID_MAIL, OP_ABORT_MAIL, &payload);
+            }
+            return Err(e);
+        }
+
+        let cmd = SendMailRequest { message_id };
+        host_call(&self.binding, CAPID_MAIL, OP_SEND_MAIL, &serialize(cmd)?)
+            .map_err(|e| e.into())
+            .and_then(|v| deserialize::<SendMailResponse>(v.as_ref()))
+            .map(|r| r.delivery_id)
+    }
+
+    fn write_attachments(&self, message: &Message, message_id: &str) -> HandlerResult<()> {
+        for (index, a) in message.attachments.iter().enumerate() {
+            let mut chunks = AttachmentWriter {
+                binding: &self.binding,
+                message_id,
+                attachment_index: index as u32,
+                sequence_no: 0,
+            };
+            match &a.source {
+                AttachmentSource::Inline(bytes) => chunks.write_all(bytes)?,
+                AttachmentSource::Blob {
+                    binding,
+                    container,
+                    id,
+                } => {
+                    let store = objectstore::host(binding);
+                    let mut reader = store
+                        .chunk_reader(container, id, ATTACHMENT_CHUNK_SIZE as u64)?
+                        .ok_or_else(|| format!("Attachment blob {}/{} not found", container, id))?;
+                    chunks.write_from(&mut reader)?
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+struct AttachmentWriter<'a> {
+    binding: &'a str,
+    message_id: &'a str,
+    attachment_index: u32,
+    sequence_no: u64,
+}
+
+impl<'a> AttachmentWriter<'a> {
+    fn write_all(&mut self, bytes: &[u8]) -> HandlerResult<()> {
+        let mut chunks = bytes.chunks(ATTACHMENT_CHUNK_SIZE).peekable();
+        if chunks.peek().is_none() {
+            return self.send(Vec::new(), true);
+        }
+        while let Some(chunk) = chunks.next() {
+            self.send(chunk.to_vec(), chunks.peek().is_none())?;
+        }
+        Ok(())
+    }
+
+    fn write_from<R: Read>(&mut self, reader: &mut R) -> HandlerResult<()> {
+        let mut pending: Option<Vec<u8>> = None;
+        loop {
+            let mut chunk = Vec::with_capacity(ATTACHMENT_CHUNK_SIZE);
+            reader
+                .by_ref()
+                .take(ATTACHMENT_CHUNK_SIZE as u64)
+                .read_to_end(&mut chunk)?;
+            if chunk.is_empty() {
+                return self.send(pending.unwrap_or_default(), true);
+            }
+            if let Some(previous) = pending.replace(chunk) {
+                self.send(previous, false)?;
+            }
+        }
+    }
+
+    fn send(&mut self, bytes: Vec<u8>, is_last: bool) -> HandlerResult<()> {
+        let cmd = AttachmentChunk {
+            message_id: self.message_id.to_string(),
+            attachment_index: self.attachment_index,
+            sequence_no: self.sequence_no,
+            bytes,
+            is_last,
+        };
+        host_call(
+            self.binding,
+            CAPID_MAIL,
+            OP_WRITE_ATTACHMENT_CHUNK,
+            &serialize(cmd)?,
+        )?;
+        self.sequence_no += 1;
+        Ok(())
+    }
+}
@@ -8,30 +8,611 @@ use wapc_guest::host_call;
 const CAPID_MESSAGING: &str = "wascc:messaging";
 
 use crate::HandlerResult;
-use codec::messaging::{BrokerMessage, RequestMessage, OP_PERFORM_REQUEST, OP_PUBLISH_MESSAGE};
-use codec::serialize;
+use codec::messaging::{BrokerMessage, OP_DELIVER_MESSAGE, OP_PERFORM_REQUEST, OP_PUBLISH_MESSAGE};
+use codec::{deserialize, serialize};
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use serde_derive::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use std::sync::RwLock;
 use wascc_codec as codec;
 
+pub mod durable;
+pub mod envelope;
+pub mod rpc;
+pub mod subject;
+
+pub use envelope::{Envelope, Versioned};
+pub use subject::{subject_matches, Subject};
+
+/// The operation for an actor to ask the broker to add a subscription on its behalf
+pub const OP_SUBSCRIBE: &str = "Subscribe";
+/// The operation for an actor to ask the broker to remove one of its subscriptions
+pub const OP_UNSUBSCRIBE: &str = "Unsubscribe";
+/// The operation for an actor to perform a request whose reply arrives in multiple chunks
+pub const OP_PERFORM_STREAM_REQUEST: &str = "StreamRequest";
+/// The operation to deliver a single reply chunk of a streaming request to an actor
+pub const OP_DELIVER_REPLY_CHUNK: &str = "DeliverReplyChunk";
+/// The operation for an actor to publish several messages in a single host call
+pub const OP_PUBLISH_BATCH: &str = "PublishBatch";
+/// The operation for an actor to acknowledge successful processing of a delivered message
+pub const OP_ACK_MESSAGE: &str = "AckMessage";
+/// The operation for an actor to reject a delivered message, requesting redelivery
+pub const OP_NACK_MESSAGE: &str = "NackMessage";
+/// The operation for an actor to query information about the broker
+pub const OP_QUERY_BROKER_INFO: &str = "QueryBrokerInfo";
+
+/// The subject to which dead letters are published unless the binding is configured otherwise
+pub const DEFAULT_DEAD_LETTER_SUBJECT: &str = "wascc.deadletter";
+
+/// A request for the broker to create (or remove) a subscription for the calling actor.
+/// Messages received on the subscription are delivered to the actor via the
+/// `OP_DELIVER_MESSAGE` operation
+#[derive(Debug, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SubscriptionRequest {
+    /// The subject (which may contain wildcards) of the subscription
+    pub subject: String,
+    /// The name of the queue group. Members of the same queue group share the load of
+    /// messages published on the subject. This will be empty for regular subscriptions
+    #[serde(default)]
+    pub queue_group: String,
+}
+
+/// A request for the broker to publish a request whose reply will be streamed back to the
+/// actor as a series of `OP_DELIVER_REPLY_CHUNK` operations
+#[derive(Debug, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct StreamRequestMessage {
+    /// Subject on which to publish the request
+    pub subject: String,
+    /// Raw body of the request message
+    #[serde(with = "serde_bytes")]
+    #[serde(default)]
+    pub body: Vec<u8>,
+    /// The timeout (milliseconds) to await each reply chunk before giving up
+    #[serde(rename = "timeout")]
+    pub timeout_ms: i64,
+    /// The correlation ID to propagate to the recipient. Empty if there is none
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub correlation_id: String,
+}
+
+/// The provider's acknowledgement of a streaming request, containing the identifier
+/// that will be attached to every reply chunk
+#[derive(Debug, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ReplyStream {
+    /// Unique identifier of the reply stream
+    pub stream_id: String,
+}
+
+/// A single chunk of a streamed reply
+#[derive(Debug, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ReplyChunk {
+    /// Identifier of the reply stream to which this chunk belongs
+    pub stream_id: String,
+    /// Zero-based position of this chunk within the reply
+    pub sequence_no: u64,
+    /// Indicates that this is the final chunk of the reply
+    pub is_last: bool,
+    /// The raw bytes of this chunk
+    #[serde(with = "serde_bytes")]
+    #[serde(default)]
+    pub body: Vec<u8>,
+}
+
+/// A set of messages to be published by the broker, in order, from a single host call
+#[derive(Debug, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PublishBatch {
+    /// The messages to publish
+    pub messages: Vec<BrokerMessage>,
+    /// The correlation ID to propagate with every message in the batch. Empty if there is none
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub correlation_id: String,
+}
+
+/// Acknowledges successful processing of a message delivered by an at-least-once provider
+#[derive(Debug, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct MessageAck {
+    /// The provider-assigned identifier of the delivered message
+    pub message_id: String,
+}
+
+/// Rejects a message delivered by an at-least-once provider, asking for it to be redelivered
+#[derive(Debug, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct MessageNack {
+    /// The provider-assigned identifier of the delivered message
+    pub message_id: String,
+    /// How long (in milliseconds) the provider should wait before redelivering the message
+    pub redeliver_delay_ms: u64,
+}
+
+/// Information about the broker to which the provider is connected
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BrokerInfo {
+    /// Indicates whether the provider currently has a connection to the broker
+    pub connected: bool,
+    /// The identifier of the broker server, if known
+    #[serde(default)]
+    pub server_id: String,
+    /// The largest message body (in bytes) the broker will accept. Zero if unknown
+    #[serde(default)]
+    pub max_payload: u64,
+}
+
+/// The standard envelope wrapped around a message that could not be processed, published
+/// to a dead letter subject so that it can be inspected or replayed later
+#[derive(Debug, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DeadLetter {
+    /// The subject on which the failed message was originally delivered
+    pub original_subject: String,
+    /// The reply subject of the failed message. Empty if there was none
+    pub reply_to: String,
+    /// The raw bytes of the failed message
+    #[serde(with = "serde_bytes")]
+    #[serde(default)]
+    pub body: Vec<u8>,
+    /// A description of the error that caused processing to fail
+    pub error: String,
+    /// The number of times processing of the message was attempted
+    pub attempts: u32,
+    /// The correlation ID in effect when the message failed. Empty if there was none
+    #[serde(default)]
+    pub correlation_id: String,
+}
+
+/// Wire-compatible with the codec's `BrokerMessage`, with the addition of the optional
+/// correlation ID, message ID, and time-to-live. Providers unaware of these will simply
+/// ignore the extra fields
+#[derive(Debug, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct ExtendedBrokerMessage {
+    subject: String,
+    reply_to: String,
+    #[serde(with = "serde_bytes")]
+    #[serde(default)]
+    body: Vec<u8>,
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    correlation_id: String,
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    message_id: String,
+    #[serde(default, skip_serializing_if = "is_zero")]
+    ttl_ms: u64,
+}
+
+/// Wire-compatible with the codec's `RequestMessage`, with the addition of a correlation ID
+/// and a delay to apply before publishing
+#[derive(Debug, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct CorrelatedRequestMessage {
+    subject: String,
+    #[serde(with = "serde_bytes")]
+    #[serde(default)]
+    body: Vec<u8>,
+    #[serde(rename = "timeout")]
+    timeout_ms: i64,
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    correlation_id: String,
+    #[serde(default, skip_serializing_if = "is_zero")]
+    delay_ms: u64,
+}
+
+fn is_zero(v: &u64) -> bool {
+    *v == 0
+}
+
+/// Exponential backoff applied between the attempts of a retried request
+#[derive(Debug, Clone, PartialEq)]
+pub struct Backoff {
+    /// The delay (in milliseconds) before the first retry
+    pub initial_ms: u64,
+    /// The maximum delay (in milliseconds) between attempts
+    pub max_ms: u64,
+    /// The factor by which the delay grows after each retry
+    pub multiplier: u32,
+    /// When set, each delay is randomized between zero and its computed value using the
+    /// host's random number generator (`wascc:extras`). If the actor isn't permitted to use
+    /// that capability, the computed value is used as-is
+    pub jitter: bool,
+}
+
+impl Default for Backoff {
+    fn default() -> Self {
+        Backoff {
+            initial_ms: 50,
+            max_ms: 2_000,
+            multiplier: 2,
+            jitter: true,
+        }
+    }
+}
+
+impl Backoff {
+    /// Computes the delay (in milliseconds) preceding the given retry, where the first retry
+    /// is number 1
+    pub fn delay_ms(&self, retry: u32) -> u64 {
+        let factor = (self.multiplier.max(1) as u64).saturating_pow(retry.saturating_sub(1));
+        let delay = self.initial_ms.saturating_mul(factor).min(self.max_ms);
+        if self.jitter && delay > 0 {
+            crate::extras::default()
+                .get_random(0, delay.min(u32::MAX as u64) as u32)
+                .map(|r| r as u64)
+                .unwrap_or(delay)
+        } else {
+            delay
+        }
+    }
+}
+
+/// Decides whether a failed attempt of a retried request should be repeated
+pub type RetryPredicate = fn(&(dyn std::error::Error + Send + Sync)) -> bool;
+
+/// The default `RetryPolicy::retry_if`, which retries only failures that may succeed when
+/// repeated: timeouts, requests that found no responders, and an unavailable or disconnected
+/// broker. These are recognized from the error reported by the host, so any other failure
+/// (such as a malformed request or an authorization error) is treated as permanent
+pub fn is_transient(e: &(dyn std::error::Error + Send + Sync)) -> bool {
+    let message = e.to_string().to_ascii_lowercase();
+    [
+        "timeout",
+        "timed out",
+        "no responders",
+        "unavailable",
+        "connection",
+        "temporar",
+    ]
+    .iter()
+    .any(|m| message.contains(m))
+}
+
+/// Governs how `request_with_policy` retries failed requests
+#[derive(Debug, Clone)]
+pub struct RetryPolicy {
+    /// The maximum number of attempts, including the first
+    pub attempts: u32,
+    /// The timeout (in milliseconds) to await a reply for each individual attempt
+    pub attempt_timeout_ms: u64,
+    /// The backoff applied between attempts
+    pub backoff: Backoff,
+    /// The total time (in milliseconds) all attempts may take. Zero means no deadline
+    pub deadline_ms: u64,
+    /// Decides whether a failed attempt is retried. `is_transient` by default; use
+    /// `|_| true` to retry every failure
+    pub retry_if: RetryPredicate,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        RetryPolicy {
+            attempts: 3,
+            attempt_timeout_ms: 1_000,
+            backoff: Backoff::default(),
+            deadline_ms: 5_000,
+            retry_if: is_transient,
+        }
+    }
+}
+
+/// Used to pull only the correlation ID out of an inbound message
+#[derive(Debug, Default, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct CorrelationHeader {
+    #[serde(default)]
+    correlation_id: String,
+}
+
+lazy_static! {
+    static ref CURRENT_CORRELATION_ID: RwLock<Option<String>> = RwLock::new(None);
+}
+
+/// Returns the correlation ID of the handler invocation currently in progress, if any. This
+/// is captured automatically from messages delivered via `OP_DELIVER_MESSAGE`, and is
+/// attached to all messages published or requests made while handling that message
+pub fn correlation_id() -> Option<String> {
+    CURRENT_CORRELATION_ID
+        .read()
+        .unwrap_or_else(|e| e.into_inner())
+        .clone()
+}
+
+/// Sets the correlation ID that will be attached to outbound messages for the remainder of
+/// the current handler invocation. Actors that start a new pipeline (e.g. in response to
+/// an HTTP request) can use this to originate a correlation ID
+pub fn set_correlation_id(id: &str) {
+    *CURRENT_CORRELATION_ID
+        .write()
+        .unwrap_or_else(|e| e.into_inner()) = if id.is_empty() {
+        None
+    } else {
+        Some(id.to_string())
+    };
+}
+
+#[doc(hidden)]
+pub fn capture_correlation_id(operation: &str, msg: &[u8]) {
+    let id = if operation == OP_DELIVER_MESSAGE {
+        deserialize::<CorrelationHeader>(msg)
+            .unwrap_or_default()
+            .correlation_id
+    } else {
+        "".to_string()
+    };
+    set_correlation_id(&id);
+}
+
+fn current_correlation_id() -> String {
+    correlation_id().unwrap_or_default()
+}
+
+/// Reassembles streamed replies from the chunks delivered to the actor. Chunks may arrive
+/// in any order; a reply is handed back once its last chunk and every chunk before it
+/// have been received. Chunks numbered beyond the last chunk are discarded. Because each chunk arrives in its own handler invocation, actors
+/// will typically keep an assembler in a static.
+#[derive(Debug, Default)]
+pub struct ReplyAssembler {
+    pending: HashMap<String, PendingReply>,
+}
+
+#[derive(Debug, Default)]
+struct PendingReply {
+    chunks: BTreeMap<u64, Vec<u8>>,
+    last_sequence_no: Option<u64>,
+}
+
+impl ReplyAssembler {
+    /// Adds a chunk to the assembler, returning the complete reply body if this chunk
+    /// finished the stream
+    pub fn push(&mut self, chunk: ReplyChunk) -> Option<Vec<u8>> {
+        let reply = self.pending.entry(chunk.stream_id.clone()).or_default();
+        if chunk.is_last {
+            reply.last_sequence_no = Some(chunk.sequence_no);
+            // Chunks claiming to come after the last one can't be part of the reply
+            let _ = reply.chunks.split_off(&chunk.sequence_no);
+        }
+        if reply
+            .last_sequence_no
+            .is_some_and(|last| chunk.sequence_no > last)
+        {
+            return None;
+        }
+        reply.chunks.insert(chunk.sequence_no, chunk.body);
+
+        // Every key is at most `last`, so holding `last + 1` distinct keys means holding
+        // exactly `0..=last`
+        let complete = match reply.last_sequence_no {
+            Some(last) => last
+                .checked_add(1)
+                .is_some_and(|count| reply.chunks.len() as u64 == count),
+            None => false,
+        };
+        if complete {
+            self.pending
+                .remove(&chunk.stream_id)
+                .map(|r| r.chunks.into_values().flatten().collect())
+        } else {
+            None
+        }
+    }
+
+    /// Adds a raw `OP_DELIVER_REPLY_CHUNK` payload to the assembler
+    pub fn push_raw(&mut self, msg: &[u8]) -> HandlerResult<Option<Vec<u8>>> {
+        Ok(self.push(deserialize::<ReplyChunk>(msg)?))
+    }
+
+    /// Discards any chunks received so far for the given stream
+    pub fn abandon(&mut self, stream_id: &str) {
+        self.pending.remove(stream_id);
+    }
+
+    /// Indicates whether chunks have been received for a stream that is not yet complete
+    pub fn is_pending(&self, stream_id: &str) -> bool {
+        self.pending.contains_key(stream_id)
+    }
+}
+
+/// A message delivered to the actor via `OP_DELIVER_MESSAGE`. This can be used directly as
+/// the parameter type of a message handler, and takes care of the reply-to plumbing when
+/// responding to requests
+#[derive(Debug, PartialEq, Deserialize)]
+#[serde(from = "ExtendedBrokerMessage")]
+pub struct DeliveredMessage {
+    message: BrokerMessage,
+    message_id: String,
+    binding: String,
+}
+
+impl From<BrokerMessage> for DeliveredMessage {
+    fn from(message: BrokerMessage) -> Self {
+        DeliveredMessage {
+            message,
+            message_id: "".to_string(),
+            binding: "default".to_string(),
+        }
+    }
+}
+
+impl From<ExtendedBrokerMessage> for DeliveredMessage {
+    fn from(message: ExtendedBrokerMessage) -> Self {
+        DeliveredMessage {
+            message: BrokerMessage {
+                subject: message.subject,
+                reply_to: message.reply_to,
+                body: message.body,
+            },
+            message_id: message.message_id,
+            binding: "default".to_string(),
+        }
+    }
+}
+
+impl DeliveredMessage {
+    /// Sets the name of the message broker binding through which replies will be sent.
+    /// Replies are sent through the default binding unless otherwise specified
+    pub fn with_binding(mut self, binding: &str) -> Self {
+        self.binding = binding.to_string();
+        self
+    }
+
+    /// The subject on which the message was delivered
+    pub fn subject(&self) -> &str {
+        &self.message.subject
+    }
+
+    /// The reply subject of the message, if the sender is expecting a reply
+    pub fn reply_to(&self) -> Option<&str> {
+        if self.message.reply_to.is_empty() {
+            None
+        } else {
+            Some(&self.message.reply_to)
+        }
+    }
+
+    /// The raw bytes of the message
+    pub fn body(&self) -> &[u8] {
+        &self.message.body
+    }
+
+    /// Deserializes the body of the message with the standard codec deserializer
+    pub fn body_as<T: DeserializeOwned>(&self) -> HandlerResult<T> {
+        deserialize(&self.message.body)
+    }
+
+    /// The provider-assigned identifier of the message. This is only present for messages
+    /// delivered by providers with at-least-once semantics
+    pub fn message_id(&self) -> Option<&str> {
+        if self.message_id.is_empty() {
+            None
+        } else {
+            Some(&self.message_id)
+        }
+    }
+
+    /// Acknowledges successful processing of this message. Returns an error if the message
+    /// was not delivered with a message ID
+    pub fn ack(&self) -> HandlerResult<()> {
+        host(&self.binding).ack(self.require_message_id()?)
+    }
+
+    /// Rejects this message, asking the provider to redeliver it after the given delay (in
+    /// milliseconds). Returns an error if the message was not delivered with a message ID
+    pub fn nack(&self, redeliver_delay_ms: u64) -> HandlerResult<()> {
+        host(&self.binding).nack(self.require_message_id()?, redeliver_delay_ms)
+    }
+
+    /// Publishes this message to the default dead letter subject after processing has failed
+    pub fn dead_letter(&self, error: &str, attempts: u32) -> HandlerResult<()> {
+        host(&self.binding).dead_letter(&self.message, error, attempts)
+    }
+
+    fn require_message_id(&self) -> HandlerResult<&str> {
+        self.message_id().ok_or_else(|| {
+            format!(
+                "Message on subject '{}' has no message ID, it cannot be acknowledged",
+                self.message.subject
+            )
+            .into()
+        })
+    }
+
+    /// Returns the underlying broker message
+    pub fn into_inner(self) -> BrokerMessage {
+        self.message
+    }
+
+    /// Publishes the given payload to the reply subject of this message. Returns an error
+    /// if the message has no reply subject
+    pub fn reply(&self, payload: &[u8]) -> HandlerResult<()> {
+        match self.reply_to() {
+            Some(reply_to) => host(&self.binding).publish(reply_to, None, payload),
+            None => Err(format!(
+                "Cannot reply to message on subject '{}', it has no reply subject",
+                self.message.subject
+            )
+            .into()),
+        }
+    }
+
+    /// Serializes the given value with the standard codec serializer and publishes it to
+    /// the reply subject of this message
+    pub fn reply_typed<T: Serialize>(&self, value: &T) -> HandlerResult<()> {
+        self.reply(&serialize(value)?)
+    }
+}
+
+/// Generates a function that routes messages delivered via `OP_DELIVER_MESSAGE` to handler
+/// functions based on their subject. Patterns may contain the `*` (single token) and `>`
+/// (trailing tokens) wildcards, and are tried in the order given. Each handler receives the
+/// delivered message along with its body, deserialized into the handler's parameter type.
+/// Messages that match no route produce an error.
+///
+/// ```
+/// extern crate wascc_actor as actor;
+/// use actor::prelude::*;
+/// use actor::messaging::DeliveredMessage;
+///
+/// actor_handlers! { codec::messaging::OP_DELIVER_MESSAGE => route_messages }
+///
+/// message_routes!(route_messages {
+///     "orders.*.created" => order_created,
+///     "orders.>" => order_event
+/// });
+///
+/// fn order_created(msg: &DeliveredMessage, order_id: String) -> HandlerResult<()> {
+///     msg.reply_typed(&order_id)
+/// }
+///
+/// fn order_event(_msg: &DeliveredMessage, _event: Vec<String>) -> HandlerResult<()> {
+///     Ok(())
+/// }
+/// ```
+#[macro_export]
+macro_rules! message_routes(
+    { $name:ident { $($pattern:expr => $handler:ident),* $(,)? } } => {
+        fn $name(msg: $crate::messaging::DeliveredMessage) -> $crate::HandlerResult<()> {
+            $(
+                if $crate::messaging::subject_matches($pattern, msg.subject()) {
+                    return $handler(&msg, msg.body_as()?);
+                }
+            )*
+            Err(format!("No route for message on subject '{}'", msg.subject()).into())
+        }
+    };
+);
+
 /// Create a new named message broker host binding
 pub fn host(binding: &str) -> MessageBrokerHostBinding {
     MessageBrokerHostBinding {
         binding: binding.to_string(),
+        dead_letter_subject: DEFAULT_DEAD_LETTER_SUBJECT.to_string(),
     }
 }
 
 /// Create a default message broker host binding
 pub fn default() -> MessageBrokerHostBinding {
-    MessageBrokerHostBinding {
-        binding: "default".to_string(),
-    }
+    host("default")
 }
 
 /// Exposes message broker functionality to actor modules
 pub struct MessageBrokerHostBinding {
     binding: String,
+    dead_letter_subject: String,
 }
 
 impl MessageBrokerHostBinding {
+    /// Sets the subject to which `dead_letter` publishes failed messages. If not set,
+    /// `DEFAULT_DEAD_LETTER_SUBJECT` is used
+    pub fn with_dead_letter_subject(mut self, subject: &str) -> Self {
+        self.dead_letter_subject = subject.to_string();
+        self
+    }
+
     /// Publishes a message on a given subject with an optional reply subject
     pub fn publish(
         &self,
@@ -39,10 +620,37 @@ impl MessageBrokerHostBinding {
         reply_to: Option<&str>,
         payload: &[u8],
     ) -> HandlerResult<()> {
-        let cmd = BrokerMessage {
+        self.perform_publish(subject, reply_to, payload, 0)
+    }
+
+    /// Publishes a message that the provider should drop if it cannot be delivered within
+    /// the given time-to-live (in milliseconds). This is useful for time-sensitive
+    /// notifications that are worthless once stale. Providers that don't support expiration
+    /// will deliver the message normally
+    pub fn publish_with_ttl(
+        &self,
+        subject: &str,
+        reply_to: Option<&str>,
+        payload: &[u8],
+        ttl_ms: u64,
+    ) -> HandlerResult<()> {
+        self.perform_publish(subject, reply_to, payload, ttl_ms)
+    }
+
+    fn perform_publish(
+        &self,
+        subject: &str,
+        reply_to: Option<&str>,
+        payload: &[u8],
+        ttl_ms: u64,
+    ) -> HandlerResult<()> {
+        let cmd = ExtendedBrokerMessage {
             subject: subject.to_string(),
             reply_to: reply_to.map_or("".to_string(), |r| r.to_string()),
             body: payload.to_vec(),
+            correlation_id: current_correlation_id(),
+            message_id: "".to_string(),
+            ttl_ms,
         };
 
         host_call(
@@ -55,6 +663,53 @@ impl MessageBrokerHostBinding {
         .map(|_vec| ())
     }
 
+    /// Wraps a message that could not be processed in a `DeadLetter` envelope and publishes it
+    /// to this binding's dead letter subject, so that poison messages aren't silently lost
+    /// when a handler fails
+    pub fn dead_letter(
+        &self,
+        original: &BrokerMessage,
+        error: &str,
+        attempts: u32,
+    ) -> HandlerResult<()> {
+        let letter = DeadLetter {
+            original_subject: original.subject.to_string(),
+            reply_to: original.reply_to.to_string(),
+            body: original.body.clone(),
+            error: error.to_string(),
+            attempts,
+            correlation_id: current_correlation_id(),
+        };
+
+        self.publish(&self.dead_letter_subject, None, &serialize(letter)?)
+    }
+
+    /// Publishes a set of messages, each consisting of a subject and a payload, in a single
+    /// host call. Actors emitting many small messages per request should prefer this over
+    /// repeated calls to `publish`. Messages are published in the order supplied
+    pub fn publish_batch(&self, messages: &[(&str, &[u8])]) -> HandlerResult<()> {
+        let cmd = PublishBatch {
+            messages: messages
+                .iter()
+                .map(|(subject, payload)| BrokerMessage {
+                    subject: subject.to_string(),
+                    reply_to: "".to_string(),
+                    body: payload.to_vec(),
+                })
+                .collect(),
+            correlation_id: current_correlation_id(),
+        };
+
+        host_call(
+            &self.binding,
+            CAPID_MESSAGING,
+            OP_PUBLISH_BATCH,
+            &serialize(cmd)?,
+        )
+        .map_err(|e| e.into())
+        .map(|_vec| ())
+    }
+
     /// Publishes a message and expects a reply to come back within a given timeout (in milliseconds)
     pub fn request(
         &self,
@@ -62,10 +717,76 @@ impl MessageBrokerHostBinding {
         payload: &[u8],
         timeout_ms: u64,
     ) -> HandlerResult<Vec<u8>> {
-        let cmd = RequestMessage {
+        self.perform_request(subject, payload, timeout_ms, 0)
+    }
+
+    /// Performs a request, retrying failed attempts according to the given policy. Because
+    /// actors cannot block, the backoff delay before each retry is passed along to the
+    /// provider, which waits before publishing the retried request (providers that don't
+    /// support delays will retry immediately). The policy's deadline bounds the total time,
+    /// including delays and per-attempt timeouts, that all attempts may take. Failures the
+    /// policy's `retry_if` predicate rejects are not retried. The error from the final
+    /// attempt is returned if none succeed
+    pub fn request_with_policy(
+        &self,
+        subject: &str,
+        payload: &[u8],
+        policy: &RetryPolicy,
+    ) -> HandlerResult<Vec<u8>> {
+        let mut remaining_ms = if policy.deadline_ms == 0 {
+            u64::MAX
+        } else {
+            policy.deadline_ms
+        };
+        let mut last_error = None;
+
+        for attempt in 0..policy.attempts.max(1) {
+            let delay_ms = if attempt == 0 {
+                0
+            } else {
+                policy.backoff.delay_ms(attempt)
+            };
+            if delay_ms >= remaining_ms {
+                break;
+            }
+            remaining_ms -= delay_ms;
+            let timeout_ms = policy.attempt_timeout_ms.min(remaining_ms);
+
+            match self.perform_request(subject, payload, timeout_ms, delay_ms) {
+                Ok(reply) => return Ok(reply),
+                Err(e) => {
+                    let retry = (policy.retry_if)(e.as_ref());
+                    last_error = Some(e);
+                    if !retry {
+                        break;
+                    }
+                }
+            }
+            remaining_ms = remaining_ms.saturating_sub(timeout_ms);
+            if remaining_ms == 0 {
+                break;
+            }
+        }
+
+        Err(match last_error {
+            Some(e) => format!("Request on subject '{}' failed: {}", subject, e).into(),
+            None => format!("Deadline exceeded for request on subject '{}'", subject).into(),
+        })
+    }
+
+    fn perform_request(
+        &self,
+        subject: &str,
+        payload: &[u8],
+        timeout_ms: u64,
+        delay_ms: u64,
+    ) -> HandlerResult<Vec<u8>> {
+        let cmd = CorrelatedRequestMessage {
             subject: subject.to_string(),
             timeout_ms: timeout_ms as _,
             body: payload.to_vec(),
+            correlation_id: current_correlation_id(),
+            delay_ms,
         };
 
         // The broker plugin applies no wrapper around the response from the broker, the
@@ -78,4 +799,109 @@ impl MessageBrokerHostBinding {
         )
         .map_err(|e| e.into())
     }
+
+    /// Publishes a request whose reply may be too large for a single broker message. The
+    /// reply is delivered back to this actor as successive `OP_DELIVER_REPLY_CHUNK`
+    /// operations, tagged with the stream ID returned from this call. Use a `ReplyAssembler`
+    /// to put the chunks back together. The timeout (in milliseconds) applies to each chunk
+    pub fn request_stream(
+        &self,
+        subject: &str,
+        payload: &[u8],
+        timeout_ms: u64,
+    ) -> HandlerResult<ReplyStream> {
+        let cmd = StreamRequestMessage {
+            subject: subject.to_string(),
+            body: payload.to_vec(),
+            timeout_ms: timeout_ms as _,
+            correlation_id: current_correlation_id(),
+        };
+
+        host_call(
+            &self.binding,
+            CAPID_MESSAGING,
+            OP_PERFORM_STREAM_REQUEST,
+            &serialize(cmd)?,
+        )
+        .map_err(|e| e.into())
+        .and_then(|v| deserialize::<ReplyStream>(v.as_ref()))
+    }
+
+    /// Queries the provider for information about the broker, such as whether it is connected
+    /// and the maximum payload size it accepts
+    pub fn broker_info(&self) -> HandlerResult<BrokerInfo> {
+        host_call(&self.binding, CAPID_MESSAGING, OP_QUERY_BROKER_INFO, &[])
+            .map_err(|e| e.into())
+            .and_then(|v| deserialize::<BrokerInfo>(v.as_ref()))
+    }
+
+    /// Acknowledges successful processing of a message delivered by an at-least-once provider,
+    /// preventing its redelivery
+    pub fn ack(&self, message_id: &str) -> HandlerResult<()> {
+        let cmd = MessageAck {
+            message_id: message_id.to_string(),
+        };
+
+        host_call(
+            &self.binding,
+            CAPID_MESSAGING,
+            OP_ACK_MESSAGE,
+            &serialize(cmd)?,
+        )
+        .map_err(|e| e.into())
+        .map(|_vec| ())
+    }
+
+    /// Rejects a message delivered by an at-least-once provider, asking for it to be
+    /// redelivered after the given delay (in milliseconds)
+    pub fn nack(&self, message_id: &str, redeliver_delay_ms: u64) -> HandlerResult<()> {
+        let cmd = MessageNack {
+            message_id: message_id.to_string(),
+            redeliver_delay_ms,
+        };
+
+        host_call(
+            &self.binding,
+            CAPID_MESSAGING,
+            OP_NACK_MESSAGE,
+            &serialize(cmd)?,
+        )
+        .map_err(|e| e.into())
+        .map(|_vec| ())
+    }
+
+    /// Asks the broker to subscribe this actor to the given subject at runtime. Messages
+    /// published on the subject will be delivered to the actor via `OP_DELIVER_MESSAGE`, the
+    /// same as subscriptions declared in the host binding configuration
+    pub fn subscribe(&self, subject: &str) -> HandlerResult<()> {
+        self.manage_subscription(OP_SUBSCRIBE, subject, "")
+    }
+
+    /// Removes a subscription previously created with `subscribe`
+    pub fn unsubscribe(&self, subject: &str) -> HandlerResult<()> {
+        self.manage_subscription(OP_UNSUBSCRIBE, subject, "")
+    }
+
+    /// Asks the broker to subscribe this actor to the given subject as a member of a queue
+    /// group. Each message published on the subject will be delivered (via `OP_DELIVER_MESSAGE`)
+    /// to only one member of the group, allowing load-balanced consumption
+    pub fn subscribe_queue(&self, subject: &str, group: &str) -> HandlerResult<()> {
+        self.manage_subscription(OP_SUBSCRIBE, subject, group)
+    }
+
+    /// Removes this actor's membership in the given queue group for the subject
+    pub fn unsubscribe_queue(&self, subject: &str, group: &str) -> HandlerResult<()> {
+        self.manage_subscription(OP_UNSUBSCRIBE, subject, group)
+    }
+
+    fn manage_subscription(&self, op: &str, subject: &str, group: &str) -> HandlerResult<()> {
+        let cmd = SubscriptionRequest {
+            subject: subject.to_string(),
+            queue_group: group.to_string(),
+        };
+
+        host_call(&self.binding, CAPID_MESSAGING, op, &serialize(cmd)?)
+            .map_err(|e| e.into())
+            .map(|_vec| ())
+    }
 }
@@ -0,0 +1,234 @@
+//! # Durable Messaging
+//!
+//! This module contains the client interface for durable (persistent) stream consumers
+//! exposed by `wascc:messaging` providers that support them. Unlike the fire-and-forget
+//! `publish` and `request` functions, messages fetched from a durable consumer remain
+//! pending until they are explicitly acknowledged, allowing at-least-once processing.
+
+use super::CAPID_MESSAGING;
+use crate::HandlerResult;
+use serde_derive::{Deserialize, Serialize};
+use wapc_guest::host_call;
+use wascc_codec::{deserialize, serialize};
+
+/// The operation to create (or look up an existing) durable consumer on a stream
+pub const OP_CREATE_CONSUMER: &str = "CreateConsumer";
+/// The operation to delete a durable consumer
+pub const OP_DELETE_CONSUMER: &str = "DeleteConsumer";
+/// The operation to fetch a batch of pending messages for a durable consumer
+pub const OP_FETCH_MESSAGES: &str = "FetchMessages";
+/// The operation to acknowledge a message fetched from a durable consumer
+pub const OP_ACK_CONSUMER_MESSAGE: &str = "AckConsumerMessage";
+/// The operation to query the state of a durable consumer
+pub const OP_QUERY_CONSUMER: &str = "QueryConsumer";
+
+/// Configuration for a durable consumer
+#[derive(Debug, Clone, PartialEq, Default, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ConsumerConfig {
+    /// The name of the stream the consumer reads from
+    pub stream: String,
+    /// The durable name of the consumer. Consumers with the same name share their position
+    /// in the stream
+    pub name: String,
+    /// Only messages with subjects matching this filter will be delivered. Empty for all
+    #[serde(default)]
+    pub filter_subject: String,
+    /// How long (in milliseconds) the provider waits for an acknowledgement before
+    /// redelivering a message. Zero uses the provider default
+    #[serde(default)]
+    pub ack_wait_ms: u64,
+    /// The maximum number of delivery attempts for a message. Zero means unlimited
+    #[serde(default)]
+    pub max_deliver: u64,
+}
+
+/// Identifies a durable consumer on a stream
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ConsumerQuery {
+    /// The name of the stream
+    pub stream: String,
+    /// The durable name of the consumer
+    pub consumer: String,
+}
+
+/// A request to fetch a batch of messages from a durable consumer
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct FetchRequest {
+    /// The name of the stream
+    pub stream: String,
+    /// The durable name of the consumer
+    pub consumer: String,
+    /// The maximum number of messages to return
+    pub batch_size: u64,
+    /// How long (in milliseconds) to wait for messages to become available
+    #[serde(rename = "timeout")]
+    pub timeout_ms: i64,
+}
+
+/// A message fetched from a durable consumer
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct StreamMessage {
+    /// Provider-assigned identifier used to acknowledge this message
+    pub message_id: String,
+    /// The subject on which the message was originally published
+    pub subject: String,
+    /// The position of this message within the stream
+    pub stream_sequence: u64,
+    /// The number of times this message has been delivered, including this delivery
+    pub delivery_count: u64,
+    /// The raw bytes of the message
+    #[serde(with = "serde_bytes")]
+    #[serde(default)]
+    pub body: Vec<u8>,
+}
+
+/// The results of a fetch request
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct FetchResponse {
+    /// The fetched messages, in stream order
+    pub messages: Vec<StreamMessage>,
+}
+
+/// A request to acknowledge a message fetched from a durable consumer
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ConsumerAck {
+    /// The name of the stream
+    pub stream: String,
+    /// The durable name of the consumer
+    pub consumer: String,
+    /// The identifier of the message being acknowledged
+    pub message_id: String,
+}
+
+/// The state of a durable consumer
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ConsumerInfo {
+    /// The name of the stream
+    pub stream: String,
+    /// The durable name of the consumer
+    pub name: String,
+    /// The stream sequence number of the last message delivered to the consumer
+    pub delivered_sequence: u64,
+    /// The number of messages delivered but not yet acknowledged
+    pub num_ack_pending: u64,
+    /// The number of messages in the stream that have not yet been delivered to the
+    /// consumer (the consumer's lag)
+    pub num_pending: u64,
+}
+
+/// Creates a new named durable messaging host binding
+pub fn host(binding: &str) -> DurableConsumerHostBinding {
+    DurableConsumerHostBinding {
+        binding: binding.to_string(),
+    }
+}
+
+/// Creates a default durable messaging host binding
+pub fn default() -> DurableConsumerHostBinding {
+    DurableConsumerHostBinding {
+        binding: "default".to_string(),
+    }
+}
+
+/// Exposes durable stream consumer functionality to actor modules
+pub struct DurableConsumerHostBinding {
+    binding: String,
+}
+
+impl DurableConsumerHostBinding {
+    /// Creates a durable consumer with the given configuration. If a consumer with the
+    /// same name already exists on the stream, its current state is returned
+    pub fn create_consumer(&self, config: ConsumerConfig) -> HandlerResult<ConsumerInfo> {
+        host_call(
+            &self.binding,
+            CAPID_MESSAGING,
+            OP_CREATE_CONSUMER,
+            &serialize(config)?,
+        )
+        .map_err(|e| e.into())
+        .and_then(|v| deserialize::<ConsumerInfo>(v.as_ref()))
+    }
+
+    /// Deletes a durable consumer, discarding its position in the stream
+    pub fn delete_consumer(&self, stream: &str, consumer: &str) -> HandlerResult<()> {
+        let cmd = ConsumerQuery {
+            stream: stream.to_string(),
+            consumer: consumer.to_string(),
+        };
+        host_call(
+            &self.binding,
+            CAPID_MESSAGING,
+            OP_DELETE_CONSUMER,
+            &serialize(cmd)?,
+        )
+        .map_err(|e| e.into())
+        .map(|_vec| ())
+    }
+
+    /// Fetches up to `batch_size` pending messages for the consumer, waiting up to the given
+    /// timeout (in milliseconds) for them to become available. Each returned message must be
+    /// acknowledged with `ack`, otherwise it will be redelivered
+    pub fn fetch(
+        &self,
+        stream: &str,
+        consumer: &str,
+        batch_size: u64,
+        timeout_ms: u64,
+    ) -> HandlerResult<Vec<StreamMessage>> {
+        let cmd = FetchRequest {
+            stream: stream.to_string(),
+            consumer: consumer.to_string(),
+            batch_size,
+            timeout_ms: timeout_ms as _,
+        };
+        host_call(
+            &self.binding,
+            CAPID_MESSAGING,
+            OP_FETCH_MESSAGES,
+            &serialize(cmd)?,
+        )
+        .map_err(|e| e.into())
+        .and_then(|v| deserialize::<FetchResponse>(v.as_ref()))
+        .map(|r| r.messages)
+    }
+
+    /// Acknowledges successful processing of a fetched message
+    pub fn ack(&self, stream: &str, consumer: &str, message_id: &str) -> HandlerResult<()> {
+        let cmd = ConsumerAck {
+            stream: stream.to_string(),
+            consumer: consumer.to_string(),
+            message_id: message_id.to_string(),
+        };
+        host_call(
+            &self.binding,
+            CAPID_MESSAGING,
+            OP_ACK_CONSUMER_MESSAGE,
+            &serialize(cmd)?,
+        )
+        .map_err(|e| e.into())
+        .map(|_vec| ())
+    }
+
+    /// Queries the current state of a durable consumer, including its lag
+    pub fn consumer_info(&self, stream: &str, consumer: &str) -> HandlerResult<ConsumerInfo> {
+        let cmd = ConsumerQuery {
+            stream: stream.to_string(),
+            consumer: consumer.to_string(),
+        };
+        host_call(
+            &self.binding,
+            CAPID_MESSAGING,
+            OP_QUERY_CONSUMER,
+            &serialize(cmd)?,
+        )
+        .map_err(|e| e.into())
+        .and_then(|v| deserialize::<ConsumerInfo>(v.as_ref()))
+    }
+}
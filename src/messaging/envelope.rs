@@ -0,0 +1,111 @@
+//! # Message Envelopes
+//!
+//! This module contains a versioned envelope for message payloads, allowing independently
+//! deployed actors to evolve the format of the messages they exchange
+
+use crate::HandlerResult;
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use serde_derive::{Deserialize, Serialize};
+use std::cmp::Ordering;
+use wascc_codec::{deserialize, serialize};
+
+/// Implemented by message types that can be sealed in an `Envelope`. The schema name
+/// identifies the kind of message, and the version should be incremented whenever its
+/// format changes
+pub trait Versioned: Serialize + DeserializeOwned {
+    /// The name of the message schema, e.g. `orders.OrderPlaced`
+    const SCHEMA: &'static str;
+    /// The version of the schema implemented by this type
+    const VERSION: u32;
+
+    /// Decodes a payload written with a different version of the schema. The default
+    /// implementation decodes the payload directly, which succeeds as long as fields added
+    /// since the older version have serde defaults, and ignores any fields added by newer
+    /// versions. Override this to migrate formats that changed incompatibly
+    fn from_version(version: u32, payload: &[u8]) -> HandlerResult<Self> {
+        deserialize(payload).map_err(|e| {
+            format!(
+                "Cannot decode version {} of schema '{}' as version {}: {}",
+                version,
+                Self::SCHEMA,
+                Self::VERSION,
+                e
+            )
+            .into()
+        })
+    }
+}
+
+/// How the version of a sealed payload relates to the version expected by the reader
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Compatibility {
+    /// The payload was written with the same version the reader expects
+    Exact,
+    /// The payload was written with an older version than the reader expects
+    Older,
+    /// The payload was written with a newer version than the reader expects
+    Newer,
+}
+
+/// A payload wrapped with the name and version of its schema
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Envelope {
+    /// The name of the payload's schema
+    pub schema: String,
+    /// The version of the schema with which the payload was written
+    pub version: u32,
+    /// The serialized payload
+    #[serde(with = "serde_bytes")]
+    #[serde(default)]
+    pub payload: Vec<u8>,
+}
+
+impl Envelope {
+    /// Serializes a value into a new envelope carrying its schema name and version
+    pub fn seal<T: Versioned>(value: &T) -> HandlerResult<Envelope> {
+        Ok(Envelope {
+            schema: T::SCHEMA.to_string(),
+            version: T::VERSION,
+            payload: serialize(value)?,
+        })
+    }
+
+    /// Decodes an envelope from the raw bytes of a message
+    pub fn from_bytes(bytes: &[u8]) -> HandlerResult<Envelope> {
+        deserialize(bytes)
+    }
+
+    /// Serializes the envelope for use as the body of a message
+    pub fn to_bytes(&self) -> HandlerResult<Vec<u8>> {
+        serialize(self)
+    }
+
+    /// Indicates how the version of this envelope's payload compares to the version of `T`.
+    /// Returns an error if the envelope holds a different schema
+    pub fn compatibility<T: Versioned>(&self) -> HandlerResult<Compatibility> {
+        if self.schema != T::SCHEMA {
+            return Err(format!(
+                "Envelope contains schema '{}', expected '{}'",
+                self.schema,
+                T::SCHEMA
+            )
+            .into());
+        }
+        Ok(match self.version.cmp(&T::VERSION) {
+            Ordering::Equal => Compatibility::Exact,
+            Ordering::Less => Compatibility::Older,
+            Ordering::Greater => Compatibility::Newer,
+        })
+    }
+
+    /// Opens the envelope, decoding its payload as `T`. Payloads written with other versions
+    /// of the schema are decoded with `Versioned::from_version`
+    pub fn open<T: Versioned>(&self) -> HandlerResult<T> {
+        match self.compatibility::<T>()? {
+            Compatibility::Exact => deserialize(&self.payload),
+            _ => T::from_version(self.version, &self.payload),
+        }
+    }
+}
@@ -0,0 +1,193 @@
+//! # RPC Services
+//!
+//! This module contains the support types for RPC services built on top of the message
+//! broker with the `rpc_service!` macro. Each method of a service is exposed on its own
+//! subject beneath the service's subject prefix, and replies are wrapped in an `RpcReply`
+//! so that errors raised by the server are reported to the client rather than surfacing
+//! as request timeouts.
+
+use super::DeliveredMessage;
+use crate::HandlerResult;
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use serde_derive::{Deserialize, Serialize};
+use wascc_codec::{deserialize, serialize};
+
+/// The default time (in milliseconds) an RPC client waits for a reply
+pub const DEFAULT_TIMEOUT_MS: u64 = 2_000;
+
+/// The reply to an RPC method invocation
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RpcReply {
+    /// The error raised by the server. Empty if the invocation succeeded
+    #[serde(default)]
+    pub error: String,
+    /// The serialized return value of the method
+    #[serde(with = "serde_bytes")]
+    #[serde(default)]
+    pub body: Vec<u8>,
+}
+
+/// Replies to an RPC invocation with a successful result
+pub fn reply_ok<T: Serialize>(msg: &DeliveredMessage, value: &T) -> HandlerResult<()> {
+    let reply = RpcReply {
+        error: "".to_string(),
+        body: serialize(value)?,
+    };
+    msg.reply_typed(&reply)
+}
+
+/// Replies to an RPC invocation with an error
+pub fn reply_err(msg: &DeliveredMessage, error: &str) -> HandlerResult<()> {
+    let reply = RpcReply {
+        error: error.to_string(),
+        body: vec![],
+    };
+    msg.reply_typed(&reply)
+}
+
+/// Decodes the raw reply to an RPC invocation, returning the error raised by the server if
+/// the invocation failed
+pub fn decode_reply<T: DeserializeOwned>(raw: &[u8]) -> HandlerResult<T> {
+    let reply = deserialize::<RpcReply>(raw)?;
+    if reply.error.is_empty() {
+        deserialize(&reply.body)
+    } else {
+        Err(reply.error.into())
+    }
+}
+
+/// Generates a module containing a broker-backed RPC service. The module contains:
+///
+/// * `Service` - a trait with one method per declared RPC method, implemented by the server
+/// * `Client` - a typed stub that invokes the service's methods via broker requests
+/// * `dispatch` - routes a delivered message to the matching `Service` method and replies
+///   with its result
+/// * `SUBJECTS` - a wildcard subject covering every method, for the server to subscribe to
+///
+/// Each method is exposed on the subject `<prefix>.<method name>`.
+///
+/// ```
+/// extern crate wascc_actor as actor;
+/// use actor::prelude::*;
+/// use actor::messaging::DeliveredMessage;
+///
+/// rpc_service! {
+///     /// Inventory management
+///     pub mod inventory("svc.inventory") {
+///         /// Returns the number of items in stock for a SKU
+///         fn stock_level(String) -> u32;
+///     }
+/// }
+///
+/// struct Warehouse;
+///
+/// impl inventory::Service for Warehouse {
+///     fn stock_level(&self, _sku: String) -> HandlerResult<u32> {
+///         Ok(42)
+///     }
+/// }
+///
+/// actor_handlers! { codec::messaging::OP_DELIVER_MESSAGE => handle_message }
+///
+/// fn handle_message(msg: DeliveredMessage) -> HandlerResult<()> {
+///     inventory::dispatch(&Warehouse, &msg)
+/// }
+///
+/// fn check_stock() -> HandlerResult<u32> {
+///     inventory::Client::new().stock_level(&"SKU-1".to_string())
+/// }
+/// ```
+#[macro_export]
+macro_rules! rpc_service(
+    {
+        $(#[$attr:meta])*
+        $vis:vis mod $module:ident($prefix:literal) {
+            $(
+                $(#[$method_attr:meta])*
+                fn $method:ident($req:ty) -> $resp:ty;
+            )*
+        }
+    } => {
+        $(#[$attr])*
+        $vis mod $module {
+            #[allow(unused_imports)]
+            use super::*;
+
+            /// A wildcard subject covering every method of this service
+            pub const SUBJECTS: &str = concat!($prefix, ".>");
+
+            /// The operations provided by this service
+            pub trait Service {
+                $(
+                    $(#[$method_attr])*
+                    fn $method(&self, req: $req) -> $crate::HandlerResult<$resp>;
+                )*
+            }
+
+            /// Routes a delivered message to the matching service method, replying with its
+            /// result. Returns an error if the subject matches no method of this service
+            pub fn dispatch<S: Service>(
+                service: &S,
+                msg: &$crate::messaging::DeliveredMessage,
+            ) -> $crate::HandlerResult<()> {
+                $(
+                    if msg.subject() == concat!($prefix, ".", stringify!($method)) {
+                        return match msg.body_as::<$req>().and_then(|req| service.$method(req)) {
+                            Ok(v) => $crate::messaging::rpc::reply_ok(msg, &v),
+                            Err(e) => $crate::messaging::rpc::reply_err(msg, &e.to_string()),
+                        };
+                    }
+                )*
+                Err(format!("No RPC method for subject '{}'", msg.subject()).into())
+            }
+
+            /// A typed client for this service
+            pub struct Client {
+                binding: String,
+                timeout_ms: u64,
+            }
+
+            impl Default for Client {
+                fn default() -> Self {
+                    Client {
+                        binding: "default".to_string(),
+                        timeout_ms: $crate::messaging::rpc::DEFAULT_TIMEOUT_MS,
+                    }
+                }
+            }
+
+            impl Client {
+                /// Creates a client that uses the default message broker binding
+                pub fn new() -> Self {
+                    Client::default()
+                }
+
+                /// Sets the message broker binding used to invoke the service
+                pub fn with_binding(mut self, binding: &str) -> Self {
+                    self.binding = binding.to_string();
+                    self
+                }
+
+                /// Sets the time (in milliseconds) to wait for each reply
+                pub fn with_timeout(mut self, timeout_ms: u64) -> Self {
+                    self.timeout_ms = timeout_ms;
+                    self
+                }
+
+                $(
+                    $(#[$method_attr])*
+                    pub fn $method(&self, req: &$req) -> $crate::HandlerResult<$resp> {
+                        let reply = $crate::messaging::host(&self.binding).request(
+                            concat!($prefix, ".", stringify!($method)),
+                            &$crate::prelude::serialize(req)?,
+                            self.timeout_ms,
+                        )?;
+                        $crate::messaging::rpc::decode_reply(&reply)
+                    }
+                )*
+            }
+        }
+    };
+);
@@ -0,0 +1,151 @@
+//! # Subjects
+//!
+//! This module contains helpers for building well-formed message broker subjects
+
+use crate::HandlerResult;
+use std::fmt;
+
+/// The wildcard that matches exactly one token of a subject
+pub const WILDCARD_TOKEN: &str = "*";
+/// The wildcard that matches one or more trailing tokens of a subject
+pub const WILDCARD_TAIL: &str = ">";
+
+/// A builder for dot-delimited broker subjects. Each token is validated as it is added, so
+/// malformed subjects (empty tokens, whitespace, or wildcard characters embedded in literal
+/// tokens) are caught before they reach the broker. The first validation error is reported
+/// by `build`.
+///
+/// ```
+/// # use wascc_actor::messaging::Subject;
+/// let subject = Subject::new("orders").token("1234").wildcard().build().unwrap();
+/// assert_eq!(subject, "orders.1234.*");
+/// assert!(Subject::new("orders").token("bad token").build().is_err());
+/// ```
+#[derive(Debug, Clone, PartialEq)]
+pub struct Subject {
+    tokens: Vec<String>,
+    error: Option<String>,
+}
+
+impl Subject {
+    /// Starts a new subject. The root may contain several dot-delimited tokens, each of
+    /// which is validated
+    pub fn new(root: &str) -> Subject {
+        let mut subject = Subject {
+            tokens: Vec::new(),
+            error: None,
+        };
+        for token in root.split('.') {
+            subject = subject.token(token);
+        }
+        subject
+    }
+
+    /// Appends a literal token to the subject
+    pub fn token(mut self, token: &str) -> Subject {
+        if self.error.is_none() {
+            if let Err(e) = self.validate_append(token) {
+                self.error = Some(e);
+            } else {
+                self.tokens.push(token.to_string());
+            }
+        }
+        self
+    }
+
+    /// Appends a single-token wildcard (`*`) to the subject
+    pub fn wildcard(self) -> Subject {
+        self.push_wildcard(WILDCARD_TOKEN)
+    }
+
+    /// Appends a trailing wildcard (`>`) to the subject. No further tokens may follow it
+    pub fn tail(self) -> Subject {
+        self.push_wildcard(WILDCARD_TAIL)
+    }
+
+    /// Indicates whether the subject contains any wildcards, in which case it can be used
+    /// for subscriptions but not for publishing
+    pub fn has_wildcards(&self) -> bool {
+        self.tokens
+            .iter()
+            .any(|t| t == WILDCARD_TOKEN || t == WILDCARD_TAIL)
+    }
+
+    /// Produces the subject string, or the first validation error encountered while
+    /// building it
+    pub fn build(self) -> HandlerResult<String> {
+        match self.error {
+            Some(e) => Err(e.into()),
+            None => Ok(self.tokens.join(".")),
+        }
+    }
+
+    fn push_wildcard(mut self, wildcard: &str) -> Subject {
+        if self.error.is_none() {
+            if self.ends_with_tail() {
+                self.error = Some(tail_error(wildcard));
+            } else {
+                self.tokens.push(wildcard.to_string());
+            }
+        }
+        self
+    }
+
+    fn ends_with_tail(&self) -> bool {
+        self.tokens.last().is_some_and(|t| t == WILDCARD_TAIL)
+    }
+
+    fn validate_append(&self, token: &str) -> Result<(), String> {
+        if self.ends_with_tail() {
+            Err(tail_error(token))
+        } else if token.is_empty() {
+            Err("Subject tokens cannot be empty".to_string())
+        } else if token.chars().any(char::is_whitespace) {
+            Err(format!("Subject token '{}' contains whitespace", token))
+        } else if token.contains(['.', '*', '>']) {
+            Err(format!(
+                "Subject token '{}' contains a reserved character ('.', '*' or '>')",
+                token
+            ))
+        } else {
+            Ok(())
+        }
+    }
+}
+
+impl fmt::Display for Subject {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.tokens.join("."))
+    }
+}
+
+/// Indicates whether a subject matches a subscription pattern, using the same rules as
+/// NATS-style brokers: `*` matches exactly one token, and `>` (which must be the final token
+/// of the pattern) matches one or more trailing tokens
+///
+/// ```
+/// # use wascc_actor::messaging::subject_matches;
+/// assert!(subject_matches("orders.*.created", "orders.1234.created"));
+/// assert!(subject_matches("orders.>", "orders.1234.shipped"));
+/// assert!(!subject_matches("orders.>", "orders"));
+/// ```
+pub fn subject_matches(pattern: &str, subject: &str) -> bool {
+    let mut pattern_tokens = pattern.split('.');
+    let mut subject_tokens = subject.split('.');
+    loop {
+        match (pattern_tokens.next(), subject_tokens.next()) {
+            (Some(WILDCARD_TAIL), Some(_)) => return pattern_tokens.next().is_none(),
+            (Some(WILDCARD_TOKEN), Some(_)) => continue,
+            (Some(p), Some(s)) if p == s => continue,
+            (None, None) => return true,
+            _ => return false,
+        }
+    }
+}
+
+fn tail_error(token: &str) -> String {
+    format!(
+        "Subject token '{}' cannot follow the '{}' wildcard",
+        token, WILDCARD_TAIL
+    )
+}
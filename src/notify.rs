@@ -0,0 +1,153 @@
+//! # Notifications
+//!
+//! This module contains the client through which actors send SMS messages and mobile push
+//! notifications with the currently bound `wascc:notify` capability provider. Message text
+//! comes from templates managed by the provider, rendered with the parameters the actor
+//! supplies
+//!
+//! ```
+//! extern crate wascc_actor as actor;
+//! use actor::prelude::*;
+//! use actor::notify::Notification;
+//!
+//! fn order_shipped(phone: &str, device: &str, order_id: &str) -> HandlerResult<()> {
+//!     let notification = Notification::new("order-shipped").param("orderId", order_id);
+//!     notify::default().sms(phone, &notification)?;
+//!     notify::default().push(device, &notification.data("deepLink", "orders"))?;
+//!     Ok(())
+//! }
+//! ```
+
+use serde_derive::{Deserialize, Serialize};
+use std::collections::HashMap;
+use wapc_guest::host_call;
+use wascc_codec::{deserialize, serialize};
+
+use crate::HandlerResult;
+
+/// The reserved capability ID for notification functionality
+pub const CAPID_NOTIFY: &str = "wascc:notify";
+
+/// Guest sends a `NotificationRequest` to the capability provider, receives a
+/// `NotificationReceipt` back
+pub const OP_SEND_SMS: &str = "SendSms";
+/// Guest sends a `NotificationRequest` to the capability provider, receives a
+/// `NotificationReceipt` back
+pub const OP_SEND_PUSH: &str = "SendPush";
+
+/// The content of a notification: a provider-managed template and the parameters it is
+/// rendered with
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct Notification {
+    /// The name of the template
+    pub template: String,
+    /// The values substituted into the template
+    pub params: HashMap<String, String>,
+    /// Key-value data delivered alongside a push notification to the receiving app.
+    /// Ignored for SMS
+    pub data: HashMap<String, String>,
+}
+
+impl Notification {
+    /// Creates a notification rendered from the named template
+    pub fn new(template: &str) -> Notification {
+        Notification {
+            template: template.to_string(),
+            ..Default::default()
+        }
+    }
+
+    /// Sets a template parameter
+    pub fn param(mut self, name: &str, value: &str) -> Notification {
+        self.params.insert(name.to_string(), value.to_string());
+        self
+    }
+
+    /// Sets a value delivered to the receiving app with a push notification
+    pub fn data(mut self, name: &str, value: &str) -> Notification {
+        self.data.insert(name.to_string(), value.to_string());
+        self
+    }
+}
+
+/// A notification addressed to a target
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct NotificationRequest {
+    /// For SMS, the recipient's phone number in E.164 format. For push, the device token
+    /// or the topic the notification is sent to
+    pub target: String,
+    /// The name of the template
+    pub template: String,
+    /// The values substituted into the template
+    #[serde(default)]
+    pub params: HashMap<String, String>,
+    /// Key-value data delivered alongside a push notification
+    #[serde(default)]
+    pub data: HashMap<String, String>,
+}
+
+/// The provider's answer to a `NotificationRequest`
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct NotificationReceipt {
+    /// The ID assigned to the notification by the delivery service
+    pub notification_id: String,
+}
+
+/// An abstraction around a host runtime capability for notifications
+pub struct NotifyHostBinding {
+    binding: String,
+}
+
+impl Default for NotifyHostBinding {
+    fn default() -> Self {
+        NotifyHostBinding {
+            binding: "default".to_string(),
+        }
+    }
+}
+
+/// Creates a named host binding for the notification capability
+pub fn host(binding: &str) -> NotifyHostBinding {
+    NotifyHostBinding {
+        binding: binding.to_string(),
+    }
+}
+
+/// Creates the default host binding for the notification capability
+pub fn default() -> NotifyHostBinding {
+    NotifyHostBinding::default()
+}
+
+impl NotifyHostBinding {
+    /// Sends an SMS message to a phone number in E.164 format (such as `+15555550123`),
+    /// returning the ID assigned to it by the delivery service
+    pub fn sms(&self, phone_number: &str, notification: &Notification) -> HandlerResult<String> {
+        self.send(OP_SEND_SMS, phone_number, notification)
+    }
+
+    /// Sends a push notification to a device token or topic, returning the ID assigned to
+    /// it by the delivery service
+    pub fn push(&self, target: &str, notification: &Notification) -> HandlerResult<String> {
+        self.send(OP_SEND_PUSH, target, notification)
+    }
+
+    fn send(
+        &self,
+        operation: &str,
+        target: &str,
+        notification: &Notification,
+    ) -> HandlerResult<String> {
+        let cmd = NotificationRequest {
+            target: target.to_string(),
+            template: notification.template.clone(),
+            params: notification.params.clone(),
+            data: notification.data.clone(),
+        };
+        host_call(&self.binding, CAPID_NOTIFY, operation, &serialize(cmd)?)
+            .map_err(|e| e.into())
+            .and_then(|v| deserialize::<NotificationReceipt>(v.as_ref()))
+            .map(|r| r.notification_id)
+    }
+}
@@ -1,4 +1,9 @@
+use crate::keyvalue::KeyValueStoreHostBinding;
 use crate::HandlerResult;
+use serde_derive::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+use std::ops::Deref;
 use wapc_guest::host_call;
 use wascc_codec::blobstore::Blob;
 use wascc_codec::blobstore::Container;
@@ -11,6 +16,585 @@ use wascc_codec::{deserialize, serialize};
 
 const CAPID_BLOBSTORE: &str = "wascc:blobstore";
 
+pub mod collector;
+pub mod form;
+pub mod io;
+pub mod tracker;
+
+pub use collector::DownloadCollector;
+pub use form::{FormUpload, UploadedFile};
+pub use io::{ChunkReader, ChunkWriter};
+pub use tracker::{Direction, TrackedTransfer, TransferTracker};
+
+/// Guest sends a ChunkRequest to the capability provider, receives the requested FileChunk back
+pub const OP_GET_OBJECT_CHUNK: &str = "GetObjectChunk";
+
+/// Guest sends a RangedStreamRequest to the capability provider to begin streaming a byte
+/// range of a blob to the actor, lack of error indicates success
+pub const OP_START_DOWNLOAD_RANGE: &str = "StartDownloadRange";
+
+/// Guest sends an UploadCompletion to the capability provider once all chunks of a checksummed
+/// upload have been sent, lack of error indicates the provider accepted the upload. Only
+/// uploads made with `put_blob_checksummed` or `start_checksummed_upload` use this operation
+pub const OP_COMPLETE_UPLOAD: &str = "CompleteUpload";
+
+/// Guest sends a BlobMetadata to the capability provider, replacing the blob's metadata.
+/// Lack of error indicates success
+pub const OP_SET_OBJECT_METADATA: &str = "SetObjectMetadata";
+/// Guest sends a Blob to the capability provider, receives a BlobMetadata back
+pub const OP_GET_OBJECT_METADATA: &str = "GetObjectMetadata";
+/// Guest sends a MetadataQuery to the capability provider, receives a BlobList back
+pub const OP_LIST_OBJECTS_BY_METADATA: &str = "ListObjectsByMetadata";
+
+/// Guest sends a PresignRequest to the capability provider, receives a PresignedUrl back
+pub const OP_PRESIGN_URL: &str = "PresignUrl";
+
+/// Guest sends a CopyRequest to the capability provider, receives the new Blob back
+pub const OP_COPY_OBJECT: &str = "CopyObject";
+/// Guest sends a CopyRequest to the capability provider, receives the renamed Blob back
+pub const OP_MOVE_OBJECT: &str = "MoveObject";
+
+/// Guest sends a Container to the capability provider, receives a ContainerInfo back. An
+/// empty ID in the response indicates the container does not exist
+pub const OP_GET_CONTAINER_INFO: &str = "GetContainerInfo";
+
+/// Guest sends a ListRequest to the capability provider, receives a BlobPage back
+pub const OP_LIST_OBJECTS_PAGED: &str = "ListObjectsPaged";
+
+/// Guest sends a Blob to the capability provider, receives a VersionList back
+pub const OP_LIST_OBJECT_VERSIONS: &str = "ListObjectVersions";
+/// Guest sends an ObjectVersion to the capability provider, receives a BlobVersion back. An
+/// empty ID in the response indicates the version does not exist
+pub const OP_GET_OBJECT_VERSION_INFO: &str = "GetObjectVersionInfo";
+/// Guest sends an ObjectVersion to the capability provider, lack of error indicates success
+pub const OP_REMOVE_OBJECT_VERSION: &str = "RemoveObjectVersion";
+
+/// Guest sends a FileChunk (with no bytes) to the capability provider to begin an upload
+/// whose parts may be sent in any order, lack of error indicates success
+pub const OP_START_MULTIPART_UPLOAD: &str = "StartMultipartUpload";
+/// Guest sends a FileChunk holding a single part to the capability provider, receives a
+/// PartReceipt back
+pub const OP_UPLOAD_PART: &str = "UploadPart";
+/// Guest sends an UploadManifest to the capability provider once every part of a multipart
+/// upload has been sent, lack of error indicates the provider assembled the blob
+pub const OP_COMPLETE_MULTIPART_UPLOAD: &str = "CompleteMultipartUpload";
+/// Guest sends a Blob to the capability provider, discarding the parts of an unfinished
+/// multipart upload
+pub const OP_ABORT_MULTIPART_UPLOAD: &str = "AbortMultipartUpload";
+
+/// Guest sends a PolicyRequest to the capability provider, replacing the container's
+/// retention policy. Lack of error indicates success
+pub const OP_SET_CONTAINER_POLICY: &str = "SetContainerPolicy";
+/// Guest sends a Container to the capability provider, receives a PolicyRequest back
+pub const OP_GET_CONTAINER_POLICY: &str = "GetContainerPolicy";
+
+/// The chunk size used by helpers that transfer an entire blob in a single call
+pub const DEFAULT_CHUNK_SIZE: u64 = 256 * 1024;
+
+/// Determines the chunk size used for an upload
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ChunkingStrategy {
+    /// Every chunk but the last is exactly this many bytes
+    Fixed(u64),
+    /// The provider chooses the chunk size, falling back to `DEFAULT_CHUNK_SIZE` if it does
+    /// not return one
+    ProviderSuggested,
+    /// The smallest chunk size that transfers the blob in at most this many chunks
+    MaxChunks(u64),
+}
+
+impl ChunkingStrategy {
+    /// The chunk size this strategy requests for a blob of the given size
+    pub fn chunk_size(&self, total_bytes: u64) -> HandlerResult<u64> {
+        match *self {
+            ChunkingStrategy::Fixed(0) => Err("Chunk size must be greater than zero".into()),
+            ChunkingStrategy::Fixed(size) => Ok(size),
+            ChunkingStrategy::ProviderSuggested => Ok(DEFAULT_CHUNK_SIZE),
+            ChunkingStrategy::MaxChunks(0) => {
+                Err("Maximum chunk count must be greater than zero".into())
+            }
+            ChunkingStrategy::MaxChunks(max) => Ok(total_bytes.div_ceil(max).max(1)),
+        }
+    }
+}
+
+/// Returns the number of chunks needed to transfer the given number of bytes, counting a
+/// final partial chunk
+pub fn chunk_count(total_bytes: u64, chunk_size: u64) -> u64 {
+    if chunk_size == 0 {
+        0
+    } else {
+        total_bytes.div_ceil(chunk_size)
+    }
+}
+
+/// A request for a single chunk of a blob, returned synchronously by the provider
+#[derive(Debug, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ChunkRequest {
+    /// The unique ID of the requested blob
+    pub id: String,
+    /// The container of the requested blob
+    pub container: String,
+    /// The size of each chunk of the blob
+    pub chunk_size: u64,
+    /// The sequence number of the requested chunk
+    pub sequence_no: u64,
+    /// The byte offset within the blob at which the first chunk begins
+    #[serde(default)]
+    pub offset: u64,
+    /// The version of the blob to read. Empty to read the latest version
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub version_id: String,
+}
+
+impl ChunkRequest {
+    fn for_blob(blob: &Blob, chunk_size: u64, offset: u64) -> ChunkRequest {
+        ChunkRequest {
+            id: blob.id.to_string(),
+            container: blob.container.to_string(),
+            chunk_size,
+            sequence_no: 0,
+            offset,
+            version_id: "".to_string(),
+        }
+    }
+}
+
+/// The codec's `StreamRequest` with the addition of a byte range, sent with its own
+/// operation so that providers unaware of ranges reject it rather than streaming the whole
+/// blob
+#[derive(Debug, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct RangedStreamRequest {
+    id: String,
+    container: String,
+    chunk_size: u64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    context: Option<String>,
+    offset: u64,
+    length: u64,
+}
+
+/// Wire-compatible with the codec's `FileChunk`, with the addition of the blob's MIME type.
+/// Providers unaware of content types will ignore the extra field
+#[derive(Debug, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct TypedFileChunk {
+    sequence_no: u64,
+    container: String,
+    id: String,
+    total_bytes: u64,
+    chunk_size: u64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    context: Option<String>,
+    #[serde(with = "serde_bytes")]
+    #[serde(default)]
+    chunk_bytes: Vec<u8>,
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    content_type: String,
+}
+
+/// Wire-compatible with the codec's `Blob`, with the addition of the blob's MIME type
+#[derive(Debug, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct TypedBlob {
+    id: String,
+    container: String,
+    byte_size: u64,
+    #[serde(default)]
+    content_type: String,
+    #[serde(default)]
+    etag: String,
+}
+
+/// Information about a blob returned by `get_blob_details`. This dereferences to the underlying
+/// `Blob`, so it can be passed anywhere a `&Blob` is expected
+#[derive(Debug, PartialEq)]
+pub struct BlobInfo {
+    blob: Blob,
+    content_type: Option<String>,
+    etag: Option<String>,
+}
+
+impl BlobInfo {
+    /// The MIME type supplied when the blob was uploaded, if any
+    pub fn content_type(&self) -> Option<&str> {
+        self.content_type.as_deref()
+    }
+
+    /// The entity tag the provider assigned to the blob's current contents, if any
+    pub fn etag(&self) -> Option<&str> {
+        self.etag.as_deref()
+    }
+
+    /// Consumes this value, returning the underlying `Blob`
+    pub fn into_blob(self) -> Blob {
+        self.blob
+    }
+}
+
+impl Deref for BlobInfo {
+    type Target = Blob;
+
+    fn deref(&self) -> &Blob {
+        &self.blob
+    }
+}
+
+impl From<TypedBlob> for BlobInfo {
+    fn from(b: TypedBlob) -> BlobInfo {
+        BlobInfo {
+            blob: Blob {
+                id: b.id,
+                container: b.container,
+                byte_size: b.byte_size,
+            },
+            content_type: if b.content_type.is_empty() {
+                None
+            } else {
+                Some(b.content_type)
+            },
+            etag: if b.etag.is_empty() {
+                None
+            } else {
+                Some(b.etag)
+            },
+        }
+    }
+}
+
+/// A retention policy under which the provider automatically expires the objects in a
+/// container. Useful for temporary artifacts such as uploads awaiting processing
+#[derive(Debug, Clone, Default, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ContainerPolicy {
+    /// Objects older than this many seconds are removed. `None` for no age limit
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_age_s: Option<u64>,
+    /// When the container holds more than this many objects, the oldest are removed. `None`
+    /// for no limit
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_objects: Option<u64>,
+}
+
+/// A container's retention policy, as exchanged with the provider
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PolicyRequest {
+    /// The container to which the policy applies
+    pub container: String,
+    /// The retention policy
+    #[serde(default)]
+    pub policy: ContainerPolicy,
+}
+
+/// Identifies a single version of a blob held by a provider that keeps object versions
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ObjectVersion {
+    /// The unique ID of the blob
+    pub id: String,
+    /// The container of the blob
+    pub container: String,
+    /// The provider-assigned ID of the version
+    pub version_id: String,
+}
+
+/// A single stored version of a blob
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BlobVersion {
+    /// The unique ID of the blob
+    pub id: String,
+    /// The container of the blob
+    pub container: String,
+    /// The provider-assigned ID of the version
+    pub version_id: String,
+    /// Total number of bytes of this version
+    pub byte_size: u64,
+    /// The time this version was written, in seconds since the Unix epoch
+    #[serde(default)]
+    pub last_modified: u64,
+    /// Indicates whether this is the current version of the blob
+    #[serde(default)]
+    pub is_latest: bool,
+}
+
+/// The stored versions of a blob, newest first
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct VersionList {
+    /// The versions of the blob
+    #[serde(default)]
+    pub versions: Vec<BlobVersion>,
+}
+
+/// Usage information for a container
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ContainerInfo {
+    /// The unique ID of the container
+    pub id: String,
+    /// The number of objects in the container
+    pub object_count: u64,
+    /// The combined size in bytes of every object in the container
+    pub total_bytes: u64,
+    /// The time the container was created, in seconds since the Unix epoch. Zero if the
+    /// provider does not record creation times
+    #[serde(default)]
+    pub created_at: u64,
+}
+
+/// A request for a single page of the objects in a container
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ListRequest {
+    /// The container to list
+    pub container: String,
+    /// The token returned with the previous page, or empty to request the first page
+    #[serde(default)]
+    pub continuation_token: String,
+    /// The maximum number of objects to return in the page
+    pub max_results: u32,
+}
+
+/// A single page of the objects in a container
+#[derive(Debug, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BlobPage {
+    /// The objects in this page
+    #[serde(default)]
+    pub blobs: Vec<Blob>,
+    /// The token with which to request the next page. `None` if this is the last page
+    #[serde(default)]
+    pub continuation_token: Option<String>,
+}
+
+/// Metadata (such as content type, owner, or custom tags) attached to a blob
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BlobMetadata {
+    /// The unique ID of the blob
+    pub id: String,
+    /// The container of the blob
+    pub container: String,
+    /// The metadata entries attached to the blob
+    #[serde(default)]
+    pub metadata: HashMap<String, String>,
+}
+
+/// A request to list the blobs in a container whose metadata contains every entry in the
+/// filter
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct MetadataQuery {
+    /// The container to search
+    pub container: String,
+    /// The metadata entries a blob must have to be included
+    pub filter: HashMap<String, String>,
+}
+
+/// A request for a time-limited URL through which a blob can be accessed directly
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PresignRequest {
+    /// The unique ID of the blob
+    pub id: String,
+    /// The container of the blob
+    pub container: String,
+    /// The HTTP method the URL will permit, `GET` or `PUT`
+    pub method: String,
+    /// The number of seconds for which the URL remains valid
+    pub expires_s: u64,
+}
+
+/// A time-limited URL generated by the provider
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PresignedUrl {
+    /// The URL
+    pub url: String,
+}
+
+/// A request for the provider to copy a blob without its bytes passing through the actor
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CopyRequest {
+    /// The container of the blob to copy
+    pub source_container: String,
+    /// The unique ID of the blob to copy
+    pub source_id: String,
+    /// The container in which to place the copy
+    pub destination_container: String,
+    /// The unique ID of the copy
+    pub destination_id: String,
+}
+
+/// Signals the end of an upload, carrying the SHA-256 digest of everything that was sent so
+/// the provider can verify the bytes it stored
+#[derive(Debug, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct UploadCompletion {
+    /// The unique ID of the uploaded blob
+    pub id: String,
+    /// The container of the uploaded blob
+    pub container: String,
+    /// Total number of bytes sent
+    pub total_bytes: u64,
+    /// Hex-encoded SHA-256 digest of the bytes sent
+    pub sha256: String,
+}
+
+/// An upload that computes a running SHA-256 digest of the chunks as they are sent. When
+/// started with `start_checksummed_upload`, the digest is sent to the provider when the
+/// upload is finished
+pub struct ChecksummedUpload<'a> {
+    store: &'a ObjectStoreHostBinding,
+    transfer: Transfer,
+    hasher: Sha256,
+    next_sequence_no: u64,
+    bytes_sent: u64,
+    complete: bool,
+}
+
+impl<'a> ChecksummedUpload<'a> {
+    fn new(
+        store: &'a ObjectStoreHostBinding,
+        transfer: Transfer,
+        complete: bool,
+    ) -> ChecksummedUpload<'a> {
+        ChecksummedUpload {
+            store,
+            transfer,
+            hasher: Sha256::new(),
+            next_sequence_no: 0,
+            bytes_sent: 0,
+            complete,
+        }
+    }
+
+    /// The transfer being performed
+    pub fn transfer(&self) -> &Transfer {
+        &self.transfer
+    }
+
+    /// Sends the next chunk of the blob
+    pub fn send_chunk(&mut self, bytes: &[u8]) -> HandlerResult<()> {
+        self.store
+            .upload_chunk(&self.transfer, self.next_sequence_no, bytes)?;
+        self.hasher.update(bytes);
+        self.next_sequence_no += 1;
+        self.bytes_sent += bytes.len() as u64;
+        Ok(())
+    }
+
+    /// Completes the upload, returning the hex-encoded SHA-256 digest of all chunks. If the
+    /// upload was started with `start_checksummed_upload`, the digest is first sent to the
+    /// provider with `OP_COMPLETE_UPLOAD`
+    pub fn finish(self) -> HandlerResult<String> {
+        let digest = to_hex(&self.hasher.finalize());
+        if !self.complete {
+            return Ok(digest);
+        }
+        let cmd = UploadCompletion {
+            id: self.transfer.blob_id.to_string(),
+            container: self.transfer.container.to_string(),
+            total_bytes: self.bytes_sent,
+            sha256: digest.clone(),
+        };
+        host_call(
+            &self.store.binding,
+            CAPID_BLOBSTORE,
+            OP_COMPLETE_UPLOAD,
+            &serialize(cmd)?,
+        )
+        .map_err(|e| e.into())
+        .map(|_v| digest)
+    }
+}
+
+fn to_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+/// The provider's acknowledgement of a single part of a multipart upload
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PartReceipt {
+    /// The index of the part within the blob
+    pub sequence_no: u64,
+    /// The number of bytes in the part
+    #[serde(default)]
+    pub byte_size: u64,
+    /// An opaque tag assigned to the part by the provider, if any
+    #[serde(default)]
+    pub etag: String,
+}
+
+/// The complete list of parts making up a multipart upload, sent to the provider so that it
+/// can assemble the blob
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct UploadManifest {
+    /// The unique ID of the uploaded blob
+    pub id: String,
+    /// The container of the uploaded blob
+    pub container: String,
+    /// Total number of bytes in all parts
+    pub total_bytes: u64,
+    /// The parts of the blob, ordered by sequence number
+    pub parts: Vec<PartReceipt>,
+}
+
+/// The state of a transfer saved with `save_transfer`, from which it can be resumed
+#[derive(Debug, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SavedTransfer {
+    /// The transfer being performed
+    pub transfer: Transfer,
+    /// The sequence number of the next chunk to be sent
+    pub next_sequence_no: u64,
+}
+
+/// Saves the state of an in-progress transfer in the key-value store, so that it can be
+/// resumed (for uploads, with `resume_upload`) if the actor is restarted before it completes
+pub fn save_transfer(
+    store: &KeyValueStoreHostBinding,
+    key: &str,
+    transfer: &Transfer,
+    next_sequence_no: u64,
+) -> HandlerResult<()> {
+    let saved = SavedTransfer {
+        transfer: copy_transfer(transfer),
+        next_sequence_no,
+    };
+    store.set(key, &serde_json::to_string(&saved)?, None)
+}
+
+fn copy_transfer(transfer: &Transfer) -> Transfer {
+    Transfer {
+        blob_id: transfer.blob_id.to_string(),
+        container: transfer.container.to_string(),
+        chunk_size: transfer.chunk_size,
+        total_size: transfer.total_size,
+        total_chunks: transfer.total_chunks,
+        context: transfer.context.clone(),
+    }
+}
+
+/// Restores the state of a transfer previously saved with `save_transfer`, or `None` if
+/// nothing is saved under the given key
+pub fn load_transfer(
+    store: &KeyValueStoreHostBinding,
+    key: &str,
+) -> HandlerResult<Option<SavedTransfer>> {
+    match store.get(key)? {
+        Some(json) => Ok(Some(serde_json::from_str(&json)?)),
+        None => Ok(None),
+    }
+}
+
 /// An abstraction around a host runtime capability for a key-value store
 pub struct ObjectStoreHostBinding {
     binding: String,
@@ -45,101 +629,879 @@ impl ObjectStoreHostBinding {
         host_call(
             &self.binding,
             CAPID_BLOBSTORE,
-            OP_CREATE_CONTAINER,
+            OP_CREATE_CONTAINER,
+            &serialize(cmd)?,
+        )
+        .map(|v| deserialize::<Container>(v.as_ref()).unwrap())
+        .map_err(|e| e.into())
+    }
+
+    /// Removes a container from the store. Whether or not this will fail if the container
+    /// has items may be specific to a given provider implementation.
+    pub fn remove_container(&self, name: &str) -> HandlerResult<()> {
+        let cmd = Container {
+            id: name.to_string(),
+        };
+        host_call(
+            &self.binding,
+            CAPID_BLOBSTORE,
+            OP_REMOVE_CONTAINER,
+            &serialize(cmd)?,
+        )
+        .map(|_v| ())
+        .map_err(|e| e.into())
+    }
+
+    /// Replaces the retention policy of a container. Pass `ContainerPolicy::default()` to
+    /// stop objects from being expired
+    pub fn set_container_policy(
+        &self,
+        container: &str,
+        policy: ContainerPolicy,
+    ) -> HandlerResult<()> {
+        let cmd = PolicyRequest {
+            container: container.to_string(),
+            policy,
+        };
+        host_call(
+            &self.binding,
+            CAPID_BLOBSTORE,
+            OP_SET_CONTAINER_POLICY,
+            &serialize(cmd)?,
+        )
+        .map(|_v| ())
+        .map_err(|e| e.into())
+    }
+
+    /// Retrieves the retention policy of a container. A container without a policy returns
+    /// `ContainerPolicy::default()`
+    pub fn get_container_policy(&self, container: &str) -> HandlerResult<ContainerPolicy> {
+        let cmd = Container {
+            id: container.to_string(),
+        };
+        host_call(
+            &self.binding,
+            CAPID_BLOBSTORE,
+            OP_GET_CONTAINER_POLICY,
+            &serialize(cmd)?,
+        )
+        .map_err(|e| e.into())
+        .and_then(|v| deserialize::<PolicyRequest>(v.as_ref()))
+        .map(|r| r.policy)
+    }
+
+    /// Obtains usage information for a container, or `None` if the container does not exist
+    pub fn get_container_info(&self, name: &str) -> HandlerResult<Option<ContainerInfo>> {
+        let cmd = Container {
+            id: name.to_string(),
+        };
+        host_call(
+            &self.binding,
+            CAPID_BLOBSTORE,
+            OP_GET_CONTAINER_INFO,
+            &serialize(cmd)?,
+        )
+        .map_err(|e| e.into())
+        .and_then(|v| deserialize::<ContainerInfo>(v.as_ref()))
+        .map(|info| if info.id.is_empty() { None } else { Some(info) })
+    }
+
+    /// Removes an object from a container
+    pub fn remove_object(&self, name: &str, container: &str) -> crate::HandlerResult<()> {
+        let cmd = Blob {
+            id: name.to_string(),
+            container: container.to_string(),
+            byte_size: 0,
+        };
+        host_call(
+            &self.binding,
+            CAPID_BLOBSTORE,
+            OP_REMOVE_OBJECT,
+            &serialize(cmd)?,
+        )
+        .map(|_v| ())
+        .map_err(|e| e.into())
+    }
+
+    /// Lists all objects within a container
+    pub fn list_objects(&self, container: &str) -> HandlerResult<BlobList> {
+        let cmd = Container {
+            id: container.to_string(),
+        };
+        host_call(
+            &self.binding,
+            CAPID_BLOBSTORE,
+            OP_LIST_OBJECTS,
+            &serialize(cmd)?,
+        )
+        .map(|v| deserialize::<BlobList>(v.as_ref()).unwrap())
+        .map_err(|e| e.into())
+    }
+
+    /// Retrieves a single page of at most `max` objects from a container. Pass `None` to
+    /// retrieve the first page, then the continuation token of each page to retrieve the
+    /// next, until a page is returned without a token
+    pub fn list_objects_paged(
+        &self,
+        container: &str,
+        continuation_token: Option<String>,
+        max: u32,
+    ) -> HandlerResult<BlobPage> {
+        let cmd = ListRequest {
+            container: container.to_string(),
+            continuation_token: continuation_token.unwrap_or_default(),
+            max_results: max,
+        };
+        host_call(
+            &self.binding,
+            CAPID_BLOBSTORE,
+            OP_LIST_OBJECTS_PAGED,
+            &serialize(cmd)?,
+        )
+        .map_err(|e| e.into())
+        .and_then(|v| deserialize::<BlobPage>(v.as_ref()))
+    }
+
+    /// Replaces the metadata attached to a blob
+    pub fn set_blob_metadata(
+        &self,
+        container: &str,
+        id: &str,
+        metadata: HashMap<String, String>,
+    ) -> HandlerResult<()> {
+        let cmd = BlobMetadata {
+            id: id.to_string(),
+            container: container.to_string(),
+            metadata,
+        };
+        host_call(
+            &self.binding,
+            CAPID_BLOBSTORE,
+            OP_SET_OBJECT_METADATA,
+            &serialize(cmd)?,
+        )
+        .map(|_v| ())
+        .map_err(|e| e.into())
+    }
+
+    /// Obtains the metadata attached to a blob
+    pub fn get_blob_metadata(
+        &self,
+        container: &str,
+        id: &str,
+    ) -> HandlerResult<HashMap<String, String>> {
+        let cmd = Blob {
+            id: id.to_string(),
+            container: container.to_string(),
+            byte_size: 0,
+        };
+        host_call(
+            &self.binding,
+            CAPID_BLOBSTORE,
+            OP_GET_OBJECT_METADATA,
+            &serialize(cmd)?,
+        )
+        .map_err(|e| e.into())
+        .and_then(|v| deserialize::<BlobMetadata>(v.as_ref()))
+        .map(|m| m.metadata)
+    }
+
+    /// Lists the objects within a container whose metadata contains every entry of the filter
+    pub fn list_objects_by_metadata(
+        &self,
+        container: &str,
+        filter: HashMap<String, String>,
+    ) -> HandlerResult<BlobList> {
+        let cmd = MetadataQuery {
+            container: container.to_string(),
+            filter,
+        };
+        host_call(
+            &self.binding,
+            CAPID_BLOBSTORE,
+            OP_LIST_OBJECTS_BY_METADATA,
+            &serialize(cmd)?,
+        )
+        .map_err(|e| e.into())
+        .and_then(|v| deserialize::<BlobList>(v.as_ref()))
+    }
+
+    /// Copies a blob within the store (possibly between containers) without downloading and
+    /// re-uploading its contents. Any existing blob at the destination is replaced
+    pub fn copy_object(
+        &self,
+        src_container: &str,
+        src_id: &str,
+        dst_container: &str,
+        dst_id: &str,
+    ) -> HandlerResult<Blob> {
+        let cmd = CopyRequest {
+            source_container: src_container.to_string(),
+            source_id: src_id.to_string(),
+            destination_container: dst_container.to_string(),
+            destination_id: dst_id.to_string(),
+        };
+        host_call(
+            &self.binding,
+            CAPID_BLOBSTORE,
+            OP_COPY_OBJECT,
+            &serialize(cmd)?,
+        )
+        .map_err(|e| e.into())
+        .and_then(|v| deserialize::<Blob>(v.as_ref()))
+    }
+
+    /// Renames a blob within a container. The provider's native move operation is used if it
+    /// has one, otherwise the blob is copied to its new name and the original is removed.
+    /// That fallback is not atomic: if removing the original fails, the blob is left under
+    /// both names and the error is returned. Any other failure of the native operation is
+    /// returned without falling back
+    pub fn move_object(&self, container: &str, old_id: &str, new_id: &str) -> HandlerResult<Blob> {
+        let cmd = CopyRequest {
+            source_container: container.to_string(),
+            source_id: old_id.to_string(),
+            destination_container: container.to_string(),
+            destination_id: new_id.to_string(),
+        };
+        match host_call(
+            &self.binding,
+            CAPID_BLOBSTORE,
+            OP_MOVE_OBJECT,
+            &serialize(cmd)?,
+        ) {
+            Ok(v) => deserialize::<Blob>(v.as_ref()),
+            Err(e) => {
+                let e: Box<dyn std::error::Error + Send + Sync> = e.into();
+                if !crate::errors::is_unsupported_operation(e.as_ref()) {
+                    return Err(e);
+                }
+                let blob = self.copy_object(container, old_id, container, new_id)?;
+                self.remove_object(old_id, container)?;
+                Ok(blob)
+            }
+        }
+    }
+
+    /// Asks the provider for a URL through which the blob can be downloaded directly (e.g. by
+    /// a browser) for the given number of seconds, without proxying its bytes through the actor
+    pub fn presign_get(
+        &self,
+        container: &str,
+        id: &str,
+        expires_secs: u64,
+    ) -> HandlerResult<String> {
+        self.presign(container, id, "GET", expires_secs)
+    }
+
+    /// Asks the provider for a URL through which the blob can be uploaded directly for the
+    /// given number of seconds
+    pub fn presign_put(
+        &self,
+        container: &str,
+        id: &str,
+        expires_secs: u64,
+    ) -> HandlerResult<String> {
+        self.presign(container, id, "PUT", expires_secs)
+    }
+
+    fn presign(
+        &self,
+        container: &str,
+        id: &str,
+        method: &str,
+        expires_secs: u64,
+    ) -> HandlerResult<String> {
+        let cmd = PresignRequest {
+            id: id.to_string(),
+            container: container.to_string(),
+            method: method.to_string(),
+            expires_s: expires_secs,
+        };
+        host_call(
+            &self.binding,
+            CAPID_BLOBSTORE,
+            OP_PRESIGN_URL,
+            &serialize(cmd)?,
+        )
+        .map_err(|e| e.into())
+        .and_then(|v| deserialize::<PresignedUrl>(v.as_ref()))
+        .map(|p| p.url)
+    }
+
+    /// Obtains binary object metadata, does not include the object bytes
+    pub fn get_blob_info(&self, container: &str, id: &str) -> HandlerResult<Option<Blob>> {
+        Ok(self
+            .get_blob_details(container, id)?
+            .map(|info| info.into_blob()))
+    }
+
+    /// Obtains binary object metadata like `get_blob_info`, along with the blob's content type
+    /// (if one was supplied when it was uploaded) and the provider's entity tag (if it assigns
+    /// one). Does not include the object bytes
+    pub fn get_blob_details(&self, container: &str, id: &str) -> HandlerResult<Option<BlobInfo>> {
+        let cmd = Blob {
+            id: id.to_string(),
+            container: container.to_string(),
+            byte_size: 0,
+        };
+        host_call(
+            &self.binding,
+            CAPID_BLOBSTORE,
+            OP_GET_OBJECT_INFO,
+            &serialize(cmd)?,
+        )
+        .map_err(|e| e.into())
+        .and_then(|v| deserialize::<TypedBlob>(v.as_ref()))
+        .map(|b| {
+            if b.id.is_empty() {
+                None
+            } else {
+                Some(b.into())
+            }
+        })
+    }
+
+    /// Retrieves the complete contents of a blob, or `None` if the blob does not exist. The
+    /// blob is fetched chunk by chunk and reassembled in memory, so this should only be used
+    /// for objects that comfortably fit within the actor's memory. Use `start_download` for
+    /// larger objects
+    pub fn get_blob(&self, container: &str, id: &str) -> HandlerResult<Option<Vec<u8>>> {
+        self.get_blob_with_progress(container, id, |_, _| {})
+    }
+
+    /// Retrieves the complete contents of a blob like `get_blob`, invoking the progress
+    /// callback with the number of bytes received so far and the total size of the blob
+    /// after each chunk arrives
+    pub fn get_blob_with_progress<F>(
+        &self,
+        container: &str,
+        id: &str,
+        mut on_progress: F,
+    ) -> HandlerResult<Option<Vec<u8>>>
+    where
+        F: FnMut(u64, u64),
+    {
+        let blob = match self.get_blob_info(container, id)? {
+            Some(b) => b,
+            None => return Ok(None),
+        };
+        let mut bytes = Vec::with_capacity(blob.byte_size as usize);
+        let request = ChunkRequest::for_blob(&blob, DEFAULT_CHUNK_SIZE, 0);
+        self.read_chunks(request, blob.byte_size, |chunk| {
+            bytes.extend_from_slice(chunk);
+            on_progress(bytes.len() as u64, blob.byte_size);
+        })?;
+        Ok(Some(bytes))
+    }
+
+    /// Reads a blob from the store and verifies that its SHA-256 digest matches the expected
+    /// (hex-encoded) digest. Returns `false` if the digests differ or the blob does not exist
+    pub fn verify_blob(
+        &self,
+        container: &str,
+        id: &str,
+        expected_digest: &str,
+    ) -> HandlerResult<bool> {
+        let blob = match self.get_blob_info(container, id)? {
+            Some(b) => b,
+            None => return Ok(false),
+        };
+        let mut hasher = Sha256::new();
+        let request = ChunkRequest::for_blob(&blob, DEFAULT_CHUNK_SIZE, 0);
+        self.read_chunks(request, blob.byte_size, |chunk| hasher.update(chunk))?;
+        Ok(to_hex(&hasher.finalize()).eq_ignore_ascii_case(expected_digest))
+    }
+
+    /// Lists the stored versions of a blob, newest first. Providers that do not keep object
+    /// versions may return only the current version
+    pub fn list_versions(&self, container: &str, id: &str) -> HandlerResult<Vec<BlobVersion>> {
+        let cmd = Blob {
+            id: id.to_string(),
+            container: container.to_string(),
+            byte_size: 0,
+        };
+        host_call(
+            &self.binding,
+            CAPID_BLOBSTORE,
+            OP_LIST_OBJECT_VERSIONS,
             &serialize(cmd)?,
         )
-        .map(|v| deserialize::<Container>(v.as_ref()).unwrap())
         .map_err(|e| e.into())
+        .and_then(|v| deserialize::<VersionList>(v.as_ref()))
+        .map(|list| list.versions)
     }
 
-    /// Removes a container from the store. Whether or not this will fail if the container
-    /// has items may be specific to a given provider implementation.
-    pub fn remove_container(&self, name: &str) -> HandlerResult<()> {
-        let cmd = Container {
-            id: name.to_string(),
+    /// Obtains information about a single version of a blob, or `None` if the version does
+    /// not exist
+    pub fn get_version_info(
+        &self,
+        container: &str,
+        id: &str,
+        version_id: &str,
+    ) -> HandlerResult<Option<BlobVersion>> {
+        let cmd = ObjectVersion {
+            id: id.to_string(),
+            container: container.to_string(),
+            version_id: version_id.to_string(),
         };
         host_call(
             &self.binding,
             CAPID_BLOBSTORE,
-            OP_REMOVE_CONTAINER,
+            OP_GET_OBJECT_VERSION_INFO,
             &serialize(cmd)?,
         )
-        .map(|_v| ())
         .map_err(|e| e.into())
+        .and_then(|v| deserialize::<BlobVersion>(v.as_ref()))
+        .map(|b| if b.id.is_empty() { None } else { Some(b) })
     }
 
-    /// Removes an object from a container
-    pub fn remove_object(&self, name: &str, container: &str) -> crate::HandlerResult<()> {
-        let cmd = Blob {
-            id: name.to_string(),
+    /// Retrieves the complete contents of a specific version of a blob, or `None` if the
+    /// version does not exist. Like `get_blob`, the version is reassembled in memory
+    pub fn get_blob_version(
+        &self,
+        container: &str,
+        id: &str,
+        version_id: &str,
+    ) -> HandlerResult<Option<Vec<u8>>> {
+        let version = match self.get_version_info(container, id, version_id)? {
+            Some(v) => v,
+            None => return Ok(None),
+        };
+        let request = ChunkRequest {
+            id: version.id,
+            container: version.container,
+            chunk_size: DEFAULT_CHUNK_SIZE,
+            sequence_no: 0,
+            offset: 0,
+            version_id: version.version_id,
+        };
+        let mut bytes = Vec::with_capacity(version.byte_size as usize);
+        self.read_chunks(request, version.byte_size, |chunk| {
+            bytes.extend_from_slice(chunk)
+        })?;
+        Ok(Some(bytes))
+    }
+
+    /// Permanently removes a specific version of a blob. Removing the current version makes
+    /// the next most recent version current
+    pub fn remove_object_version(
+        &self,
+        container: &str,
+        id: &str,
+        version_id: &str,
+    ) -> HandlerResult<()> {
+        let cmd = ObjectVersion {
+            id: id.to_string(),
             container: container.to_string(),
-            byte_size: 0,
+            version_id: version_id.to_string(),
         };
         host_call(
             &self.binding,
             CAPID_BLOBSTORE,
-            OP_REMOVE_OBJECT,
+            OP_REMOVE_OBJECT_VERSION,
             &serialize(cmd)?,
         )
         .map(|_v| ())
         .map_err(|e| e.into())
     }
 
-    /// Lists all objects within a container
-    pub fn list_objects(&self, container: &str) -> HandlerResult<BlobList> {
-        let cmd = Container {
-            id: container.to_string(),
+    /// Retrieves `length` bytes of a blob starting at the given byte offset, or `None` if the
+    /// blob does not exist. The range is truncated if it extends past the end of the blob
+    pub fn get_blob_range(
+        &self,
+        container: &str,
+        id: &str,
+        offset: u64,
+        length: u64,
+    ) -> HandlerResult<Option<Vec<u8>>> {
+        let blob = match self.get_blob_info(container, id)? {
+            Some(b) => b,
+            None => return Ok(None),
         };
+        if offset > blob.byte_size {
+            return Err(format!(
+                "Offset {} is beyond the end of blob '{}' ({} bytes)",
+                offset, id, blob.byte_size
+            )
+            .into());
+        }
+        let length = length.min(blob.byte_size - offset);
+        let mut bytes = Vec::with_capacity(length as usize);
+        let request = ChunkRequest::for_blob(&blob, DEFAULT_CHUNK_SIZE, offset);
+        self.read_chunks(request, length, |chunk| bytes.extend_from_slice(chunk))?;
+        Ok(Some(bytes))
+    }
+
+    fn read_chunks<F>(
+        &self,
+        mut request: ChunkRequest,
+        length: u64,
+        mut on_chunk: F,
+    ) -> HandlerResult<()>
+    where
+        F: FnMut(&[u8]),
+    {
+        let mut received = 0;
+        while received < length {
+            let chunk = self.get_chunk(&request)?;
+            if chunk.sequence_no != request.sequence_no {
+                return Err(format!(
+                    "Expected chunk {} of blob '{}', received chunk {}",
+                    request.sequence_no, request.id, chunk.sequence_no
+                )
+                .into());
+            }
+            if chunk.chunk_bytes.is_empty() {
+                return Err(format!(
+                    "Blob '{}' ended after {} of {} bytes",
+                    request.id, received, length
+                )
+                .into());
+            }
+            let wanted = (length - received).min(chunk.chunk_bytes.len() as u64);
+            received += wanted;
+            request.sequence_no += 1;
+            on_chunk(&chunk.chunk_bytes[..wanted as usize]);
+        }
+        Ok(())
+    }
+
+    fn get_chunk(&self, request: &ChunkRequest) -> HandlerResult<FileChunk> {
         host_call(
             &self.binding,
             CAPID_BLOBSTORE,
-            OP_LIST_OBJECTS,
-            &serialize(cmd)?,
+            OP_GET_OBJECT_CHUNK,
+            &serialize(request)?,
         )
-        .map(|v| deserialize::<BlobList>(v.as_ref()).unwrap())
         .map_err(|e| e.into())
+        .and_then(|v| deserialize::<FileChunk>(v.as_ref()))
     }
 
-    /// Obtains binary object metadata, does not include the object bytes
-    pub fn get_blob_info(&self, container: &str, id: &str) -> HandlerResult<Option<Blob>> {
-        let cmd = Blob {
+    /// Uploads the given bytes as a blob, performing `start_upload` and each `upload_chunk`
+    /// call in order. This is the simplest way to store small to medium sized objects
+    pub fn put_blob(
+        &self,
+        container: &str,
+        id: &str,
+        bytes: &[u8],
+        chunk_size: u64,
+    ) -> HandlerResult<()> {
+        self.put_blob_with_progress(container, id, bytes, chunk_size, |_, _| {})
+    }
+
+    /// Uploads the given bytes as a blob like `put_blob`, then sends their SHA-256 digest to
+    /// the provider with `OP_COMPLETE_UPLOAD` so that it can verify the bytes it stored.
+    /// Returns the hex-encoded digest. The provider must support `OP_COMPLETE_UPLOAD`
+    pub fn put_blob_checksummed(
+        &self,
+        container: &str,
+        id: &str,
+        bytes: &[u8],
+        chunk_size: u64,
+    ) -> HandlerResult<String> {
+        let blob = Blob {
             id: id.to_string(),
             container: container.to_string(),
-            byte_size: 0,
+            byte_size: bytes.len() as u64,
+        };
+        self.upload_bytes(&blob, bytes, chunk_size, None, true, |_, _| {})
+    }
+
+    /// Uploads the given bytes as a blob like `put_blob`, storing the given MIME type with
+    /// the blob so that it is returned by `get_blob_details`
+    pub fn put_blob_with_content_type(
+        &self,
+        container: &str,
+        id: &str,
+        bytes: &[u8],
+        chunk_size: u64,
+        content_type: &str,
+    ) -> HandlerResult<()> {
+        let blob = Blob {
+            id: id.to_string(),
+            container: container.to_string(),
+            byte_size: bytes.len() as u64,
+        };
+        self.upload_bytes(
+            &blob,
+            bytes,
+            chunk_size,
+            Some(content_type),
+            false,
+            |_, _| {},
+        )
+        .map(|_digest| ())
+    }
+
+    /// Uploads the given bytes as a blob like `put_blob`, invoking the progress callback with
+    /// the number of bytes sent so far and the total size of the blob after each chunk
+    pub fn put_blob_with_progress<F>(
+        &self,
+        container: &str,
+        id: &str,
+        bytes: &[u8],
+        chunk_size: u64,
+        on_progress: F,
+    ) -> HandlerResult<()>
+    where
+        F: FnMut(u64, u64),
+    {
+        let blob = Blob {
+            id: id.to_string(),
+            container: container.to_string(),
+            byte_size: bytes.len() as u64,
+        };
+        self.upload_bytes(&blob, bytes, chunk_size, None, false, on_progress)
+            .map(|_digest| ())
+    }
+
+    fn upload_bytes<F>(
+        &self,
+        blob: &Blob,
+        bytes: &[u8],
+        chunk_size: u64,
+        content_type: Option<&str>,
+        complete: bool,
+        mut on_progress: F,
+    ) -> HandlerResult<String>
+    where
+        F: FnMut(u64, u64),
+    {
+        let transfer = self.begin_upload(
+            blob,
+            ChunkingStrategy::Fixed(chunk_size),
+            blob.byte_size,
+            content_type,
+        )?;
+        let mut upload = ChecksummedUpload::new(self, transfer, complete);
+        let mut sent = 0;
+        for chunk in bytes.chunks(upload.transfer().chunk_size as usize) {
+            upload.send_chunk(chunk)?;
+            sent += chunk.len() as u64;
+            on_progress(sent, blob.byte_size);
+        }
+        upload.finish()
+    }
+
+    /// Starts an upload like `start_upload`, returning a `ChecksummedUpload` through which
+    /// the chunks should be sent in order. The SHA-256 digest of the chunks is sent to the
+    /// provider with `OP_COMPLETE_UPLOAD` when the upload is finished, so the provider must
+    /// support that operation
+    pub fn start_checksummed_upload(
+        &self,
+        blob: &Blob,
+        chunk_size: u64,
+        total_bytes: u64,
+    ) -> HandlerResult<ChecksummedUpload<'_>> {
+        let transfer = self.start_upload(blob, chunk_size, total_bytes)?;
+        Ok(ChecksummedUpload::new(self, transfer, true))
+    }
+
+    /// Opens a reader over the contents of a blob, or returns `None` if the blob does not
+    /// exist. Chunks of the given size are fetched from the provider as the reader is drained
+    pub fn chunk_reader(
+        &self,
+        container: &str,
+        id: &str,
+        chunk_size: u64,
+    ) -> HandlerResult<Option<ChunkReader<'_>>> {
+        if chunk_size == 0 {
+            return Err("Chunk size must be greater than zero".into());
+        }
+        Ok(self
+            .get_blob_info(container, id)?
+            .map(|blob| ChunkReader::new(self, blob, chunk_size)))
+    }
+
+    /// Starts an upload and returns a writer through which the blob's contents can be
+    /// written. Pass the size of the blob as `total_bytes`
+    pub fn chunk_writer(
+        &self,
+        container: &str,
+        id: &str,
+        chunk_size: u64,
+        total_bytes: u64,
+    ) -> HandlerResult<ChunkWriter<'_>> {
+        if chunk_size == 0 {
+            return Err("Chunk size must be greater than zero".into());
+        }
+        let blob = Blob {
+            id: id.to_string(),
+            container: container.to_string(),
+            byte_size: total_bytes,
+        };
+        let transfer = self.start_upload(&blob, chunk_size, total_bytes)?;
+        let upload = ChecksummedUpload::new(self, transfer, false);
+        if upload.transfer().chunk_size == 0 {
+            return Err("Provider returned a chunk size of zero".into());
+        }
+        Ok(ChunkWriter::new(upload))
+    }
+
+    /// Indicates that an upload is about to begin for an item. You should follow this
+    /// call up with a for loop/iteration that sends successive chunks to the store. The chunk
+    /// size specified in this call is a request or suggestion. It is up to the provider to determine
+    /// the actual chunk size, which is returned in the resulting `Transfer` instance
+    pub fn start_upload(
+        &self,
+        blob: &Blob,
+        chunk_size: u64,
+        total_bytes: u64,
+    ) -> HandlerResult<Transfer> {
+        self.begin_upload(blob, ChunkingStrategy::Fixed(chunk_size), total_bytes, None)
+    }
+
+    /// Indicates that an upload is about to begin for an item like `start_upload`, choosing
+    /// the chunk size with the given strategy. The effective chunk size and chunk count are
+    /// returned in the resulting `Transfer`
+    pub fn start_upload_with_strategy(
+        &self,
+        blob: &Blob,
+        strategy: ChunkingStrategy,
+        total_bytes: u64,
+    ) -> HandlerResult<Transfer> {
+        self.begin_upload(blob, strategy, total_bytes, None)
+    }
+
+    /// Indicates that an upload is about to begin for an item like `start_upload`, storing
+    /// the given MIME type with the blob so that it is returned by `get_blob_details`
+    pub fn start_upload_with_content_type(
+        &self,
+        blob: &Blob,
+        chunk_size: u64,
+        total_bytes: u64,
+        content_type: &str,
+    ) -> HandlerResult<Transfer> {
+        self.begin_upload(
+            blob,
+            ChunkingStrategy::Fixed(chunk_size),
+            total_bytes,
+            Some(content_type),
+        )
+    }
+
+    fn begin_upload(
+        &self,
+        blob: &Blob,
+        strategy: ChunkingStrategy,
+        total_bytes: u64,
+        content_type: Option<&str>,
+    ) -> HandlerResult<Transfer> {
+        let chunk_size = strategy.chunk_size(total_bytes)?;
+        let cmd = TypedFileChunk {
+            sequence_no: 0,
+            container: blob.container.to_string(),
+            id: blob.id.to_string(),
+            chunk_size,
+            total_bytes,
+            chunk_bytes: vec![],
+            context: None,
+            content_type: content_type.unwrap_or_default().to_string(),
         };
         host_call(
             &self.binding,
             CAPID_BLOBSTORE,
-            OP_GET_OBJECT_INFO,
+            OP_START_UPLOAD,
             &serialize(cmd)?,
         )
+        .map_err(|e| e.into())
         .map(|v| {
-            let b = deserialize::<Blob>(v.as_ref()).unwrap();
-            if b.id.is_empty() {
-                None
-            } else {
-                Some(b)
+            let chunk_size = match strategy {
+                ChunkingStrategy::ProviderSuggested => deserialize::<Transfer>(v.as_ref())
+                    .ok()
+                    .map(|t| t.chunk_size)
+                    .filter(|size| *size > 0)
+                    .unwrap_or(chunk_size),
+                _ => chunk_size,
+            };
+            Transfer {
+                blob_id: blob.id.to_string(),
+                container: blob.container.to_string(),
+                chunk_size,
+                total_size: total_bytes,
+                total_chunks: chunk_count(total_bytes, chunk_size),
+                context: None,
             }
         })
+    }
+
+    /// Continues an interrupted upload, sending the chunks of `bytes` (the complete contents of
+    /// the blob) starting at the chunk with the given sequence number (not a byte offset). The
+    /// transfer and starting chunk are typically recovered with `load_transfer`
+    pub fn resume_upload(
+        &self,
+        transfer: &Transfer,
+        from_chunk: u64,
+        bytes: &[u8],
+    ) -> HandlerResult<()> {
+        if transfer.chunk_size == 0 {
+            return Err("Chunk size must be greater than zero".into());
+        }
+        if bytes.len() as u64 != transfer.total_size {
+            return Err(format!(
+                "Cannot resume upload of '{}', expected {} bytes but was given {}",
+                transfer.blob_id,
+                transfer.total_size,
+                bytes.len()
+            )
+            .into());
+        }
+        for (sequence_no, chunk) in bytes
+            .chunks(transfer.chunk_size as usize)
+            .enumerate()
+            .skip(from_chunk as usize)
+        {
+            self.upload_chunk(transfer, sequence_no as u64, chunk)?;
+        }
+        Ok(())
+    }
+
+    /// Uploads an individual chunk of a file to the blob store. This call must only ever
+    /// come after signaling the start of a new upload with the `start_upload` function.
+    pub fn upload_chunk(
+        &self,
+        transfer: &Transfer,
+        offset: u64,
+        bytes: &[u8],
+    ) -> crate::HandlerResult<()> {
+        let cmd = FileChunk {
+            id: transfer.blob_id.to_string(),
+            container: transfer.container.to_string(),
+            sequence_no: offset,
+            chunk_size: transfer.chunk_size,
+            total_bytes: transfer.total_size,
+            chunk_bytes: bytes.to_vec(),
+            context: None,
+        };
+        host_call(
+            &self.binding,
+            CAPID_BLOBSTORE,
+            OP_UPLOAD_CHUNK,
+            &serialize(cmd)?,
+        )
+        .map(|_v| ())
         .map_err(|e| e.into())
     }
 
-    /// Indicates that an upload is about to begin for an item. You should follow this
-    /// call up with a for loop/iteration that sends successive chunks to the store. The chunk
-    /// size specified in this call is a request or suggestion. It is up to the provider to determine
-    /// the actual chunk size, which is returned in the resulting `Transfer` instance
-    pub fn start_upload(
+    /// Begins an upload whose parts may be sent in any order, and concurrently where the
+    /// host allows it. Each part is sent with `upload_part`, and the upload is finished by
+    /// passing the receipts of every part to `complete_upload`. All parts but the last must
+    /// be exactly `chunk_size` bytes
+    pub fn start_multipart_upload(
         &self,
         blob: &Blob,
         chunk_size: u64,
         total_bytes: u64,
     ) -> HandlerResult<Transfer> {
+        if chunk_size == 0 {
+            return Err("Chunk size must be greater than zero".into());
+        }
         let transfer = Transfer {
             blob_id: blob.id.to_string(),
             container: blob.container.to_string(),
             chunk_size,
             total_size: total_bytes,
-            total_chunks: total_bytes / chunk_size,
+            total_chunks: chunk_count(total_bytes, chunk_size),
             context: None,
         };
         let cmd = FileChunk {
@@ -154,34 +1516,100 @@ impl ObjectStoreHostBinding {
         host_call(
             &self.binding,
             CAPID_BLOBSTORE,
-            OP_START_UPLOAD,
+            OP_START_MULTIPART_UPLOAD,
             &serialize(cmd)?,
         )
         .map(|_v| transfer)
         .map_err(|e| e.into())
     }
 
-    /// Uploads an individual chunk of a file to the blob store. This call must only ever
-    /// come after signaling the start of a new upload with the `start_upload` function.
-    pub fn upload_chunk(
+    /// Sends a single part of a multipart upload. Parts are identified by their index within
+    /// the blob and may be sent in any order. The returned receipt must be included in the
+    /// manifest passed to `complete_upload`
+    pub fn upload_part(
         &self,
         transfer: &Transfer,
-        offset: u64,
+        sequence_no: u64,
         bytes: &[u8],
-    ) -> crate::HandlerResult<()> {
+    ) -> HandlerResult<PartReceipt> {
         let cmd = FileChunk {
             id: transfer.blob_id.to_string(),
             container: transfer.container.to_string(),
-            sequence_no: offset,
+            sequence_no,
             chunk_size: transfer.chunk_size,
             total_bytes: transfer.total_size,
             chunk_bytes: bytes.to_vec(),
-            context: None,
+            context: transfer.context.clone(),
         };
         host_call(
             &self.binding,
             CAPID_BLOBSTORE,
-            OP_UPLOAD_CHUNK,
+            OP_UPLOAD_PART,
+            &serialize(cmd)?,
+        )
+        .map_err(|e| e.into())
+        .and_then(|v| deserialize::<PartReceipt>(v.as_ref()))
+        .map(|receipt| PartReceipt {
+            sequence_no,
+            byte_size: bytes.len() as u64,
+            etag: receipt.etag,
+        })
+    }
+
+    /// Completes a multipart upload. The manifest must contain exactly one receipt for each
+    /// part of the blob, in any order, and the parts must account for the size of the
+    /// transfer
+    pub fn complete_upload(
+        &self,
+        transfer: &Transfer,
+        mut manifest: Vec<PartReceipt>,
+    ) -> HandlerResult<()> {
+        manifest.sort_by_key(|p| p.sequence_no);
+        for (expected, part) in manifest.iter().enumerate() {
+            if part.sequence_no != expected as u64 {
+                return Err(format!(
+                    "Manifest for '{}' is missing part {}",
+                    transfer.blob_id, expected
+                )
+                .into());
+            }
+        }
+        let total_bytes: u64 = manifest.iter().map(|p| p.byte_size).sum();
+        if total_bytes != transfer.total_size {
+            return Err(format!(
+                "Manifest for '{}' accounts for {} of {} bytes",
+                transfer.blob_id, total_bytes, transfer.total_size
+            )
+            .into());
+        }
+        let cmd = UploadManifest {
+            id: transfer.blob_id.to_string(),
+            container: transfer.container.to_string(),
+            total_bytes,
+            parts: manifest,
+        };
+        host_call(
+            &self.binding,
+            CAPID_BLOBSTORE,
+            OP_COMPLETE_MULTIPART_UPLOAD,
+            &serialize(cmd)?,
+        )
+        .map(|_v| ())
+        .map_err(|e| e.into())
+    }
+
+    /// Abandons a multipart upload, allowing the provider to discard any parts it has
+    /// already received
+    pub fn abort_multipart_upload(&self, transfer: &Transfer) -> HandlerResult<()> {
+        let cmd = Blob {
+            id: transfer.blob_id.to_string(),
+            container: transfer.container.to_string(),
+            byte_size: transfer.total_size,
+        };
+        host_call(
+            &self.binding,
+            CAPID_BLOBSTORE,
+            OP_ABORT_MULTIPART_UPLOAD,
             &serialize(cmd)?,
         )
         .map(|_v| ())
@@ -202,7 +1630,7 @@ impl ObjectStoreHostBinding {
             container: blob.container.to_string(),
             chunk_size,
             total_size: blob.byte_size,
-            total_chunks: blob.byte_size / chunk_size,
+            total_chunks: chunk_count(blob.byte_size, chunk_size),
             context: context.clone(),
         };
         let cmd = StreamRequest {
@@ -220,4 +1648,47 @@ impl ObjectStoreHostBinding {
         .map(|_v| transfer)
         .map_err(|e| e.into())
     }
+
+    /// Begins a chunked download of `length` bytes of a blob, starting at the given byte
+    /// offset. This works like `start_download`, except that only the requested range is
+    /// streamed to the actor via `OP_RECEIVE_CHUNK`, which is useful for serving HTTP range
+    /// requests or reading file headers. The range is truncated if it extends past the end
+    /// of the blob. Providers that cannot stream ranges fail with an unsupported operation
+    pub fn start_download_range(
+        &self,
+        blob: &Blob,
+        chunk_size: u64,
+        context: Option<String>,
+        offset: u64,
+        length: u64,
+    ) -> crate::HandlerResult<Transfer> {
+        if chunk_size == 0 {
+            return Err("Chunk size must be greater than zero".into());
+        }
+        let length = length.min(blob.byte_size.saturating_sub(offset));
+        let transfer = Transfer {
+            blob_id: blob.id.to_string(),
+            container: blob.container.to_string(),
+            chunk_size,
+            total_size: length,
+            total_chunks: chunk_count(length, chunk_size),
+            context: context.clone(),
+        };
+        let cmd = RangedStreamRequest {
+            container: blob.container.to_string(),
+            id: blob.id.to_string(),
+            chunk_size,
+            context,
+            offset,
+            length,
+        };
+        host_call(
+            &self.binding,
+            CAPID_BLOBSTORE,
+            OP_START_DOWNLOAD_RANGE,
+            &serialize(cmd)?,
+        )
+        .map(|_v| transfer)
+        .map_err(|e| e.into())
+    }
 }
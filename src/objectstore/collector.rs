@@ -0,0 +1,207 @@
+//! # Download Collection
+//!
+//! This module contains a helper for reassembling blobs from the chunks delivered to an
+//! actor via `OP_RECEIVE_CHUNK` after a call to `start_download`
+
+use super::chunk_count;
+use crate::keyvalue::KeyValueStoreHostBinding;
+use crate::HandlerResult;
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use wascc_codec::blobstore::FileChunk;
+use wascc_codec::deserialize;
+
+/// Collects the chunks of one or more in-progress downloads. Chunks may arrive in any
+/// order; the collector tracks which sequence numbers have been received, reports gaps,
+/// and hands back the assembled blob once every chunk has arrived. Because each chunk
+/// arrives in its own handler invocation, actors will typically keep a collector in a static.
+///
+/// By default, received chunks are held in memory. A collector can instead spill chunks into
+/// the key-value store, keeping actor memory usage flat and allowing a download to continue
+/// if the actor is restarted part way through.
+#[derive(Default)]
+pub struct DownloadCollector {
+    downloads: HashMap<String, PartialDownload>,
+    spill: Option<KeyValueSpill>,
+}
+
+struct KeyValueSpill {
+    store: KeyValueStoreHostBinding,
+    prefix: String,
+}
+
+struct PartialDownload {
+    total_bytes: u64,
+    chunk_size: u64,
+    received: BTreeSet<u64>,
+    chunks: BTreeMap<u64, Vec<u8>>,
+}
+
+impl PartialDownload {
+    fn expected_chunks(&self) -> u64 {
+        chunk_count(self.total_bytes, self.chunk_size)
+    }
+}
+
+impl DownloadCollector {
+    /// Creates a collector that holds received chunks in memory
+    pub fn new() -> DownloadCollector {
+        DownloadCollector::default()
+    }
+
+    /// Creates a collector that stores received chunks in the key-value store, under keys
+    /// beginning with the given prefix
+    pub fn with_kv_spill(store: KeyValueStoreHostBinding, prefix: &str) -> DownloadCollector {
+        DownloadCollector {
+            downloads: HashMap::new(),
+            spill: Some(KeyValueSpill {
+                store,
+                prefix: prefix.to_string(),
+            }),
+        }
+    }
+
+    /// Adds a chunk to the collector, returning the complete blob if this chunk finished
+    /// the download
+    pub fn push(&mut self, chunk: FileChunk) -> HandlerResult<Option<Vec<u8>>> {
+        let key = download_key(&chunk.container, &chunk.id);
+        if !self.downloads.contains_key(&key) {
+            let received = self.restore_received(&key)?;
+            self.downloads.insert(
+                key.clone(),
+                PartialDownload {
+                    total_bytes: chunk.total_bytes,
+                    chunk_size: chunk.chunk_size,
+                    received,
+                    chunks: BTreeMap::new(),
+                },
+            );
+        }
+
+        let expected = self.downloads[&key].expected_chunks();
+        if chunk.sequence_no >= expected && chunk.total_bytes > 0 {
+            return Err(format!(
+                "Chunk {} of blob '{}' is out of range, expected {} chunks",
+                chunk.sequence_no, chunk.id, expected
+            )
+            .into());
+        }
+
+        if let Some(ref spill) = self.spill {
+            spill.store.set(
+                &chunk_key(&spill.prefix, &key, chunk.sequence_no),
+                &base64::encode(&chunk.chunk_bytes),
+                None,
+            )?;
+            spill.store.set_add(
+                &index_key(&spill.prefix, &key),
+                &chunk.sequence_no.to_string(),
+            )?;
+        }
+        let download = self.downloads.get_mut(&key).unwrap();
+        download.received.insert(chunk.sequence_no);
+        if self.spill.is_none() {
+            download.chunks.insert(chunk.sequence_no, chunk.chunk_bytes);
+        }
+
+        if download.received.len() as u64 >= expected {
+            self.assemble(&key).map(Some)
+        } else {
+            Ok(None)
+        }
+    }
+
+    /// Adds a raw `OP_RECEIVE_CHUNK` payload to the collector
+    pub fn push_raw(&mut self, msg: &[u8]) -> HandlerResult<Option<Vec<u8>>> {
+        self.push(deserialize::<FileChunk>(msg)?)
+    }
+
+    /// Returns the number of bytes received and the total number of bytes expected for an
+    /// in-progress download
+    pub fn progress(&self, container: &str, id: &str) -> Option<(u64, u64)> {
+        self.downloads.get(&download_key(container, id)).map(|d| {
+            let received = (d.received.len() as u64 * d.chunk_size).min(d.total_bytes);
+            (received, d.total_bytes)
+        })
+    }
+
+    /// Returns the sequence numbers of chunks that have not been received even though a
+    /// later chunk of the same download has been
+    pub fn gaps(&self, container: &str, id: &str) -> Vec<u64> {
+        match self.downloads.get(&download_key(container, id)) {
+            Some(d) => match d.received.iter().next_back() {
+                Some(&highest) => (0..highest).filter(|s| !d.received.contains(s)).collect(),
+                None => vec![],
+            },
+            None => vec![],
+        }
+    }
+
+    /// Discards all state held for an in-progress download
+    pub fn abandon(&mut self, container: &str, id: &str) -> HandlerResult<()> {
+        let key = download_key(container, id);
+        if let Some(download) = self.downloads.remove(&key) {
+            self.remove_spilled(&key, &download)?;
+        }
+        Ok(())
+    }
+
+    fn assemble(&mut self, key: &str) -> HandlerResult<Vec<u8>> {
+        let download = self.downloads.remove(key).unwrap();
+        let bytes = match self.spill {
+            Some(ref spill) => {
+                let mut bytes = Vec::with_capacity(download.total_bytes as usize);
+                for sequence_no in &download.received {
+                    let encoded = spill
+                        .store
+                        .get(&chunk_key(&spill.prefix, key, *sequence_no))?
+                        .ok_or_else(|| format!("Spilled chunk {} is missing", sequence_no))?;
+                    bytes.extend(base64::decode(&encoded)?);
+                }
+                bytes
+            }
+            None => download
+                .chunks
+                .values()
+                .flat_map(|c| c.iter().cloned())
+                .collect(),
+        };
+        self.remove_spilled(key, &download)?;
+        Ok(bytes)
+    }
+
+    fn restore_received(&self, key: &str) -> HandlerResult<BTreeSet<u64>> {
+        match self.spill {
+            Some(ref spill) => Ok(spill
+                .store
+                .set_members(&index_key(&spill.prefix, key))?
+                .iter()
+                .filter_map(|s| s.parse().ok())
+                .collect()),
+            None => Ok(BTreeSet::new()),
+        }
+    }
+
+    fn remove_spilled(&self, key: &str, download: &PartialDownload) -> HandlerResult<()> {
+        if let Some(ref spill) = self.spill {
+            for sequence_no in &download.received {
+                spill
+                    .store
+                    .del_key(&chunk_key(&spill.prefix, key, *sequence_no))?;
+            }
+            spill.store.del_key(&index_key(&spill.prefix, key))?;
+        }
+        Ok(())
+    }
+}
+
+fn download_key(container: &str, id: &str) -> String {
+    format!("{}/{}", container, id)
+}
+
+fn index_key(prefix: &str, download: &str) -> String {
+    format!("{}:{}:chunks", prefix, download)
+}
+
+fn chunk_key(prefix: &str, download: &str, sequence_no: u64) -> String {
+    format!("{}:{}:{}", prefix, download, sequence_no)
+}
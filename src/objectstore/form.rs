@@ -0,0 +1,235 @@
+//! # HTTP Uploads
+//!
+//! This module contains a bridge from HTTP request bodies to the chunked upload API. Plain
+//! bodies are stored as a single blob, while `multipart/form-data` bodies are split into
+//! their parts, with each file part stored as its own blob
+
+use super::ObjectStoreHostBinding;
+use crate::HandlerResult;
+use std::collections::HashMap;
+use wascc_codec::blobstore::Blob;
+use wascc_codec::http::Request;
+
+const MULTIPART_FORM_DATA: &str = "multipart/form-data";
+
+/// A file stored from the body of an HTTP request
+#[derive(Debug, PartialEq)]
+pub struct UploadedFile {
+    /// The name of the form field that carried the file. Empty for non-multipart bodies
+    pub field_name: String,
+    /// The file name supplied by the client, if any
+    pub file_name: Option<String>,
+    /// The MIME type supplied by the client, if any, which is stored with the blob
+    pub content_type: Option<String>,
+    /// The blob in which the file was stored
+    pub blob: Blob,
+    /// Hex-encoded SHA-256 digest of the file's contents
+    pub sha256: String,
+}
+
+/// The result of storing the body of an HTTP request
+#[derive(Debug, PartialEq, Default)]
+pub struct FormUpload {
+    /// The files stored as blobs
+    pub files: Vec<UploadedFile>,
+    /// The plain (non-file) fields of a multipart form
+    pub fields: HashMap<String, String>,
+}
+
+struct Part<'a> {
+    field_name: String,
+    file_name: Option<String>,
+    content_type: Option<String>,
+    body: &'a [u8],
+}
+
+impl ObjectStoreHostBinding {
+    /// Stores the body of an HTTP request in the given container. If the request carries a
+    /// `multipart/form-data` body, every part with a file name is stored as a blob named
+    /// `<id>/<part index>-<field name>` and the remaining parts are returned as plain form
+    /// fields. The part index keeps the blobs of repeated fields (such as multi-file inputs)
+    /// apart, and characters other than ASCII letters, digits, `-`, `_` and `.` in the
+    /// client-supplied field name are replaced with `_`.
+    /// Otherwise the entire body is stored as a single blob with the given ID. The request's
+    /// (or part's) `Content-Type` is stored with each blob
+    pub fn put_request_body(
+        &self,
+        container: &str,
+        id: &str,
+        request: &Request,
+        chunk_size: u64,
+    ) -> HandlerResult<FormUpload> {
+        let content_type = header(request, "content-type");
+        let mut upload = FormUpload::default();
+        match content_type.and_then(multipart_boundary) {
+            Some(boundary) => {
+                for (index, part) in parse_multipart(&request.body, &boundary)?
+                    .into_iter()
+                    .enumerate()
+                {
+                    if part.file_name.is_none() {
+                        upload.fields.insert(
+                            part.field_name,
+                            String::from_utf8_lossy(part.body).to_string(),
+                        );
+                        continue;
+                    }
+                    let blob_id = format!("{}/{}-{}", id, index, sanitize(&part.field_name));
+                    upload.files.push(self.store_part(
+                        container,
+                        &blob_id,
+                        part.body,
+                        chunk_size,
+                        part.field_name,
+                        part.file_name,
+                        part.content_type,
+                    )?);
+                }
+            }
+            None => upload.files.push(self.store_part(
+                container,
+                id,
+                &request.body,
+                chunk_size,
+                "".to_string(),
+                None,
+                content_type.map(|c| c.to_string()),
+            )?),
+        }
+        Ok(upload)
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    fn store_part(
+        &self,
+        container: &str,
+        id: &str,
+        bytes: &[u8],
+        chunk_size: u64,
+        field_name: String,
+        file_name: Option<String>,
+        content_type: Option<String>,
+    ) -> HandlerResult<UploadedFile> {
+        let blob = Blob {
+            id: id.to_string(),
+            container: container.to_string(),
+            byte_size: bytes.len() as u64,
+        };
+        let sha256 = self.upload_bytes(
+            &blob,
+            bytes,
+            chunk_size,
+            content_type.as_deref(),
+            false,
+            |_, _| {},
+        )?;
+        Ok(UploadedFile {
+            field_name,
+            file_name,
+            content_type,
+            blob,
+            sha256,
+        })
+    }
+}
+
+fn sanitize(field_name: &str) -> String {
+    field_name
+        .chars()
+        .map(|c| match c {
+            'a'..='z' | 'A'..='Z' | '0'..='9' | '-' | '_' | '.' => c,
+            _ => '_',
+        })
+        .collect()
+}
+
+fn header<'a>(request: &'a Request, name: &str) -> Option<&'a str> {
+    request
+        .header
+        .iter()
+        .find(|(k, _)| k.eq_ignore_ascii_case(name))
+        .map(|(_, v)| v.as_str())
+}
+
+fn multipart_boundary(content_type: &str) -> Option<String> {
+    let mut params = content_type.split(';');
+    if !params
+        .next()?
+        .trim()
+        .eq_ignore_ascii_case(MULTIPART_FORM_DATA)
+    {
+        return None;
+    }
+    params
+        .filter_map(|p| header_param(p, "boundary"))
+        .next()
+        .filter(|b| !b.is_empty())
+}
+
+fn header_param(param: &str, name: &str) -> Option<String> {
+    let mut pair = param.trim().splitn(2, '=');
+    let key = pair.next()?.trim();
+    let value = pair.next()?.trim();
+    if key.eq_ignore_ascii_case(name) {
+        Some(value.trim_matches('"').to_string())
+    } else {
+        None
+    }
+}
+
+fn parse_multipart<'a>(body: &'a [u8], boundary: &str) -> HandlerResult<Vec<Part<'a>>> {
+    let delimiter = format!("--{}", boundary).into_bytes();
+    let mut parts = Vec::new();
+    let mut rest = match find(body, &delimiter) {
+        Some(pos) => &body[pos + delimiter.len()..],
+        None => return Err("Multipart body does not contain the boundary".into()),
+    };
+    loop {
+        if rest.starts_with(b"--") {
+            return Ok(parts);
+        }
+        rest = rest
+            .strip_prefix(b"\r\n")
+            .ok_or("Malformed multipart boundary line")?;
+        let header_end =
+            find(rest, b"\r\n\r\n").ok_or("Multipart part is missing its header terminator")?;
+        let headers = String::from_utf8_lossy(&rest[..header_end]).to_string();
+        rest = &rest[header_end + 4..];
+
+        let mut closing = b"\r\n".to_vec();
+        closing.extend_from_slice(&delimiter);
+        let body_end =
+            find(rest, &closing).ok_or("Multipart body is missing its final boundary")?;
+        parts.push(parse_part(&headers, &rest[..body_end])?);
+        rest = &rest[body_end + closing.len()..];
+    }
+}
+
+fn parse_part<'a>(headers: &str, body: &'a [u8]) -> HandlerResult<Part<'a>> {
+    let mut field_name = None;
+    let mut file_name = None;
+    let mut content_type = None;
+    for line in headers.split("\r\n") {
+        let mut pair = line.splitn(2, ':');
+        let name = pair.next().unwrap_or_default().trim();
+        let value = pair.next().unwrap_or_default().trim();
+        if name.eq_ignore_ascii_case("content-disposition") {
+            for param in value.split(';').skip(1) {
+                field_name = field_name.or_else(|| header_param(param, "name"));
+                file_name = file_name.or_else(|| header_param(param, "filename"));
+            }
+        } else if name.eq_ignore_ascii_case("content-type") {
+            content_type = Some(value.to_string());
+        }
+    }
+    Ok(Part {
+        field_name: field_name.ok_or("Multipart part has no field name")?,
+        file_name,
+        content_type,
+        body,
+    })
+}
+
+fn find(haystack: &[u8], needle: &[u8]) -> Option<usize> {
+    haystack.windows(needle.len()).position(|w| w == needle)
+}
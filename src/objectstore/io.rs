@@ -0,0 +1,133 @@
+//! # Blob Readers and Writers
+//!
+//! This module contains adapters that expose blobs through the standard `Read` and `Write`
+//! traits, so that existing code which works with readers and writers (compression, parsing,
+//! hashing) can operate on blobstore objects without holding them entirely in memory
+
+use super::{ChecksummedUpload, ChunkRequest, ObjectStoreHostBinding};
+use crate::HandlerResult;
+use std::io::{self, Read, Write};
+use wascc_codec::blobstore::{Blob, Transfer};
+
+/// Reads the contents of a blob, fetching one chunk at a time from the provider as the
+/// reader is drained. Obtained from `ObjectStoreHostBinding::chunk_reader`
+pub struct ChunkReader<'a> {
+    store: &'a ObjectStoreHostBinding,
+    blob: Blob,
+    request: ChunkRequest,
+    received: u64,
+    buffer: Vec<u8>,
+    position: usize,
+}
+
+impl<'a> ChunkReader<'a> {
+    pub(crate) fn new(
+        store: &'a ObjectStoreHostBinding,
+        blob: Blob,
+        chunk_size: u64,
+    ) -> ChunkReader<'a> {
+        ChunkReader {
+            store,
+            request: ChunkRequest::for_blob(&blob, chunk_size, 0),
+            blob,
+            received: 0,
+            buffer: Vec::new(),
+            position: 0,
+        }
+    }
+
+    /// The blob being read
+    pub fn blob(&self) -> &Blob {
+        &self.blob
+    }
+
+    fn fill_buffer(&mut self) -> io::Result<()> {
+        let chunk = self.store.get_chunk(&self.request).map_err(to_io_error)?;
+        if chunk.chunk_bytes.is_empty() {
+            return Err(io::Error::new(
+                io::ErrorKind::UnexpectedEof,
+                format!(
+                    "Blob '{}' ended after {} of {} bytes",
+                    self.blob.id, self.received, self.blob.byte_size
+                ),
+            ));
+        }
+        self.received += chunk.chunk_bytes.len() as u64;
+        self.request.sequence_no += 1;
+        self.buffer = chunk.chunk_bytes;
+        self.position = 0;
+        Ok(())
+    }
+}
+
+impl<'a> Read for ChunkReader<'a> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if self.position >= self.buffer.len() {
+            if self.received >= self.blob.byte_size {
+                return Ok(0);
+            }
+            self.fill_buffer()?;
+        }
+        let available = &self.buffer[self.position..];
+        let count = available.len().min(buf.len());
+        buf[..count].copy_from_slice(&available[..count]);
+        self.position += count;
+        Ok(count)
+    }
+}
+
+/// Writes the contents of a blob, sending a chunk to the provider each time a full chunk's
+/// worth of bytes has been written. Obtained from `ObjectStoreHostBinding::chunk_writer`.
+///
+/// Because only the last chunk of an upload may be shorter than the chunk size, `flush`
+/// does not send partially filled chunks. The upload must be completed by calling `finish`,
+/// which sends any remaining bytes; dropping the writer abandons the upload
+pub struct ChunkWriter<'a> {
+    upload: ChecksummedUpload<'a>,
+    buffer: Vec<u8>,
+}
+
+impl<'a> ChunkWriter<'a> {
+    pub(crate) fn new(upload: ChecksummedUpload<'a>) -> ChunkWriter<'a> {
+        let capacity = upload.transfer().chunk_size as usize;
+        ChunkWriter {
+            upload,
+            buffer: Vec::with_capacity(capacity),
+        }
+    }
+
+    /// The transfer being performed
+    pub fn transfer(&self) -> &Transfer {
+        self.upload.transfer()
+    }
+
+    /// Sends any buffered bytes as the final chunk and completes the upload. Returns the
+    /// hex-encoded SHA-256 digest of everything written
+    pub fn finish(mut self) -> HandlerResult<String> {
+        if !self.buffer.is_empty() {
+            self.upload.send_chunk(&self.buffer)?;
+        }
+        self.upload.finish()
+    }
+}
+
+impl<'a> Write for ChunkWriter<'a> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let chunk_size = self.upload.transfer().chunk_size as usize;
+        let count = (chunk_size - self.buffer.len()).min(buf.len());
+        self.buffer.extend_from_slice(&buf[..count]);
+        if self.buffer.len() == chunk_size {
+            self.upload.send_chunk(&self.buffer).map_err(to_io_error)?;
+            self.buffer.clear();
+        }
+        Ok(count)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+fn to_io_error(e: Box<dyn std::error::Error + Send + Sync>) -> io::Error {
+    io::Error::other(e)
+}
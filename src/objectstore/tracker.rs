@@ -0,0 +1,156 @@
+//! # Transfer Tracking
+//!
+//! This module contains a tracker that records the progress of in-flight uploads and
+//! downloads in the key-value store, so that an actor can find and resume them after it has
+//! been restarted
+
+use super::{load_transfer, save_transfer, SavedTransfer};
+use crate::keyvalue::KeyValueStoreHostBinding;
+use crate::HandlerResult;
+use serde_derive::{Deserialize, Serialize};
+use wascc_codec::blobstore::Transfer;
+
+/// The direction in which a tracked transfer moves data
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize, Serialize)]
+pub enum Direction {
+    /// The actor is sending the blob to the store
+    Upload,
+    /// The store is sending the blob to the actor
+    Download,
+}
+
+/// The recorded state of a transfer. Progress is derived from the highest chunk recorded, so
+/// recording a chunk more than once doesn't inflate it
+#[derive(Debug, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TrackedTransfer {
+    /// The ID under which the transfer is tracked
+    pub transfer_id: String,
+    /// The direction of the transfer
+    pub direction: Direction,
+    /// The transfer being performed
+    pub transfer: Transfer,
+    /// The number of bytes transferred so far
+    pub bytes_done: u64,
+    /// The sequence number of the most recently transferred chunk, if any
+    pub last_chunk: Option<u64>,
+}
+
+impl TrackedTransfer {
+    /// The sequence number of the chunk from which the transfer should continue
+    pub fn next_sequence_no(&self) -> u64 {
+        self.last_chunk.map_or(0, |c| c + 1)
+    }
+
+    /// Indicates whether every byte of the transfer has been recorded
+    pub fn is_complete(&self) -> bool {
+        self.bytes_done >= self.transfer.total_size
+    }
+}
+
+/// Records active transfers in the key-value store. Each transfer is saved with
+/// `save_transfer` under its own key beneath the tracker's prefix, and the IDs of all active
+/// transfers are kept in a set so that they can be enumerated after a restart
+pub struct TransferTracker {
+    store: KeyValueStoreHostBinding,
+    prefix: String,
+}
+
+impl TransferTracker {
+    /// Creates a tracker that stores its records under keys beginning with the given prefix
+    pub fn new(store: KeyValueStoreHostBinding, prefix: &str) -> TransferTracker {
+        TransferTracker {
+            store,
+            prefix: prefix.to_string(),
+        }
+    }
+
+    /// Starts tracking a transfer, returning the ID under which it is tracked. Tracking a
+    /// transfer that is already tracked resets its progress
+    pub fn track(&self, direction: Direction, transfer: &Transfer) -> HandlerResult<String> {
+        let transfer_id = transfer_id(direction, transfer);
+        save_transfer(&self.store, &self.record_key(&transfer_id), transfer, 0)?;
+        self.store.set_add(&self.index_key(), &transfer_id)?;
+        Ok(transfer_id)
+    }
+
+    /// Records that the chunk with the given sequence number has been transferred, returning
+    /// the updated state of the transfer. Recording a chunk again (after a redelivery or a
+    /// restart) has no effect
+    pub fn record_chunk(
+        &self,
+        transfer_id: &str,
+        sequence_no: u64,
+    ) -> HandlerResult<TrackedTransfer> {
+        let key = self.record_key(transfer_id);
+        let mut saved = load_transfer(&self.store, &key)?
+            .ok_or_else(|| format!("Transfer '{}' is not being tracked", transfer_id))?;
+        let next_sequence_no = sequence_no.saturating_add(1);
+        if next_sequence_no > saved.next_sequence_no {
+            saved.next_sequence_no = next_sequence_no;
+            save_transfer(&self.store, &key, &saved.transfer, next_sequence_no)?;
+        }
+        Ok(tracked(transfer_id, saved))
+    }
+
+    /// Retrieves the recorded state of a transfer, or `None` if it is not being tracked
+    pub fn get(&self, transfer_id: &str) -> HandlerResult<Option<TrackedTransfer>> {
+        Ok(load_transfer(&self.store, &self.record_key(transfer_id))?
+            .map(|saved| tracked(transfer_id, saved)))
+    }
+
+    /// Retrieves the recorded state of every tracked transfer, from which in-flight
+    /// transfers can be reconstructed after a restart
+    pub fn active(&self) -> HandlerResult<Vec<TrackedTransfer>> {
+        let mut transfers = Vec::new();
+        for transfer_id in self.store.set_members(&self.index_key())? {
+            if let Some(tracked) = self.get(&transfer_id)? {
+                transfers.push(tracked);
+            }
+        }
+        Ok(transfers)
+    }
+
+    /// Stops tracking a transfer, typically once it has completed or been abandoned
+    pub fn finish(&self, transfer_id: &str) -> HandlerResult<()> {
+        self.store.del_key(&self.record_key(transfer_id))?;
+        self.store
+            .set_remove(&self.index_key(), transfer_id)
+            .map(|_| ())
+    }
+
+    fn index_key(&self) -> String {
+        format!("{}:transfers", self.prefix)
+    }
+
+    fn record_key(&self, transfer_id: &str) -> String {
+        format!("{}:transfer:{}", self.prefix, transfer_id)
+    }
+}
+
+fn tracked(transfer_id: &str, saved: SavedTransfer) -> TrackedTransfer {
+    let direction = if transfer_id.starts_with("down:") {
+        Direction::Download
+    } else {
+        Direction::Upload
+    };
+    let bytes_done = saved
+        .next_sequence_no
+        .saturating_mul(saved.transfer.chunk_size)
+        .min(saved.transfer.total_size);
+    TrackedTransfer {
+        transfer_id: transfer_id.to_string(),
+        direction,
+        transfer: saved.transfer,
+        bytes_done,
+        last_chunk: saved.next_sequence_no.checked_sub(1),
+    }
+}
+
+fn transfer_id(direction: Direction, transfer: &Transfer) -> String {
+    let direction = match direction {
+        Direction::Upload => "up",
+        Direction::Download => "down",
+    };
+    format!("{}:{}/{}", direction, transfer.container, transfer.blob_id)
+}
@@ -15,11 +15,19 @@
 //! Glob imports for common actor module development
 
 pub use crate::actor_handlers;
+pub use crate::ctx_log;
+pub use crate::message_routes;
 pub use crate::println;
+pub use crate::rpc_service;
 pub use wascc_codec as codec;
 
 pub use crate::errors;
+pub use crate::http::ResponseExt;
 pub use crate::wapc::prelude::CallResult;
 pub use crate::HandlerResult;
-pub use crate::{events, extras, keyvalue, logger, messaging, objectstore, untyped};
+pub use crate::{
+    actors, cache, config, crypto, device, events, extras, flags, graphdb, grpc, http, identity,
+    inference, keyvalue, logger, mail, messaging, notify, objectstore, scheduler, secrets, sql,
+    telemetry, untyped, ws,
+};
 pub use wascc_codec::{deserialize, serialize};
@@ -0,0 +1,206 @@
+//! # Scheduler
+//!
+//! This module contains the client through which actors register recurring and one-shot
+//! jobs with the currently bound `wascc:scheduler` capability provider. When a job fires,
+//! the provider delivers a `ScheduledDelivery` to the actor with the `OP_DELIVER_JOB`
+//! operation, carrying the operation name and payload the job was registered with
+//!
+//! ```
+//! extern crate wascc_actor as actor;
+//! use actor::prelude::*;
+//! use actor::scheduler::ScheduledDelivery;
+//!
+//! actor_handlers! {
+//!     scheduler::OP_DELIVER_JOB => on_job
+//! }
+//!
+//! fn start_purging() -> HandlerResult<String> {
+//!     scheduler::default().schedule("*/5 * * * *", "PurgeExpired", &[])
+//! }
+//!
+//! fn on_job(job: ScheduledDelivery) -> HandlerResult<()> {
+//!     match job.operation.as_str() {
+//!         "PurgeExpired" => Ok(()),
+//!         _ => Err(format!("Unexpected scheduled operation: {}", job.operation).into()),
+//!     }
+//! }
+//! # fn main() {}
+//! ```
+
+use serde::de::DeserializeOwned;
+use serde_derive::{Deserialize, Serialize};
+use wapc_guest::host_call;
+use wascc_codec::{deserialize, serialize};
+
+use crate::HandlerResult;
+
+/// The reserved capability ID for scheduling functionality
+pub const CAPID_SCHEDULER: &str = "wascc:scheduler";
+
+/// Guest sends a `ScheduleRequest` to the capability provider, receives a `ScheduleResponse` back
+pub const OP_SCHEDULE_JOB: &str = "ScheduleJob";
+/// Guest sends a `CancelJobRequest` to the capability provider, receives nothing back
+pub const OP_CANCEL_JOB: &str = "CancelJob";
+/// Capability provider sends a `ScheduledDelivery` to the actor when a job fires, receives
+/// nothing back
+pub const OP_DELIVER_JOB: &str = "DeliverScheduledJob";
+
+/// A request to register a job. Exactly one of `cron` and `delay_ms` is set
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ScheduleRequest {
+    /// A cron expression (minute, hour, day of month, month, day of week) for a recurring job
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cron: Option<String>,
+    /// The number of milliseconds after which a one-shot job fires
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub delay_ms: Option<u64>,
+    /// The operation name delivered to the actor when the job fires
+    pub operation: String,
+    /// The payload delivered to the actor when the job fires
+    #[serde(with = "serde_bytes")]
+    #[serde(default)]
+    pub payload: Vec<u8>,
+}
+
+/// The provider's answer to a `ScheduleRequest`
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ScheduleResponse {
+    /// The ID of the registered job
+    pub job_id: String,
+}
+
+/// A request to cancel a job
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CancelJobRequest {
+    /// The ID of the job
+    pub job_id: String,
+}
+
+/// A job that has fired, delivered to the actor with the `OP_DELIVER_JOB` operation
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ScheduledDelivery {
+    /// The ID of the job
+    pub job_id: String,
+    /// The operation name the job was registered with
+    pub operation: String,
+    /// The payload the job was registered with
+    #[serde(with = "serde_bytes")]
+    #[serde(default)]
+    pub payload: Vec<u8>,
+    /// The time the job was due to fire, in milliseconds since the Unix epoch
+    pub scheduled_at_ms: u64,
+    /// The time the job actually fired, in milliseconds since the Unix epoch
+    pub fired_at_ms: u64,
+}
+
+impl ScheduledDelivery {
+    /// Deserializes the payload, which was registered with `schedule_typed` or
+    /// `schedule_once_typed`
+    pub fn payload_as<T: DeserializeOwned>(&self) -> HandlerResult<T> {
+        deserialize(&self.payload)
+    }
+}
+
+/// An abstraction around a host runtime capability for scheduling jobs
+pub struct SchedulerHostBinding {
+    binding: String,
+}
+
+impl Default for SchedulerHostBinding {
+    fn default() -> Self {
+        SchedulerHostBinding {
+            binding: "default".to_string(),
+        }
+    }
+}
+
+/// Creates a named host binding for the scheduler capability
+pub fn host(binding: &str) -> SchedulerHostBinding {
+    SchedulerHostBinding {
+        binding: binding.to_string(),
+    }
+}
+
+/// Creates the default host binding for the scheduler capability
+pub fn default() -> SchedulerHostBinding {
+    SchedulerHostBinding::default()
+}
+
+impl SchedulerHostBinding {
+    /// Registers a recurring job on a cron schedule, returning the job's ID
+    pub fn schedule(&self, cron: &str, operation: &str, payload: &[u8]) -> HandlerResult<String> {
+        self.register(ScheduleRequest {
+            cron: Some(cron.to_string()),
+            delay_ms: None,
+            operation: operation.to_string(),
+            payload: payload.to_vec(),
+        })
+    }
+
+    /// Registers a recurring job on a cron schedule with a serialized payload
+    pub fn schedule_typed<T: serde::Serialize>(
+        &self,
+        cron: &str,
+        operation: &str,
+        payload: &T,
+    ) -> HandlerResult<String> {
+        self.schedule(cron, operation, &serialize(payload)?)
+    }
+
+    /// Registers a job that fires once after the given number of milliseconds, returning
+    /// the job's ID
+    pub fn schedule_once(
+        &self,
+        delay_ms: u64,
+        operation: &str,
+        payload: &[u8],
+    ) -> HandlerResult<String> {
+        self.register(ScheduleRequest {
+            cron: None,
+            delay_ms: Some(delay_ms),
+            operation: operation.to_string(),
+            payload: payload.to_vec(),
+        })
+    }
+
+    /// Registers a one-shot job with a serialized payload
+    pub fn schedule_once_typed<T: serde::Serialize>(
+        &self,
+        delay_ms: u64,
+        operation: &str,
+        payload: &T,
+    ) -> HandlerResult<String> {
+        self.schedule_once(delay_ms, operation, &serialize(payload)?)
+    }
+
+    /// Cancels a job. Cancelling a one-shot job that has already fired has no effect
+    pub fn cancel(&self, job_id: &str) -> HandlerResult<()> {
+        let cmd = CancelJobRequest {
+            job_id: job_id.to_string(),
+        };
+        host_call(
+            &self.binding,
+            CAPID_SCHEDULER,
+            OP_CANCEL_JOB,
+            &serialize(cmd)?,
+        )
+        .map(|_v| ())
+        .map_err(|e| e.into())
+    }
+
+    fn register(&self, cmd: ScheduleRequest) -> HandlerResult<String> {
+        host_call(
+            &self.binding,
+            CAPID_SCHEDULER,
+            OP_SCHEDULE_JOB,
+            &serialize(cmd)?,
+        )
+        .map_err(|e| e.into())
+        .and_then(|v| deserialize::<ScheduleResponse>(v.as_ref()))
+        .map(|r| r.job_id)
+    }
+}
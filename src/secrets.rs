@@ -0,0 +1,214 @@
+//! # Secrets
+//!
+//! This module contains the client through which actors retrieve credentials, API keys and
+//! other secrets at runtime from the currently bound `wascc:secrets` capability provider
+//! (typically backed by a vault-style secret store), rather than baking them into binding
+//! configuration
+//!
+//! ```
+//! extern crate wascc_actor as actor;
+//! use actor::prelude::*;
+//!
+//! fn payment_api_key() -> HandlerResult<String> {
+//!     let secret = secrets::default()
+//!         .get_secret("payments/api-key")?
+//!         .ok_or("payment API key has not been provisioned")?;
+//!     Ok(secret.value_str()?.to_string())
+//! }
+//! ```
+
+use serde::de::DeserializeOwned;
+use serde_derive::{Deserialize, Serialize};
+use std::fmt;
+use wapc_guest::host_call;
+use wascc_codec::{deserialize, serialize};
+
+use crate::HandlerResult;
+
+/// The reserved capability ID for secrets functionality
+pub const CAPID_SECRETS: &str = "wascc:secrets";
+
+/// Guest sends a `GetSecretRequest` to the capability provider, receives a
+/// `GetSecretResponse` back
+pub const OP_GET_SECRET: &str = "GetSecret";
+/// Guest sends a `RenewLeaseRequest` to the capability provider, receives a `Lease` back
+pub const OP_RENEW_LEASE: &str = "RenewLease";
+/// Guest sends a `RevokeLeaseRequest` to the capability provider, receives nothing back
+pub const OP_REVOKE_LEASE: &str = "RevokeLease";
+
+/// A request for a secret, optionally at a specific version
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct GetSecretRequest {
+    /// The path of the secret in the store
+    pub path: String,
+    /// The version to retrieve, or the latest version if absent
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub version: Option<u64>,
+}
+
+/// The provider's answer to a `GetSecretRequest`
+#[derive(Debug, Clone, PartialEq, Default, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct GetSecretResponse {
+    /// The secret, if it exists (at the requested version)
+    #[serde(default)]
+    pub secret: Option<Secret>,
+}
+
+/// A secret retrieved from the store. Its value is redacted from the `Debug` output
+#[derive(Clone, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Secret {
+    /// The path of the secret in the store
+    pub path: String,
+    /// The version of the secret
+    pub version: u64,
+    /// The secret's value
+    #[serde(with = "serde_bytes")]
+    pub value: Vec<u8>,
+    /// The lease under which the secret was issued, for dynamic secrets that expire
+    #[serde(default)]
+    pub lease: Option<Lease>,
+}
+
+impl Secret {
+    /// The value as UTF-8 text
+    pub fn value_str(&self) -> HandlerResult<&str> {
+        std::str::from_utf8(&self.value)
+            .map_err(|e| format!("Secret {} is not valid UTF-8: {}", self.path, e).into())
+    }
+
+    /// Deserializes the value from JSON, for secrets that hold several related fields
+    /// such as a username and password
+    pub fn deserialize<T: DeserializeOwned>(&self) -> HandlerResult<T> {
+        serde_json::from_slice(&self.value)
+            .map_err(|e| format!("Secret {} is not valid JSON: {}", self.path, e).into())
+    }
+}
+
+impl fmt::Debug for Secret {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Secret")
+            .field("path", &self.path)
+            .field("version", &self.version)
+            .field("value", &"<redacted>")
+            .field("lease", &self.lease)
+            .finish()
+    }
+}
+
+/// The lease under which a dynamic secret was issued. The secret is valid until the lease
+/// expires unless the lease is renewed
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Lease {
+    /// The ID of the lease
+    pub lease_id: String,
+    /// The number of seconds until the lease expires
+    pub ttl_s: u64,
+    /// Indicates whether the lease can be renewed
+    pub renewable: bool,
+}
+
+/// A request to extend a lease
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RenewLeaseRequest {
+    /// The ID of the lease
+    pub lease_id: String,
+    /// The requested number of seconds to extend the lease by. The provider may grant less
+    pub increment_s: u64,
+}
+
+/// A request to revoke a lease, invalidating its secret
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RevokeLeaseRequest {
+    /// The ID of the lease
+    pub lease_id: String,
+}
+
+/// An abstraction around a host runtime capability for a secret store
+pub struct SecretsHostBinding {
+    binding: String,
+}
+
+impl Default for SecretsHostBinding {
+    fn default() -> Self {
+        SecretsHostBinding {
+            binding: "default".to_string(),
+        }
+    }
+}
+
+/// Creates a named host binding for the secrets capability
+pub fn host(binding: &str) -> SecretsHostBinding {
+    SecretsHostBinding {
+        binding: binding.to_string(),
+    }
+}
+
+/// Creates the default host binding for the secrets capability
+pub fn default() -> SecretsHostBinding {
+    SecretsHostBinding::default()
+}
+
+impl SecretsHostBinding {
+    /// Retrieves the latest version of a secret
+    pub fn get_secret(&self, path: &str) -> HandlerResult<Option<Secret>> {
+        self.fetch(path, None)
+    }
+
+    /// Retrieves a specific version of a secret
+    pub fn get_secret_version(&self, path: &str, version: u64) -> HandlerResult<Option<Secret>> {
+        self.fetch(path, Some(version))
+    }
+
+    /// Extends the lease of a dynamic secret, returning the lease with its new time to live
+    pub fn renew_lease(&self, lease_id: &str, increment_s: u64) -> HandlerResult<Lease> {
+        let cmd = RenewLeaseRequest {
+            lease_id: lease_id.to_string(),
+            increment_s,
+        };
+        host_call(
+            &self.binding,
+            CAPID_SECRETS,
+            OP_RENEW_LEASE,
+            &serialize(cmd)?,
+        )
+        .map_err(|e| e.into())
+        .and_then(|v| deserialize::<Lease>(v.as_ref()))
+    }
+
+    /// Revokes the lease of a dynamic secret, invalidating the secret
+    pub fn revoke_lease(&self, lease_id: &str) -> HandlerResult<()> {
+        let cmd = RevokeLeaseRequest {
+            lease_id: lease_id.to_string(),
+        };
+        host_call(
+            &self.binding,
+            CAPID_SECRETS,
+            OP_REVOKE_LEASE,
+            &serialize(cmd)?,
+        )
+        .map(|_v| ())
+        .map_err(|e| e.into())
+    }
+
+    fn fetch(&self, path: &str, version: Option<u64>) -> HandlerResult<Option<Secret>> {
+        let cmd = GetSecretRequest {
+            path: path.to_string(),
+            version,
+        };
+        host_call(
+            &self.binding,
+            CAPID_SECRETS,
+            OP_GET_SECRET,
+            &serialize(cmd)?,
+        )
+        .map_err(|e| e.into())
+        .and_then(|v| deserialize::<GetSecretResponse>(v.as_ref()))
+        .map(|r| r.secret)
+    }
+}
@@ -0,0 +1,372 @@
+//! # SQL Database
+//!
+//! This module contains the SQL client through which actors consume the currently bound
+//! `wascc:sql` capability provider. Statements and their parameters are always sent to the
+//! provider separately, so values never need to be spliced into SQL text. Placeholder
+//! syntax (`$1`, `?`, etc.) is that of the provider's database
+//!
+//! ```
+//! extern crate wascc_actor as actor;
+//! use actor::prelude::*;
+//! use serde_derive::Deserialize;
+//!
+//! #[derive(Deserialize)]
+//! struct Customer {
+//!     id: i64,
+//!     name: String,
+//!     email: Option<String>,
+//! }
+//!
+//! fn customers_named(name: &str) -> HandlerResult<Vec<Customer>> {
+//!     sql::default().query("SELECT id, name, email FROM customers WHERE name = $1", &[name.into()])
+//! }
+//!
+//! fn rename(id: i64, name: &str) -> HandlerResult<u64> {
+//!     sql::default()
+//!         .statement("UPDATE customers SET name = $1 WHERE id = $2")
+//!         .bind(name)
+//!         .bind(id)
+//!         .execute()
+//! }
+//! ```
+
+use serde::de::{self, Deserialize, DeserializeOwned, Deserializer, Visitor};
+
+use serde_derive::{Deserialize, Serialize};
+use serde_json::{Map, Number, Value};
+use std::convert::TryFrom;
+use std::fmt;
+use wapc_guest::host_call;
+use wascc_codec::{deserialize, serialize};
+
+use crate::HandlerResult;
+
+/// The reserved capability ID for SQL database functionality
+pub const CAPID_SQL: &str = "wascc:sql";
+
+/// Guest sends a `StatementRequest` to the capability provider, receives a `QueryResponse` back
+pub const OP_QUERY: &str = "Query";
+/// Guest sends a `StatementRequest` to the capability provider, receives an `ExecuteResponse` back
+pub const OP_EXECUTE: &str = "Execute";
+
+/// A value bound to a statement parameter or read from a result column
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(untagged)]
+pub enum SqlValue {
+    /// SQL `NULL`
+    Null,
+    /// A boolean
+    Bool(bool),
+    /// An integer
+    Int(i64),
+    /// A floating point number
+    Float(f64),
+    /// Text
+    Text(String),
+    /// Binary data
+    Bytes(#[serde(with = "serde_bytes")] Vec<u8>),
+}
+
+macro_rules! sql_value_from(
+    { $($t:ty => $variant:ident as $target:ty),* } => {
+        $(
+            impl From<$t> for SqlValue {
+                fn from(v: $t) -> SqlValue {
+                    SqlValue::$variant(v as $target)
+                }
+            }
+        )*
+    };
+);
+
+sql_value_from! {
+    i8 => Int as i64, i16 => Int as i64, i32 => Int as i64, i64 => Int as i64,
+    u8 => Int as i64, u16 => Int as i64, u32 => Int as i64,
+    f32 => Float as f64, f64 => Float as f64
+}
+
+/// Integers above `i64::MAX` cannot be stored, so `u64` values are converted with
+/// `SqlValue::try_from` rather than `into`
+impl TryFrom<u64> for SqlValue {
+    type Error = std::num::TryFromIntError;
+
+    fn try_from(v: u64) -> Result<SqlValue, Self::Error> {
+        i64::try_from(v).map(SqlValue::Int)
+    }
+}
+
+impl From<bool> for SqlValue {
+    fn from(v: bool) -> SqlValue {
+        SqlValue::Bool(v)
+    }
+}
+
+impl From<&str> for SqlValue {
+    fn from(v: &str) -> SqlValue {
+        SqlValue::Text(v.to_string())
+    }
+}
+
+impl From<String> for SqlValue {
+    fn from(v: String) -> SqlValue {
+        SqlValue::Text(v)
+    }
+}
+
+impl From<&[u8]> for SqlValue {
+    fn from(v: &[u8]) -> SqlValue {
+        SqlValue::Bytes(v.to_vec())
+    }
+}
+
+impl From<Vec<u8>> for SqlValue {
+    fn from(v: Vec<u8>) -> SqlValue {
+        SqlValue::Bytes(v)
+    }
+}
+
+impl<T: Into<SqlValue>> From<Option<T>> for SqlValue {
+    fn from(v: Option<T>) -> SqlValue {
+        v.map(Into::into).unwrap_or(SqlValue::Null)
+    }
+}
+
+// Deserialized by hand rather than derived as untagged, which would decode UTF-8 binary
+// data as text
+impl<'de> Deserialize<'de> for SqlValue {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<SqlValue, D::Error> {
+        deserializer.deserialize_any(SqlValueVisitor)
+    }
+}
+
+struct SqlValueVisitor;
+
+impl<'de> Visitor<'de> for SqlValueVisitor {
+    type Value = SqlValue;
+
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("a SQL value")
+    }
+
+    fn visit_unit<E: de::Error>(self) -> Result<SqlValue, E> {
+        Ok(SqlValue::Null)
+    }
+
+    fn visit_none<E: de::Error>(self) -> Result<SqlValue, E> {
+        Ok(SqlValue::Null)
+    }
+
+    fn visit_some<D: Deserializer<'de>>(self, deserializer: D) -> Result<SqlValue, D::Error> {
+        SqlValue::deserialize(deserializer)
+    }
+
+    fn visit_bool<E: de::Error>(self, v: bool) -> Result<SqlValue, E> {
+        Ok(SqlValue::Bool(v))
+    }
+
+    fn visit_i64<E: de::Error>(self, v: i64) -> Result<SqlValue, E> {
+        Ok(SqlValue::Int(v))
+    }
+
+    fn visit_u64<E: de::Error>(self, v: u64) -> Result<SqlValue, E> {
+        SqlValue::try_from(v)
+            .map_err(|_| E::custom(format!("integer {} is out of range for a SQL value", v)))
+    }
+
+    fn visit_f64<E: de::Error>(self, v: f64) -> Result<SqlValue, E> {
+        Ok(SqlValue::Float(v))
+    }
+
+    fn visit_str<E: de::Error>(self, v: &str) -> Result<SqlValue, E> {
+        Ok(SqlValue::Text(v.to_string()))
+    }
+
+    fn visit_string<E: de::Error>(self, v: String) -> Result<SqlValue, E> {
+        Ok(SqlValue::Text(v))
+    }
+
+    fn visit_bytes<E: de::Error>(self, v: &[u8]) -> Result<SqlValue, E> {
+        Ok(SqlValue::Bytes(v.to_vec()))
+    }
+
+    fn visit_byte_buf<E: de::Error>(self, v: Vec<u8>) -> Result<SqlValue, E> {
+        Ok(SqlValue::Bytes(v))
+    }
+}
+
+impl SqlValue {
+    fn to_json(&self) -> Value {
+        match self {
+            SqlValue::Null => Value::Null,
+            SqlValue::Bool(b) => Value::Bool(*b),
+            SqlValue::Int(i) => Value::Number((*i).into()),
+            SqlValue::Float(f) => Number::from_f64(*f).map_or(Value::Null, Value::Number),
+            SqlValue::Text(s) => Value::String(s.clone()),
+            SqlValue::Bytes(b) => Value::Array(b.iter().map(|b| Value::from(*b)).collect()),
+        }
+    }
+}
+
+/// A statement and the values bound to its parameters, in order
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct StatementRequest {
+    /// The text of the statement, with placeholders for its parameters
+    pub sql: String,
+    /// The values of the parameters
+    pub params: Vec<SqlValue>,
+}
+
+/// The rows produced by a query
+#[derive(Debug, Clone, PartialEq, Default, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct QueryResponse {
+    /// The names of the result columns
+    pub columns: Vec<String>,
+    /// The rows, each holding one value per column
+    pub rows: Vec<Vec<SqlValue>>,
+}
+
+impl QueryResponse {
+    /// Decodes each row into a type whose fields are named after the result columns. Fails if
+    /// two columns share a name (alias them in the query to tell them apart)
+    pub fn decode<T: DeserializeOwned>(&self) -> HandlerResult<Vec<T>> {
+        for (i, column) in self.columns.iter().enumerate() {
+            if self.columns[..i].contains(column) {
+                return Err(
+                    format!("Column {} appears more than once in the result", column).into(),
+                );
+            }
+        }
+        self.rows
+            .iter()
+            .map(|row| {
+                let record: Map<String, Value> = self
+                    .columns
+                    .iter()
+                    .cloned()
+                    .zip(row.iter().map(SqlValue::to_json))
+                    .collect();
+                serde_json::from_value(Value::Object(record))
+                    .map_err(|e| format!("Failed to decode row: {}", e).into())
+            })
+            .collect()
+    }
+}
+
+/// The outcome of a statement that does not produce rows
+#[derive(Debug, Clone, PartialEq, Default, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ExecuteResponse {
+    /// The number of rows inserted, updated or deleted
+    pub rows_affected: u64,
+}
+
+/// An abstraction around a host runtime capability for a SQL database
+pub struct SqlHostBinding {
+    binding: String,
+}
+
+impl Default for SqlHostBinding {
+    fn default() -> Self {
+        SqlHostBinding {
+            binding: "default".to_string(),
+        }
+    }
+}
+
+/// Creates a named host binding for the SQL database capability
+pub fn host(binding: &str) -> SqlHostBinding {
+    SqlHostBinding {
+        binding: binding.to_string(),
+    }
+}
+
+/// Creates the default host binding for the SQL database capability
+pub fn default() -> SqlHostBinding {
+    SqlHostBinding::default()
+}
+
+impl SqlHostBinding {
+    /// Runs a query, decoding each row into a type whose fields are named after the result
+    /// columns
+    pub fn query<T: DeserializeOwned>(
+        &self,
+        sql: &str,
+        params: &[SqlValue],
+    ) -> HandlerResult<Vec<T>> {
+        self.query_rows(sql, params)?.decode()
+    }
+
+    /// Runs a query expected to produce at most one row
+    pub fn query_opt<T: DeserializeOwned>(
+        &self,
+        sql: &str,
+        params: &[SqlValue],
+    ) -> HandlerResult<Option<T>> {
+        Ok(self.query(sql, params)?.into_iter().next())
+    }
+
+    /// Runs a query, returning the raw columns and rows
+    pub fn query_rows(&self, sql: &str, params: &[SqlValue]) -> HandlerResult<QueryResponse> {
+        let cmd = StatementRequest {
+            sql: sql.to_string(),
+            params: params.to_vec(),
+        };
+        host_call(&self.binding, CAPID_SQL, OP_QUERY, &serialize(cmd)?)
+            .map_err(|e| e.into())
+            .and_then(|v| deserialize::<QueryResponse>(v.as_ref()))
+    }
+
+    /// Executes a statement that does not produce rows, returning the number of rows it
+    /// affected
+    pub fn execute(&self, sql: &str, params: &[SqlValue]) -> HandlerResult<u64> {
+        let cmd = StatementRequest {
+            sql: sql.to_string(),
+            params: params.to_vec(),
+        };
+        host_call(&self.binding, CAPID_SQL, OP_EXECUTE, &serialize(cmd)?)
+            .map_err(|e| e.into())
+            .and_then(|v| deserialize::<ExecuteResponse>(v.as_ref()))
+            .map(|r| r.rows_affected)
+    }
+
+    /// Starts a statement whose parameters are bound one at a time
+    pub fn statement(&self, sql: &str) -> Statement<'_> {
+        Statement {
+            db: self,
+            sql: sql.to_string(),
+            params: Vec::new(),
+        }
+    }
+}
+
+/// A statement with parameters bound in order. Obtained from `SqlHostBinding::statement`
+pub struct Statement<'a> {
+    db: &'a SqlHostBinding,
+    sql: String,
+    params: Vec<SqlValue>,
+}
+
+impl<'a> Statement<'a> {
+    /// Binds the next parameter
+    pub fn bind<T: Into<SqlValue>>(mut self, value: T) -> Self {
+        self.params.push(value.into());
+        self
+    }
+
+    /// Runs the statement as a query, decoding each row
+    pub fn query<T: DeserializeOwned>(&self) -> HandlerResult<Vec<T>> {
+        self.db.query(&self.sql, &self.params)
+    }
+
+    /// Runs the statement as a query expected to produce at most one row
+    pub fn query_opt<T: DeserializeOwned>(&self) -> HandlerResult<Option<T>> {
+        self.db.query_opt(&self.sql, &self.params)
+    }
+
+    /// Executes the statement, returning the number of rows it affected
+    pub fn execute(&self) -> HandlerResult<u64> {
+        self.db.execute(&self.sql, &self.params)
+    }
+}
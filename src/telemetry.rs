@@ -0,0 +1,228 @@
+//! # Telemetry
+//!
+//! This module contains the metrics instruments through which actors record operational
+//! metrics with the currently bound `wascc:telemetry` capability provider. Each
+//! measurement is sent to the provider as it is recorded, along with the instrument's
+//! labels; aggregation and export are the provider's concern
+//!
+//! ```
+//! extern crate wascc_actor as actor;
+//! use actor::prelude::*;
+//!
+//! fn record_order(region: &str, total: f64, queue_depth: usize) -> HandlerResult<()> {
+//!     telemetry::counter("orders_placed").label("region", region).inc(1)?;
+//!     telemetry::histogram("order_total").label("region", region).observe(total)?;
+//!     telemetry::gauge("fulfillment_queue_depth").set(queue_depth as f64)
+//! }
+//! ```
+
+use serde_derive::{Deserialize, Serialize};
+use std::collections::HashMap;
+use wapc_guest::host_call;
+use wascc_codec::serialize;
+
+use crate::extras::Stopwatch;
+use crate::HandlerResult;
+
+/// The reserved capability ID for metrics functionality
+pub const CAPID_TELEMETRY: &str = "wascc:telemetry";
+
+/// Guest sends a `MetricRecord` to the capability provider, receives nothing back
+pub const OP_RECORD_METRIC: &str = "RecordMetric";
+
+/// The kind of instrument that recorded a measurement
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum MetricKind {
+    /// A monotonically increasing count. The value is the amount to add
+    Counter,
+    /// A value that can go up and down. The value replaces the current one
+    Gauge,
+    /// A gauge adjustment. The value is added to the current one, and may be negative
+    GaugeDelta,
+    /// A distribution of values. The value is a single observation
+    Histogram,
+}
+
+/// A single measurement
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct MetricRecord {
+    /// The name of the metric
+    pub name: String,
+    /// The kind of instrument that recorded the measurement
+    pub kind: MetricKind,
+    /// The measured value
+    pub value: f64,
+    /// The labels (dimensions) of the measurement
+    #[serde(default)]
+    pub labels: HashMap<String, String>,
+}
+
+/// An abstraction around a host runtime capability for metrics
+pub struct TelemetryHostBinding {
+    binding: String,
+}
+
+impl Default for TelemetryHostBinding {
+    fn default() -> Self {
+        TelemetryHostBinding {
+            binding: "default".to_string(),
+        }
+    }
+}
+
+/// Creates a named host binding for the telemetry capability
+pub fn host(binding: &str) -> TelemetryHostBinding {
+    TelemetryHostBinding {
+        binding: binding.to_string(),
+    }
+}
+
+/// Creates the default host binding for the telemetry capability
+pub fn default() -> TelemetryHostBinding {
+    TelemetryHostBinding::default()
+}
+
+/// A counter recorded through the default binding
+pub fn counter(name: &str) -> Counter {
+    default().counter(name)
+}
+
+/// A gauge recorded through the default binding
+pub fn gauge(name: &str) -> Gauge {
+    default().gauge(name)
+}
+
+/// A histogram recorded through the default binding
+pub fn histogram(name: &str) -> Histogram {
+    default().histogram(name)
+}
+
+impl TelemetryHostBinding {
+    /// A counter recorded through this binding
+    pub fn counter(&self, name: &str) -> Counter {
+        Counter(self.instrument(name))
+    }
+
+    /// A gauge recorded through this binding
+    pub fn gauge(&self, name: &str) -> Gauge {
+        Gauge(self.instrument(name))
+    }
+
+    /// A histogram recorded through this binding
+    pub fn histogram(&self, name: &str) -> Histogram {
+        Histogram(self.instrument(name))
+    }
+
+    /// Sends a measurement to the provider
+    pub fn record(&self, record: &MetricRecord) -> HandlerResult<()> {
+        host_call(
+            &self.binding,
+            CAPID_TELEMETRY,
+            OP_RECORD_METRIC,
+            &serialize(record)?,
+        )
+        .map(|_v| ())
+        .map_err(|e| e.into())
+    }
+
+    fn instrument(&self, name: &str) -> Instrument {
+        Instrument {
+            binding: self.binding.clone(),
+            name: name.to_string(),
+            labels: HashMap::new(),
+        }
+    }
+}
+
+struct Instrument {
+    binding: String,
+    name: String,
+    labels: HashMap<String, String>,
+}
+
+impl Instrument {
+    fn record(&self, kind: MetricKind, value: f64) -> HandlerResult<()> {
+        host(&self.binding).record(&MetricRecord {
+            name: self.name.clone(),
+            kind,
+            value,
+            labels: self.labels.clone(),
+        })
+    }
+}
+
+/// A monotonically increasing count, such as the number of requests served
+pub struct Counter(Instrument);
+
+impl Counter {
+    /// Adds a label to every measurement recorded by this counter
+    pub fn label(mut self, name: &str, value: &str) -> Self {
+        self.0.labels.insert(name.to_string(), value.to_string());
+        self
+    }
+
+    /// Increases the count by the given amount
+    pub fn inc(&self, n: u64) -> HandlerResult<()> {
+        self.0.record(MetricKind::Counter, n as f64)
+    }
+}
+
+/// A value that can go up and down, such as a queue depth
+pub struct Gauge(Instrument);
+
+impl Gauge {
+    /// Adds a label to every measurement recorded by this gauge
+    pub fn label(mut self, name: &str, value: &str) -> Self {
+        self.0.labels.insert(name.to_string(), value.to_string());
+        self
+    }
+
+    /// Sets the current value
+    pub fn set(&self, value: f64) -> HandlerResult<()> {
+        self.0.record(MetricKind::Gauge, value)
+    }
+
+    /// Adjusts the current value by the given (possibly negative) amount
+    pub fn add(&self, delta: f64) -> HandlerResult<()> {
+        self.0.record(MetricKind::GaugeDelta, delta)
+    }
+}
+
+/// A distribution of values, such as request latencies or payload sizes
+pub struct Histogram(Instrument);
+
+impl Histogram {
+    /// Adds a label to every measurement recorded by this histogram
+    pub fn label(mut self, name: &str, value: &str) -> Self {
+        self.0.labels.insert(name.to_string(), value.to_string());
+        self
+    }
+
+    /// Records a single observation
+    pub fn observe(&self, value: f64) -> HandlerResult<()> {
+        self.0.record(MetricKind::Histogram, value)
+    }
+
+    /// Runs a function and observes the number of milliseconds it took, as measured by the
+    /// host's monotonic clock. The observation is recorded whether or not the function
+    /// succeeds, and the function's result is returned unchanged: if the host has no clock,
+    /// or the observation can't be recorded, the function still runs and nothing is observed
+    pub fn time<T, F>(&self, f: F) -> HandlerResult<T>
+    where
+        F: FnOnce() -> HandlerResult<T>,
+    {
+        let stopwatch = Stopwatch::start().ok();
+        let result = f();
+        if let Some(stopwatch) = stopwatch {
+            if let Err(e) = stopwatch
+                .elapsed_ns()
+                .and_then(|ns| self.observe(ns as f64 / 1_000_000.0))
+            {
+                log::debug!("Failed to record timing: {}", e);
+            }
+        }
+        result
+    }
+}
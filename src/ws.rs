@@ -0,0 +1,272 @@
+//! # WebSockets
+//!
+//! This module contains the types delivered to actors bound to the `wascc:websocket`
+//! capability provider as clients connect, send frames and disconnect, and the client
+//! through which actors push frames back to connected clients, either to a single session
+//! or to every session that has joined a topic
+//!
+//! ```
+//! extern crate wascc_actor as actor;
+//! use actor::prelude::*;
+//! use actor::ws::{ConnectEvent, DisconnectEvent, MessageEvent};
+//!
+//! actor_handlers! {
+//!     ws::OP_SOCKET_CONNECTED => connected,
+//!     ws::OP_SOCKET_MESSAGE => message,
+//!     ws::OP_SOCKET_DISCONNECTED => disconnected
+//! }
+//!
+//! fn connected(event: ConnectEvent) -> HandlerResult<()> {
+//!     ws::default().join(&event.session_id, "lobby")
+//! }
+//!
+//! fn message(event: MessageEvent) -> HandlerResult<()> {
+//!     let text = event.text().ok_or("expected a text frame")?;
+//!     ws::default().broadcast_text("lobby", text)
+//! }
+//!
+//! fn disconnected(event: DisconnectEvent) -> HandlerResult<()> {
+//!     log::info!("Session {} closed: {}", event.session_id, event.reason);
+//!     Ok(())
+//! }
+//! # fn main() {}
+//! ```
+
+use serde::de::DeserializeOwned;
+use serde_derive::{Deserialize, Serialize};
+use std::collections::HashMap;
+use wapc_guest::host_call;
+use wascc_codec::serialize;
+
+use crate::HandlerResult;
+
+/// The reserved capability ID for WebSocket functionality
+pub const CAPID_WEBSOCKET: &str = "wascc:websocket";
+
+/// Capability provider sends a `ConnectEvent` to the actor when a client connects, receives
+/// nothing back. Returning an error from the handler rejects the connection
+pub const OP_SOCKET_CONNECTED: &str = "SocketConnected";
+/// Capability provider sends a `MessageEvent` to the actor when a client sends a frame,
+/// receives nothing back
+pub const OP_SOCKET_MESSAGE: &str = "SocketMessage";
+/// Capability provider sends a `DisconnectEvent` to the actor when a session ends, receives
+/// nothing back
+pub const OP_SOCKET_DISCONNECTED: &str = "SocketDisconnected";
+
+/// Guest sends a `Frame` to the capability provider, receives nothing back
+pub const OP_SEND_FRAME: &str = "SendFrame";
+/// Guest sends a `TopicFrame` to the capability provider, receives nothing back
+pub const OP_BROADCAST: &str = "Broadcast";
+/// Guest sends a `TopicMembership` to the capability provider, receives nothing back
+pub const OP_JOIN_TOPIC: &str = "JoinTopic";
+/// Guest sends a `TopicMembership` to the capability provider, receives nothing back
+pub const OP_LEAVE_TOPIC: &str = "LeaveTopic";
+/// Guest sends a `CloseSession` to the capability provider, receives nothing back
+pub const OP_CLOSE_SESSION: &str = "CloseSession";
+
+/// A client has connected
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ConnectEvent {
+    /// The ID of the new session
+    pub session_id: String,
+    /// The path of the upgrade request
+    pub path: String,
+    /// The query string of the upgrade request
+    #[serde(default)]
+    pub query_string: String,
+    /// The headers of the upgrade request
+    #[serde(default)]
+    pub header: HashMap<String, String>,
+}
+
+/// A client has sent a frame
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct MessageEvent {
+    /// The ID of the session
+    pub session_id: String,
+    /// The contents of the frame
+    #[serde(with = "serde_bytes")]
+    #[serde(default)]
+    pub payload: Vec<u8>,
+    /// Indicates a text frame, as opposed to a binary frame
+    pub is_text: bool,
+}
+
+impl MessageEvent {
+    /// The contents of a text frame, or `None` for a binary frame
+    pub fn text(&self) -> Option<&str> {
+        if self.is_text {
+            std::str::from_utf8(&self.payload).ok()
+        } else {
+            None
+        }
+    }
+
+    /// Deserializes the contents of the frame from JSON
+    pub fn json<T: DeserializeOwned>(&self) -> HandlerResult<T> {
+        Ok(serde_json::from_slice(&self.payload)?)
+    }
+}
+
+/// A session has ended
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DisconnectEvent {
+    /// The ID of the session
+    pub session_id: String,
+    /// The WebSocket close code
+    #[serde(default)]
+    pub code: u16,
+    /// The reason given for closing, if any
+    #[serde(default)]
+    pub reason: String,
+}
+
+/// A frame sent to a single session
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Frame {
+    /// The ID of the session
+    pub session_id: String,
+    /// The contents of the frame
+    #[serde(with = "serde_bytes")]
+    pub payload: Vec<u8>,
+    /// Send as a text frame rather than a binary frame
+    pub is_text: bool,
+}
+
+/// A frame sent to every session that has joined a topic
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TopicFrame {
+    /// The topic
+    pub topic: String,
+    /// The contents of the frame
+    #[serde(with = "serde_bytes")]
+    pub payload: Vec<u8>,
+    /// Send as a text frame rather than a binary frame
+    pub is_text: bool,
+}
+
+/// Adds a session to or removes it from a topic
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TopicMembership {
+    /// The ID of the session
+    pub session_id: String,
+    /// The topic
+    pub topic: String,
+}
+
+/// A request to close a session
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CloseSession {
+    /// The ID of the session
+    pub session_id: String,
+    /// The WebSocket close code
+    pub code: u16,
+    /// The reason given for closing
+    pub reason: String,
+}
+
+/// An abstraction around a host runtime capability for WebSocket sessions
+pub struct WebSocketHostBinding {
+    binding: String,
+}
+
+impl Default for WebSocketHostBinding {
+    fn default() -> Self {
+        WebSocketHostBinding {
+            binding: "default".to_string(),
+        }
+    }
+}
+
+/// Creates a named host binding for the WebSocket capability
+pub fn host(binding: &str) -> WebSocketHostBinding {
+    WebSocketHostBinding {
+        binding: binding.to_string(),
+    }
+}
+
+/// Creates the default host binding for the WebSocket capability
+pub fn default() -> WebSocketHostBinding {
+    WebSocketHostBinding::default()
+}
+
+impl WebSocketHostBinding {
+    /// Sends a binary frame to a session
+    pub fn send(&self, session_id: &str, payload: &[u8]) -> HandlerResult<()> {
+        self.send_frame(session_id, payload.to_vec(), false)
+    }
+
+    /// Sends a text frame to a session
+    pub fn send_text(&self, session_id: &str, text: &str) -> HandlerResult<()> {
+        self.send_frame(session_id, text.as_bytes().to_vec(), true)
+    }
+
+    /// Sends a binary frame to every session that has joined a topic
+    pub fn broadcast(&self, topic: &str, payload: &[u8]) -> HandlerResult<()> {
+        self.broadcast_frame(topic, payload.to_vec(), false)
+    }
+
+    /// Sends a text frame to every session that has joined a topic
+    pub fn broadcast_text(&self, topic: &str, text: &str) -> HandlerResult<()> {
+        self.broadcast_frame(topic, text.as_bytes().to_vec(), true)
+    }
+
+    /// Adds a session to a topic, so that it receives the topic's broadcasts. Sessions
+    /// leave their topics automatically when they disconnect
+    pub fn join(&self, session_id: &str, topic: &str) -> HandlerResult<()> {
+        self.call(OP_JOIN_TOPIC, membership(session_id, topic))
+    }
+
+    /// Removes a session from a topic
+    pub fn leave(&self, session_id: &str, topic: &str) -> HandlerResult<()> {
+        self.call(OP_LEAVE_TOPIC, membership(session_id, topic))
+    }
+
+    /// Closes a session with the given close code (such as 1000 for a normal closure)
+    pub fn close(&self, session_id: &str, code: u16, reason: &str) -> HandlerResult<()> {
+        let cmd = CloseSession {
+            session_id: session_id.to_string(),
+            code,
+            reason: reason.to_string(),
+        };
+        self.call(OP_CLOSE_SESSION, cmd)
+    }
+
+    fn send_frame(&self, session_id: &str, payload: Vec<u8>, is_text: bool) -> HandlerResult<()> {
+        let cmd = Frame {
+            session_id: session_id.to_string(),
+            payload,
+            is_text,
+        };
+        self.call(OP_SEND_FRAME, cmd)
+    }
+
+    fn broadcast_frame(&self, topic: &str, payload: Vec<u8>, is_text: bool) -> HandlerResult<()> {
+        let cmd = TopicFrame {
+            topic: topic.to_string(),
+            payload,
+            is_text,
+        };
+        self.call(OP_BROADCAST, cmd)
+    }
+
+    fn call<T: serde::Serialize>(&self, operation: &str, cmd: T) -> HandlerResult<()> {
+        host_call(&self.binding, CAPID_WEBSOCKET, operation, &serialize(cmd)?)
+            .map(|_v| ())
+            .map_err(|e| e.into())
+    }
+}
+
+fn membership(session_id: &str, topic: &str) -> TopicMembership {
+    TopicMembership {
+        session_id: session_id.to_string(),
+        topic: topic.to_string(),
+    }
+}